@@ -0,0 +1,208 @@
+use crate::cipher::CipherBase;
+use crate::polyalphabetic::PolyalphabeticCipher;
+use log::warn;
+use std::io;
+
+/// 宽容模式下替代无法解析的数字组的字符。
+const PLACEHOLDER: char = '?';
+
+/// A1Z26密码：把每个字母写成它在字母表中从1开始的序号，例如 `ABC` 写成 `1 2 3`。
+pub struct A1Z26Cipher<'a> {
+    base: CipherBase<'a>,
+    /// 数字之间的分隔符。
+    separator: String,
+    /// 可选的多表密码层，写成序号之前先按关键词偏移，相当于对序号做维吉尼亚加密。
+    key: Option<PolyalphabeticCipher<'a>>,
+    /// 宽容模式：无法解析的数字组用占位符代替并记录位置，而不是报错。
+    tolerant: bool,
+    decrypt: bool,
+}
+
+impl<'a> A1Z26Cipher<'a> {
+    pub fn new(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        separator: &str,
+        decrypt: bool,
+    ) -> Self {
+        A1Z26Cipher {
+            base: CipherBase::new(alphabet, input_file, output_file),
+            separator: separator.to_string(),
+            key: None,
+            tolerant: false,
+            decrypt,
+        }
+    }
+
+    /// 设置是否使用宽容模式解析序号。
+    pub fn set_tolerant(&mut self, tolerant: bool) {
+        self.tolerant = tolerant;
+    }
+
+    /// 设置偏移序号用的关键词，关键词无效时返回错误。
+    pub fn set_keyword(&mut self, keyword: &str) -> io::Result<()> {
+        self.key = Some(PolyalphabeticCipher::new(
+            self.base.alphabet,
+            "",
+            "",
+            keyword,
+            self.decrypt,
+        )?);
+        Ok(())
+    }
+
+    /// 在内存中加密一段已清理的文本：先按关键词偏移，再写成序号。
+    pub fn encrypt_string(&self, text: &str) -> String {
+        match &self.key {
+            Some(key) => self.encode_string(&key.encrypt_string(text)),
+            None => self.encode_string(text),
+        }
+    }
+
+    /// 在内存中解密一串序号：先还原成字母，再按关键词反向偏移。
+    pub fn decrypt_string(&self, text: &str) -> io::Result<String> {
+        let letters = self.decode_string(text)?;
+        let Some(key) = &self.key else {
+            return Ok(letters);
+        };
+
+        // 占位符先换成字母表中的字符参与解密，保证后面字符的密钥位置不变，解密后再换回来
+        let first = self.base.alphabet.chars().next().unwrap_or(PLACEHOLDER);
+        let filled: String = letters
+            .chars()
+            .map(|c| if c == PLACEHOLDER { first } else { c })
+            .collect();
+        Ok(letters
+            .chars()
+            .zip(key.encrypt_string(&filled).chars())
+            .map(|(original, decrypted)| {
+                if original == PLACEHOLDER {
+                    PLACEHOLDER
+                } else {
+                    decrypted
+                }
+            })
+            .collect())
+    }
+
+    /// 把已清理的文本转换成以分隔符连接的序号。
+    pub fn encode_string(&self, text: &str) -> String {
+        text.chars()
+            .filter_map(|ch| self.base.alphabet.chars().position(|c| c == ch))
+            .map(|idx| (idx + 1).to_string())
+            .collect::<Vec<String>>()
+            .join(&self.separator)
+    }
+
+    /// 把序号还原成字母。序号之间可以用分隔符或任意空白隔开，多位数按整体解析。
+    /// 宽容模式下无法解析的数字组变成 `?`，否则直接报错。
+    pub fn decode_string(&self, text: &str) -> io::Result<String> {
+        let letters: Vec<char> = self.base.alphabet.chars().collect();
+        text.split(|c: char| c.is_whitespace() || self.separator.contains(c))
+            .filter(|token| !token.is_empty())
+            .enumerate()
+            .map(|(i, token)| {
+                let letter = token
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=letters.len()).contains(n))
+                    .map(|n| letters[n - 1]);
+                match letter {
+                    Some(letter) => Ok(letter),
+                    None if self.tolerant => {
+                        warn!("Undecodable group {token:?} at position {i}");
+                        Ok(PLACEHOLDER)
+                    }
+                    None => Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Invalid A1Z26 number {token:?} at position {i}"),
+                    )),
+                }
+            })
+            .collect()
+    }
+
+    pub fn base(&self) -> &CipherBase<'a> {
+        &self.base
+    }
+
+    pub fn base_mut(&mut self) -> &mut CipherBase<'a> {
+        &mut self.base
+    }
+
+    pub fn encrypt(&mut self) -> io::Result<()> {
+        // 解码时数字不经过 clean_text，无法先撤销置换
+        self.base.reject_transpose("A1Z26")?;
+        self.base.get_text()?;
+
+        if self.decrypt {
+            self.base.encrypted_text = self.decrypt_string(&self.base.plain_text)?;
+        } else {
+            self.base.clean_text();
+            self.base.encrypted_text = self.encrypt_string(&self.base.plain_text);
+        }
+
+        self.base.save_file()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    #[test]
+    fn test_a1z26_round_trip() {
+        let cipher = A1Z26Cipher::new(ALPHABET, "", "", " ", false);
+        assert_eq!(cipher.encode_string("ABC"), "1 2 3");
+        assert_eq!(cipher.decode_string("1 2 3").unwrap(), "ABC");
+        assert_eq!(cipher.encode_string("XYZ"), "24 25 26");
+        assert_eq!(cipher.decode_string("24 25\n26\n").unwrap(), "XYZ");
+    }
+
+    #[test]
+    fn test_a1z26_with_keyword() {
+        let mut encrypt = A1Z26Cipher::new(ALPHABET, "", "", " ", false);
+        encrypt.set_keyword("B").unwrap();
+        // 关键词B偏移2：A->C, B->D, Y->A
+        assert_eq!(encrypt.encrypt_string("ABY"), "3 4 1");
+
+        let mut encrypt = A1Z26Cipher::new(ALPHABET, "", "", " ", false);
+        encrypt.set_keyword("LEMON").unwrap();
+        let mut decrypt = A1Z26Cipher::new(ALPHABET, "", "", " ", true);
+        decrypt.set_keyword("LEMON").unwrap();
+
+        let numbers = encrypt.encrypt_string("ATTACKATDAWN");
+        assert_ne!(numbers, encrypt.encode_string("ATTACKATDAWN"));
+        assert_eq!(decrypt.decrypt_string(&numbers).unwrap(), "ATTACKATDAWN");
+    }
+
+    #[test]
+    fn test_a1z26_tolerant() {
+        let mut cipher = A1Z26Cipher::new(ALPHABET, "", "", " ", true);
+        // 末尾残留的单个数字0无法解析
+        assert!(cipher.decode_string("8 5 12 12 15 0").is_err());
+
+        cipher.set_tolerant(true);
+        assert_eq!(cipher.decode_string("8 5 12 12 15 0").unwrap(), "HELLO?");
+        assert_eq!(cipher.decode_string("8 5 1x 12 15").unwrap(), "HE?LO");
+
+        // 带关键词时，损坏的数字组不影响后面字符的密钥位置
+        cipher.set_keyword("B").unwrap();
+        assert_eq!(cipher.decrypt_string("10 7 0 14 17").unwrap(), "HE?LO");
+    }
+
+    #[test]
+    fn test_a1z26_separator() {
+        let cipher = A1Z26Cipher::new(ALPHABET, "", "", ",", false);
+        assert_eq!(cipher.encode_string("HELLO"), "8,5,12,12,15");
+        assert_eq!(cipher.decode_string("8,5, 12,12,15").unwrap(), "HELLO");
+
+        // 超出范围或不是数字时报错
+        assert!(cipher.decode_string("8,27").is_err());
+        assert!(cipher.decode_string("8,0").is_err());
+        assert!(cipher.decode_string("8,X").is_err());
+    }
+}
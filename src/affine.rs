@@ -0,0 +1,162 @@
+use crate::cipher::{Cipher, CipherBase};
+use crate::error::{CipherError, Result};
+use std::io;
+
+/// 仿射密码：序号为 `x` 的字符加密为序号 `(a*x + b) mod m` 的字符，`m` 是字母表长度。
+/// 解密用 `a` 的模逆元：`x = a⁻¹ * (y - b) mod m`，所以 `a` 必须与 `m` 互素。
+/// `a` 为1时就是凯撒密码。
+pub struct AffineCipher<'a> {
+    base: CipherBase<'a>,
+    /// 已对字母表长度取模，运算时不会溢出。
+    a: i32,
+    /// 已对字母表长度取模。
+    b: i32,
+    /// `a` 模字母表长度的逆元。
+    a_inverse: i32,
+    decrypt: bool,
+}
+
+/// 最大公约数。
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+/// `a` 模 `m` 的逆元，`a` 与 `m` 不互素时不存在。
+fn mod_inverse(a: i32, m: i32) -> Option<i32> {
+    let a = a.rem_euclid(m);
+    (0..m).find(|&x| (a * x - 1).rem_euclid(m) == 0)
+}
+
+impl<'a> AffineCipher<'a> {
+    /// `a` 与字母表长度不互素时变换不可逆，返回错误。
+    pub fn new(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        a: i32,
+        b: i32,
+        decrypt: bool,
+    ) -> Result<Self> {
+        let base = CipherBase::new(alphabet, input_file, output_file);
+        let m = base.letters.len() as i32;
+        let a_inverse = mod_inverse(a, m).ok_or_else(|| {
+            CipherError::InvalidInput(format!(
+                "a = {a} shares the factor {} with the alphabet length {m}, so it cannot be inverted",
+                gcd(a, m)
+            ))
+        })?;
+        Ok(AffineCipher {
+            base,
+            a: a.rem_euclid(m),
+            b: b.rem_euclid(m),
+            a_inverse,
+            decrypt,
+        })
+    }
+
+    /// 在内存中加密（或按构造时的设置解密）一段文本。字母表之外的字符原样返回。
+    pub fn encrypt_string(&self, text: &str) -> String {
+        let m = self.base.letters.len() as i32;
+        text.chars()
+            .map(|ch| match self.base.index_of(ch) {
+                Some(x) => {
+                    let y = if self.decrypt {
+                        self.a_inverse * (x as i32 - self.b)
+                    } else {
+                        self.a * x as i32 + self.b
+                    };
+                    self.base.letter_at(y.rem_euclid(m) as usize)
+                }
+                None => ch,
+            })
+            .collect()
+    }
+
+    pub fn base(&self) -> &CipherBase<'a> {
+        &self.base
+    }
+
+    pub fn base_mut(&mut self) -> &mut CipherBase<'a> {
+        &mut self.base
+    }
+
+    pub fn encrypt(&mut self) -> io::Result<()> {
+        self.base.get_text()?;
+        self.base.clean_text();
+
+        self.base.encrypted_text = self.encrypt_string(&self.base.plain_text);
+        self.base.assert_length_preserved();
+
+        self.base.save_file()
+    }
+}
+
+impl Cipher for AffineCipher<'_> {
+    fn encrypt(&mut self) -> io::Result<()> {
+        let decrypt = std::mem::replace(&mut self.decrypt, false);
+        let result = AffineCipher::encrypt(self);
+        self.decrypt = decrypt;
+        result
+    }
+
+    fn decrypt(&mut self) -> io::Result<()> {
+        let decrypt = std::mem::replace(&mut self.decrypt, true);
+        let result = AffineCipher::encrypt(self);
+        self.decrypt = decrypt;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    #[test]
+    fn test_affine_encrypt() {
+        // a=5, b=8：A(0)→I(8)，F(5)→H(33 mod 26 = 7)
+        let cipher = AffineCipher::new(ALPHABET, "", "", 5, 8, false).unwrap();
+        assert_eq!(cipher.encrypt_string("AFFINECIPHER"), "IHHWVCSWFRCP");
+    }
+
+    #[test]
+    fn test_affine_round_trip() {
+        let plaintext = "DEFENDTHEEASTWALLOFTHECASTLE";
+        for (a, b) in [(1, 3), (3, 0), (7, 25), (25, -4), (-5, 11)] {
+            let encrypt = AffineCipher::new(ALPHABET, "", "", a, b, false).unwrap();
+            let decrypt = AffineCipher::new(ALPHABET, "", "", a, b, true).unwrap();
+            assert_eq!(
+                decrypt.encrypt_string(&encrypt.encrypt_string(plaintext)),
+                plaintext,
+                "a={a}, b={b}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_affine_large_parameters() {
+        // i32::MAX ≡ 23 (mod 26)，i32::MIN ≡ 2 (mod 26)，结果与取模后的参数相同
+        let large = AffineCipher::new(ALPHABET, "", "", i32::MAX, i32::MIN, false).unwrap();
+        let small = AffineCipher::new(ALPHABET, "", "", 23, 2, false).unwrap();
+        let plaintext = "DEFENDTHEEASTWALLOFTHECASTLE";
+        let ciphertext = large.encrypt_string(plaintext);
+        assert_eq!(ciphertext, small.encrypt_string(plaintext));
+
+        let decrypt = AffineCipher::new(ALPHABET, "", "", i32::MAX, i32::MIN, true).unwrap();
+        assert_eq!(decrypt.encrypt_string(&ciphertext), plaintext);
+    }
+
+    #[test]
+    fn test_affine_rejects_non_coprime_a() {
+        for a in [0, 2, 13, 26, -4] {
+            let err = AffineCipher::new(ALPHABET, "", "", a, 1, false)
+                .err()
+                .expect("a must be coprime with 26");
+            assert!(matches!(err, CipherError::InvalidInput(_)), "{err}");
+        }
+        // 对长度为10的字母表，5不可逆而3可逆
+        assert!(AffineCipher::new("0123456789", "", "", 5, 0, false).is_err());
+        assert!(AffineCipher::new("0123456789", "", "", 3, 0, false).is_ok());
+    }
+}
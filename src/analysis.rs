@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead};
+
+/// 英文字母A到Z的标准出现频率。
+pub const ENGLISH_FREQUENCIES: [f64; 26] = [
+    0.08167, 0.01492, 0.02782, 0.04253, 0.12702, 0.02228, 0.02015, 0.06094, 0.06966, 0.00153,
+    0.00772, 0.04025, 0.02406, 0.06749, 0.07507, 0.01929, 0.00095, 0.05987, 0.06327, 0.09056,
+    0.02758, 0.00978, 0.02360, 0.00150, 0.01974, 0.00074,
+];
+
+/// 计算文本字母分布与英文标准分布之间的卡方值，值越小越接近英文。
+/// 只统计A到Z（不区分大小写），文本中没有字母时返回正无穷。
+pub fn chi_squared(text: &str) -> f64 {
+    let mut counts = [0usize; 26];
+    for ch in text.chars().filter(|c| c.is_ascii_alphabetic()) {
+        counts[(ch.to_ascii_uppercase() as u8 - b'A') as usize] += 1;
+    }
+
+    let total: usize = counts.iter().sum();
+    if total == 0 {
+        return f64::INFINITY;
+    }
+
+    counts
+        .iter()
+        .zip(ENGLISH_FREQUENCIES.iter())
+        .map(|(&observed, &frequency)| {
+            let expected = frequency * total as f64;
+            (observed as f64 - expected).powi(2) / expected
+        })
+        .sum()
+}
+
+/// 统计文本中每个字母表字符出现的次数，字母表之外的字符不计入。
+pub fn letter_counts(text: &str, alphabet: &str) -> HashMap<char, usize> {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for ch in text.chars().filter(|c| alphabet.contains(*c)) {
+        *counts.entry(ch).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// 与 `letter_counts` 相同，但逐行读取输入并累加计数，不需要把整个文件载入内存。
+/// 每行先经过 `clean` 处理，传入 `CipherBase::clean` 即可统计与加密时相同的字符。
+pub fn letter_counts_streaming<R: BufRead>(
+    mut reader: R,
+    alphabet: &str,
+    clean: impl Fn(&str) -> String,
+) -> io::Result<HashMap<char, usize>> {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        for ch in clean(&line).chars().filter(|c| alphabet.contains(*c)) {
+            *counts.entry(ch).or_insert(0) += 1;
+        }
+        line.clear();
+    }
+    Ok(counts)
+}
+
+/// 把字符计数换算成频率，所有频率之和为1。没有任何字符时返回空表。
+pub fn frequencies_from_counts(counts: &HashMap<char, usize>) -> HashMap<char, f64> {
+    let total: usize = counts.values().sum();
+    counts
+        .iter()
+        .filter(|&(_, &n)| n > 0)
+        .map(|(&ch, &n)| (ch, n as f64 / total as f64))
+        .collect()
+}
+
+/// 文本中每个字母表字符的出现频率，字母表之外的字符不计入。
+pub fn letter_frequencies(text: &str, alphabet: &str) -> HashMap<char, f64> {
+    frequencies_from_counts(&letter_counts(text, alphabet))
+}
+
+/// 频率表：每行一个字符、出现次数和百分比，按次数从多到少排列，次数相同时按字母表顺序。
+pub fn frequency_table(counts: &HashMap<char, usize>, alphabet: &str) -> String {
+    let frequencies = frequencies_from_counts(counts);
+    let mut rows: Vec<(char, usize)> = alphabet
+        .chars()
+        .filter_map(|ch| counts.get(&ch).filter(|&&n| n > 0).map(|&n| (ch, n)))
+        .collect();
+    rows.sort_by_key(|&(_, n)| std::cmp::Reverse(n));
+    rows.iter()
+        .map(|(ch, n)| format!("{ch} {n:>8} {:>7.2}%\n", frequencies[ch] * 100.0))
+        .collect()
+}
+
+/// 根据字符计数计算重合指数，即随机取两个字符恰好相同的概率。
+pub fn index_of_coincidence_from_counts(counts: &HashMap<char, usize>) -> f64 {
+    let total: usize = counts.values().sum();
+    if total < 2 {
+        return 0.0;
+    }
+    let pairs: usize = counts.values().map(|&n| n * n.saturating_sub(1)).sum();
+    pairs as f64 / (total * (total - 1)) as f64
+}
+
+/// 计算文本的重合指数，只统计字母表中的字符。英文约为0.066，均匀随机文本约为1/26。
+pub fn index_of_coincidence(text: &str, alphabet: &str) -> f64 {
+    index_of_coincidence_from_counts(&letter_counts(text, alphabet))
+}
+
+/// 估计多表代换密码的密钥长度。对每个候选长度 `n`，把文本中的字母按位置模 `n` 分成 `n` 组，
+/// 计算各组重合指数的平均值：长度正确时每组都是单表代换，平均值接近明文的重合指数。
+/// 返回按平均重合指数从高到低排列的候选长度，每组至少要有两个字母，所以 `n` 不超过字母数的一半。
+pub fn key_length_candidates(text: &str, alphabet: &str, max_len: usize) -> Vec<(usize, f64)> {
+    let letters: Vec<char> = text.chars().filter(|&c| alphabet.contains(c)).collect();
+    let mut candidates: Vec<(usize, f64)> = (1..=max_len.min(letters.len() / 2))
+        .map(|n| {
+            let total: f64 = (0..n)
+                .map(|i| {
+                    let coset: String = letters.iter().skip(i).step_by(n).collect();
+                    index_of_coincidence(&coset, alphabet)
+                })
+                .sum();
+            (n, total / n as f64)
+        })
+        .collect();
+    // 稳定排序，重合指数相同时较短的长度在前
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+    candidates
+}
+
+/// 统计文本中每个长度为 `n` 的连续片段出现的次数。文本应先经过清理；`n` 为0或比文本长时返回空表。
+pub fn ngram_frequency(text: &str, n: usize) -> HashMap<String, usize> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    if n == 0 {
+        return counts;
+    }
+    for window in chars.windows(n) {
+        *counts.entry(window.iter().collect()).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// 英文中最常见的三字母组合及其出现次数（以千计），取自大规模英文语料。
+pub const ENGLISH_TRIGRAMS: [(&str, u32); 40] = [
+    ("THE", 77534),
+    ("AND", 30997),
+    ("ING", 30679),
+    ("ENT", 17902),
+    ("ION", 17769),
+    ("HER", 15277),
+    ("FOR", 14686),
+    ("THA", 14222),
+    ("NTH", 14115),
+    ("INT", 13974),
+    ("ERE", 13344),
+    ("TIO", 13334),
+    ("TER", 12748),
+    ("EST", 12720),
+    ("ERS", 12672),
+    ("ATI", 12526),
+    ("HAT", 11929),
+    ("ATE", 11713),
+    ("ALL", 11633),
+    ("ETH", 11347),
+    ("HES", 11204),
+    ("VER", 11164),
+    ("HIS", 10987),
+    ("OFT", 10944),
+    ("ITH", 10892),
+    ("FTH", 10863),
+    ("STH", 10673),
+    ("OTH", 10628),
+    ("RES", 10544),
+    ("ONT", 10521),
+    ("DTH", 10129),
+    ("ARE", 10041),
+    ("REA", 9876),
+    ("EAR", 9583),
+    ("WAS", 9547),
+    ("SIN", 9411),
+    ("STO", 9394),
+    ("TTH", 9315),
+    ("STA", 9292),
+    ("THI", 9190),
+];
+
+/// 按英文三字母组合的对数概率给文本打分，值越大越像英文。
+/// 表中没有的组合按一个很小的概率计算，文本不足三个字母时返回负无穷。
+pub fn trigram_log_probability(text: &str) -> f64 {
+    let letters: Vec<char> = text
+        .chars()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if letters.len() < 3 {
+        return f64::NEG_INFINITY;
+    }
+
+    // 表只收录了最常见的组合，未收录组合的总量按表中总量的十倍估计
+    let table: HashMap<&str, u32> = ENGLISH_TRIGRAMS.into_iter().collect();
+    let total = table.values().map(|&n| n as f64).sum::<f64>() * 11.0;
+    let floor = (0.01 / total).log10();
+    letters
+        .windows(3)
+        .map(|window| {
+            let trigram: String = window.iter().collect();
+            table
+                .get(trigram.as_str())
+                .map_or(floor, |&n| (n as f64 / total).log10())
+        })
+        .sum()
+}
+
+/// 破解时给候选明文打分的方式。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scoring {
+    /// 单字母频率的卡方值。
+    ChiSquared,
+    /// 三字母组合的对数概率，对短文本比卡方值可靠。
+    Trigram,
+}
+
+impl Scoring {
+    /// 计算候选明文的得分，值越小越像英文。
+    pub fn score(self, text: &str) -> f64 {
+        match self {
+            Scoring::ChiSquared => chi_squared(text),
+            Scoring::Trigram => -trigram_log_probability(text),
+        }
+    }
+
+    /// 在命令行和输出中使用的名称。
+    pub fn name(self) -> &'static str {
+        match self {
+            Scoring::ChiSquared => "chi2",
+            Scoring::Trigram => "trigram",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chi_squared_prefers_english() {
+        let english = "THEQUICKBROWNFOXJUMPSOVERTHELAZYDOGANDRUNSINTOTHEFOREST";
+        let shifted = "WKHTXLFNEURZQIRAMXPSVRYHUWKHODCBGRJDQGUXQVLQWRWKHIRUHVW";
+        assert!(chi_squared(english) < chi_squared(shifted));
+        assert_eq!(chi_squared("1234"), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_letter_counts_streaming() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let text = "THE QUICK BROWN FOX\nJUMPS OVER\n\nTHE LAZY DOG, 42 TIMES";
+        let streamed =
+            letter_counts_streaming(io::Cursor::new(text), alphabet, str::to_string).unwrap();
+        assert_eq!(streamed, letter_counts(text, alphabet));
+        assert_eq!(streamed.get(&'O'), Some(&4));
+        assert_eq!(
+            index_of_coincidence_from_counts(&streamed),
+            index_of_coincidence(text, alphabet)
+        );
+    }
+
+    #[test]
+    fn test_letter_frequencies() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let frequencies = letter_frequencies("ATTACK AT DAWN!", alphabet);
+        let (&most, &frequency) = frequencies
+            .iter()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap();
+        assert_eq!(most, 'A');
+        assert!((frequency - 4.0 / 12.0).abs() < 1e-12);
+        assert!((frequencies.values().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!(letter_frequencies("1234", alphabet).is_empty());
+
+        // 频率表按次数排序，次数相同的T和A按字母表顺序
+        let table = frequency_table(&letter_counts("TTAAC", alphabet), alphabet);
+        let letters: Vec<char> = table
+            .lines()
+            .map(|line| line.chars().next().unwrap())
+            .collect();
+        assert_eq!(letters, ['A', 'T', 'C']);
+        assert!(table.starts_with("A        2   40.00%\n"));
+    }
+
+    #[test]
+    fn test_index_of_coincidence() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        assert_eq!(index_of_coincidence("AAAA", alphabet), 1.0);
+        assert_eq!(index_of_coincidence("ABCD", alphabet), 0.0);
+        // AABB：两对相同字符，共 4*3 种有序组合
+        assert!((index_of_coincidence("AABB", alphabet) - 4.0 / 12.0).abs() < 1e-12);
+        assert_eq!(index_of_coincidence("A", alphabet), 0.0);
+    }
+
+    #[test]
+    fn test_key_length_candidates() {
+        use crate::polyalphabetic::PolyalphabeticCipher;
+
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let plaintext = "ITWASTHEBESTOFTIMESITWASTHEWORSTOFTIMESITWASTHEAGEOFWISDOM\
+                         ITWASTHEAGEOFFOOLISHNESSITWASTHEEPOCHOFBELIEFITWASTHEEPOCH\
+                         OFINCREDULITYITWASTHESEASONOFLIGHTITWASTHESEASONOFDARKNESS\
+                         ITWASTHESPRINGOFHOPEITWASTHEWINTEROFDESPAIRWEHADEVERYTHING\
+                         BEFOREUSWEHADNOTHINGBEFOREUSWEWEREALLGOINGDIRECTTOHEAVEN\
+                         WEWEREALLGOINGDIRECTTHEOTHERWAY";
+        let cipher = PolyalphabeticCipher::new(alphabet, "", "", "LEMON", false).unwrap();
+        let ciphertext = cipher.encrypt_string(plaintext);
+
+        // 正确的长度排在前三，最好的候选是它的倍数
+        let candidates = key_length_candidates(&ciphertext, alphabet, 12);
+        assert_eq!(candidates.len(), 12);
+        let top: Vec<usize> = candidates.iter().take(3).map(|&(n, _)| n).collect();
+        assert!(top.contains(&5), "{candidates:?}");
+        assert_eq!(candidates[0].0 % 5, 0, "{candidates:?}");
+
+        // 太短的文本不产生每组少于两个字母的候选
+        assert_eq!(key_length_candidates("ABCDE", alphabet, 10).len(), 2);
+    }
+
+    #[test]
+    fn test_ngram_frequency() {
+        let bigrams = ngram_frequency("ABABC", 2);
+        assert_eq!(bigrams.get("AB"), Some(&2));
+        assert_eq!(bigrams.get("BA"), Some(&1));
+        assert_eq!(bigrams.get("BC"), Some(&1));
+        assert_eq!(bigrams.values().sum::<usize>(), 4);
+
+        let trigrams = ngram_frequency("THETHE", 3);
+        assert_eq!(trigrams.get("THE"), Some(&2));
+        assert!(ngram_frequency("AB", 3).is_empty());
+        assert!(ngram_frequency("AB", 0).is_empty());
+    }
+
+    #[test]
+    fn test_trigram_scoring_prefers_english() {
+        let english = "MEETMEATTHEHARBOUR";
+        let shifted = "PHHWPHDWWKHKDUERXU";
+        assert!(Scoring::Trigram.score(english) < Scoring::Trigram.score(shifted));
+        assert_eq!(trigram_log_probability("AB"), f64::NEG_INFINITY);
+    }
+}
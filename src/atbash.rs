@@ -0,0 +1,104 @@
+use crate::cipher::{Cipher, CipherBase};
+use std::io;
+
+/// 埃特巴什密码：字母表中第 `i` 个字符替换为倒数第 `i` 个，即 A↔Z、B↔Y……
+/// 没有密钥，加密和解密是同一个变换。
+pub struct AtbashCipher<'a> {
+    base: CipherBase<'a>,
+}
+
+impl<'a> AtbashCipher<'a> {
+    pub fn new(alphabet: &'a str, input_file: &'a str, output_file: &'a str) -> Self {
+        AtbashCipher {
+            base: CipherBase::new(alphabet, input_file, output_file),
+        }
+    }
+
+    /// 在内存中变换一段文本。字母表之外的字符原样返回。
+    pub fn encrypt_string(&self, text: &str) -> String {
+        self.base.atbash(text)
+    }
+
+    pub fn base(&self) -> &CipherBase<'a> {
+        &self.base
+    }
+
+    pub fn base_mut(&mut self) -> &mut CipherBase<'a> {
+        &mut self.base
+    }
+
+    pub fn encrypt(&mut self) -> io::Result<()> {
+        self.base.get_text()?;
+        self.base.clean_text();
+
+        self.base.encrypted_text = self.encrypt_string(&self.base.plain_text);
+        self.base.assert_length_preserved();
+
+        self.base.save_file()
+    }
+}
+
+impl Cipher for AtbashCipher<'_> {
+    fn encrypt(&mut self) -> io::Result<()> {
+        AtbashCipher::encrypt(self)
+    }
+
+    fn decrypt(&mut self) -> io::Result<()> {
+        AtbashCipher::encrypt(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    #[test]
+    fn test_atbash_encrypt() {
+        let cipher = AtbashCipher::new(ALPHABET, "", "");
+        assert_eq!(
+            cipher.encrypt_string(ALPHABET),
+            "ZYXWVUTSRQPONMLKJIHGFEDCBA"
+        );
+        assert_eq!(cipher.encrypt_string("WIZARD"), "DRAZIW");
+    }
+
+    #[test]
+    fn test_atbash_then_transpose_round_trip() {
+        use tempfile::NamedTempFile;
+
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        std::fs::write(input_path, "WEAREDISCOVERED").unwrap();
+
+        let mut cipher = AtbashCipher::new(ALPHABET, input_path, output_path);
+        cipher.base_mut().transpose = Some("ZEBRAS".to_string());
+        cipher.encrypt().unwrap();
+
+        // 埃特巴什没有解密方向，要明确指定先撤销置换
+        let mut cipher = AtbashCipher::new(ALPHABET, output_path, round_trip_path);
+        cipher.base_mut().transpose = Some("ZEBRAS".to_string());
+        cipher.base_mut().transpose_input = true;
+        cipher.encrypt().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(round_trip_path).unwrap(),
+            "WEAREDISCOVERED"
+        );
+    }
+
+    #[test]
+    fn test_atbash_is_an_involution() {
+        for alphabet in [ALPHABET, "ABCDE", "0123456789"] {
+            let cipher = AtbashCipher::new(alphabet, "", "");
+            assert_eq!(
+                cipher.encrypt_string(&cipher.encrypt_string(alphabet)),
+                alphabet
+            );
+        }
+    }
+}
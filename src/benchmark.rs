@@ -0,0 +1,149 @@
+use crate::a1z26::A1Z26Cipher;
+use crate::affine::AffineCipher;
+use crate::atbash::AtbashCipher;
+use crate::bifid::BifidCipher;
+use crate::caesar::CaesarCipher;
+use crate::checkerboard::StraddlingCheckerboard;
+use crate::columnar::ColumnarCipher;
+use crate::enigma::EnigmaMachine;
+use crate::foursquare::FourSquareCipher;
+use crate::playfair::PlayfairCipher;
+use crate::polyalphabetic::PolyalphabeticCipher;
+use crate::railfence::RailFenceCipher;
+use crate::scytale::ScytaleCipher;
+use crate::substitution::SubstitutionCipher;
+use crate::trifid::TrifidCipher;
+use crate::twosquare::{TwoSquareCipher, TwoSquareVariant};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::hint::black_box;
+use std::io;
+use std::time::{Duration, Instant};
+
+/// 一种密码的测速结果。
+pub struct BenchmarkResult {
+    pub name: &'static str,
+    pub chars: usize,
+    pub elapsed: Duration,
+}
+
+impl BenchmarkResult {
+    /// 每秒处理的字符数。耗时过短时按1纳秒计算，避免除以0。
+    pub fn rate(&self) -> f64 {
+        self.chars as f64 / self.elapsed.as_secs_f64().max(1e-9)
+    }
+}
+
+/// 用种子确定性地生成一段由字母表字符组成的输入。
+pub fn synthetic_input(alphabet: &str, size: usize, seed: u64) -> String {
+    let letters: Vec<char> = alphabet.chars().collect();
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..size)
+        .map(|_| letters[rng.random_range(0..letters.len())])
+        .collect()
+}
+
+/// 计时执行一次变换。
+fn time<F: FnOnce() -> String>(name: &'static str, chars: usize, f: F) -> BenchmarkResult {
+    let start = Instant::now();
+    black_box(f());
+    let elapsed = start.elapsed();
+    BenchmarkResult {
+        name,
+        chars,
+        elapsed,
+    }
+}
+
+/// 在同一段合成输入上依次测量各个文本密码的内存加密速度，不读写文件。
+/// 一次性密码本按字节处理文件，不在测量之列。
+pub fn run(alphabet: &str, size: usize, seed: u64) -> io::Result<Vec<BenchmarkResult>> {
+    let input = synthetic_input(alphabet, size, seed);
+    let reversed: String = alphabet.chars().rev().collect();
+
+    let caesar = CaesarCipher::new(alphabet, "", "", 3);
+    let affine = AffineCipher::new(alphabet, "", "", 5, 8, false)?;
+    let poly = PolyalphabeticCipher::new(alphabet, "", "", "LEMON", false)?;
+    let substitution = SubstitutionCipher::new(alphabet, "", "", &reversed, false)?;
+    let atbash = AtbashCipher::new(alphabet, "", "");
+    let railfence = RailFenceCipher::new(alphabet, "", "", 3, false)?;
+    let columnar = ColumnarCipher::new(alphabet, "", "", "ZEBRAS", false)?;
+    let playfair = PlayfairCipher::new(alphabet, "", "", "PLAYFAIR", false)?;
+    let bifid = BifidCipher::new(alphabet, "", "", "BIFID", 5, false)?;
+    let trifid = TrifidCipher::new(alphabet, "", "", "TRIFID", 5, false)?;
+    let foursquare = FourSquareCipher::new(alphabet, "", "", "EXAMPLE", "KEYWORD", false)?;
+    let twosquare = TwoSquareCipher::new(
+        alphabet,
+        "",
+        "",
+        "EXAMPLE",
+        "KEYWORD",
+        TwoSquareVariant::Horizontal,
+        false,
+    )?;
+    let a1z26 = A1Z26Cipher::new(alphabet, "", "", " ", false);
+    let checkerboard = StraddlingCheckerboard::new(alphabet, "", "", "", [2, 6], false)?;
+    let scytale = ScytaleCipher::new(alphabet, "", "", 7, false)?;
+    let mut enigma = EnigmaMachine::from_seed(alphabet, "", "", 3, seed)?;
+
+    Ok(vec![
+        time("caesar", size, || caesar.encrypt_string(&input)),
+        time("affine", size, || affine.encrypt_string(&input)),
+        time("poly", size, || poly.encrypt_string(&input)),
+        time("sub", size, || substitution.encrypt_string(&input)),
+        time("atbash", size, || atbash.encrypt_string(&input)),
+        time("railfence", size, || railfence.encrypt_string(&input)),
+        time("columnar", size, || columnar.encrypt_string(&input)),
+        time("playfair", size, || playfair.encrypt_string(&input)),
+        time("bifid", size, || bifid.encrypt_string(&input)),
+        time("trifid", size, || trifid.encrypt_string(&input)),
+        time("foursquare", size, || foursquare.encrypt_string(&input)),
+        time("twosquare", size, || twosquare.encrypt_string(&input)),
+        time("a1z26", size, || a1z26.encrypt_string(&input)),
+        time("checkerboard", size, || checkerboard.encode_string(&input)),
+        time("scytale", size, || scytale.encrypt_string(&input)),
+        time("enigma", size, || {
+            enigma
+                .encrypt_string(&input)
+                .expect("Stepping the rotors failed")
+        }),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    #[test]
+    fn test_synthetic_input_is_reproducible() {
+        let input = synthetic_input(ALPHABET, 100, 7);
+        assert_eq!(input.chars().count(), 100);
+        assert!(input.chars().all(|c| ALPHABET.contains(c)));
+        assert_eq!(input, synthetic_input(ALPHABET, 100, 7));
+        assert_ne!(input, synthetic_input(ALPHABET, 100, 8));
+    }
+
+    #[test]
+    fn test_benchmark_reports_positive_rates() {
+        let results = run(ALPHABET, 1000, 0).unwrap();
+        let names: Vec<&str> = results.iter().map(|result| result.name).collect();
+        for name in [
+            "sub",
+            "atbash",
+            "railfence",
+            "bifid",
+            "trifid",
+            "affine",
+            "playfair",
+            "columnar",
+        ] {
+            assert!(names.contains(&name), "{name} is not benchmarked");
+        }
+        assert_eq!(results.len(), 16);
+        for result in &results {
+            assert_eq!(result.chars, 1000);
+            assert!(result.rate() > 0.0, "{} has no positive rate", result.name);
+        }
+    }
+}
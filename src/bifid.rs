@@ -0,0 +1,153 @@
+use crate::cipher::{Cipher, CipherBase};
+use crate::square::{KeySquare, fractionate, locate, unfractionate};
+use std::io;
+
+/// 比菲德密码：把每个字母换成它在5x5方阵中的行号和列号，按周期分块，
+/// 每块先写出全部行号再写出全部列号，然后两两组合成新的字母。J并入I。
+pub struct BifidCipher<'a> {
+    base: CipherBase<'a>,
+    square: KeySquare,
+    /// 每块的字母数，为0时整段文本是一块。
+    period: usize,
+    decrypt: bool,
+}
+
+impl<'a> BifidCipher<'a> {
+    pub fn new(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        keyword: &str,
+        period: usize,
+        decrypt: bool,
+    ) -> io::Result<Self> {
+        Ok(BifidCipher {
+            base: CipherBase::new(alphabet, input_file, output_file),
+            square: KeySquare::new(alphabet, keyword, 5)?,
+            period,
+            decrypt,
+        })
+    }
+
+    /// 加密一块：行号序列后接列号序列，再两两组成坐标。
+    fn encrypt_block(&self, block: &[char]) -> Vec<char> {
+        fractionate(&self.coords(block))
+            .into_iter()
+            .map(|[row, col]| self.square.at(row, col))
+            .collect()
+    }
+
+    /// 解密一块：展开坐标后，前一半是原来的行号，后一半是列号。
+    fn decrypt_block(&self, block: &[char]) -> Vec<char> {
+        unfractionate(&self.coords(block))
+            .into_iter()
+            .map(|[row, col]| self.square.at(row, col))
+            .collect()
+    }
+
+    /// 一块字符在方阵中的坐标。
+    fn coords(&self, block: &[char]) -> Vec<[usize; 2]> {
+        block
+            .iter()
+            .map(|&c| {
+                let (row, col) = locate(&self.square, c);
+                [row, col]
+            })
+            .collect()
+    }
+
+    /// 在内存中加密（或按构造时的设置解密）一段文本。方阵以外的字符被去掉。
+    pub fn encrypt_string(&self, text: &str) -> String {
+        let letters: Vec<char> = text
+            .chars()
+            .map(|c| self.square.normalize(c))
+            .filter(|&c| self.square.position(c).is_some())
+            .collect();
+        let period = if self.period == 0 {
+            letters.len().max(1)
+        } else {
+            self.period
+        };
+        letters
+            .chunks(period)
+            .flat_map(|block| {
+                if self.decrypt {
+                    self.decrypt_block(block)
+                } else {
+                    self.encrypt_block(block)
+                }
+            })
+            .collect()
+    }
+
+    pub fn base(&self) -> &CipherBase<'a> {
+        &self.base
+    }
+
+    pub fn base_mut(&mut self) -> &mut CipherBase<'a> {
+        &mut self.base
+    }
+
+    pub fn encrypt(&mut self) -> io::Result<()> {
+        self.base.get_text()?;
+        self.base.clean_text();
+
+        self.base.encrypted_text = self.encrypt_string(&self.base.plain_text);
+
+        self.base.save_file()
+    }
+}
+
+impl Cipher for BifidCipher<'_> {
+    fn encrypt(&mut self) -> io::Result<()> {
+        let decrypt = std::mem::replace(&mut self.decrypt, false);
+        let result = BifidCipher::encrypt(self);
+        self.decrypt = decrypt;
+        result
+    }
+
+    fn decrypt(&mut self) -> io::Result<()> {
+        let decrypt = std::mem::replace(&mut self.decrypt, true);
+        let result = BifidCipher::encrypt(self);
+        self.decrypt = decrypt;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    #[test]
+    fn test_bifid_published_example() {
+        // 方阵 BGWKZ / QPNDS / IOAXE / FCLUM / THYVR，整段文本为一块
+        let square = "BGWKZQPNDSIOAXEFCLUMTHYVR";
+        let cipher = BifidCipher::new(ALPHABET, "", "", square, 0, false).unwrap();
+        assert_eq!(cipher.encrypt_string("FLEEATONCE"), "UAEOLWRINS");
+    }
+
+    #[test]
+    fn test_bifid_round_trip() {
+        let plaintext = "DEFENDTHEEASTWALLOFTHECASTLE";
+        for period in [0, 1, 3, 5, 7, 28, 100] {
+            let encrypt = BifidCipher::new(ALPHABET, "", "", "KEYWORD", period, false).unwrap();
+            let decrypt = BifidCipher::new(ALPHABET, "", "", "KEYWORD", period, true).unwrap();
+            let ciphertext = encrypt.encrypt_string(plaintext);
+            assert_eq!(ciphertext.len(), plaintext.len());
+            assert_eq!(
+                decrypt.encrypt_string(&ciphertext),
+                plaintext,
+                "period {period}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_bifid_period_one_is_the_square_itself() {
+        // 每块一个字母时行列不交换，密文就是明文
+        let cipher = BifidCipher::new(ALPHABET, "", "", "KEYWORD", 1, false).unwrap();
+        assert_eq!(cipher.encrypt_string("ATTACK"), "ATTACK");
+    }
+}
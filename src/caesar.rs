@@ -1,42 +1,244 @@
-use crate::cipher::Cipher;
+use crate::analysis::Scoring;
+use crate::cipher::{Cipher, CipherBase};
+use log::info;
+
+/// 用给定的偏移量解密一段文本，不需要输入输出文件。字母表之外的字符会被去掉。
+///
+/// ```
+/// let plaintext = cipher::caesar::decrypt("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "KHOOR, ZRUOG", 3);
+/// assert_eq!(plaintext, "HELLOWORLD");
+/// ```
+pub fn decrypt(alphabet: &str, text: &str, shift: i32) -> String {
+    let cipher = CaesarCipher::new(alphabet, "", "", -shift);
+    cipher.encrypt_string(&cipher.base().clean(text))
+}
 
 pub struct CaesarCipher<'a> {
-    base: Cipher<'a>,
+    base: CipherBase<'a>,
     shift: i32,
+    /// 逐行递增的偏移量：第一行用 `shift`，之后每行多加这么多。为0时不按行处理。
+    line_increment: i32,
 }
 
 impl<'a> CaesarCipher<'a> {
     pub fn new(alphabet: &'a str, input_file: &'a str, output_file: &'a str, shift: i32) -> Self {
-        CaesarCipher {
-            base: Cipher::new(alphabet, input_file, output_file),
+        let cipher = CaesarCipher {
+            base: CipherBase::new(alphabet, input_file, output_file),
             shift,
+            line_increment: 0,
+        };
+        let effective_shift = cipher.effective_shift();
+        if effective_shift != shift {
+            info!(
+                "Shift {shift} is equivalent to {effective_shift} for an alphabet of length {}",
+                cipher.base.letters.len()
+            );
         }
+        cipher
     }
 
-    fn encrypt_char(&self, alphabet: &str, ch: char, shift: i32) -> char {
-        if let Some(idx) = alphabet.find(ch) {
-            let new_idx = self
-                .base
-                .change_index(alphabet.len() as i32, idx as i32, shift);
-            alphabet.chars().nth(new_idx).expect("Index out of range")
-        } else {
-            panic!("Character '{ch}' not found in alphabet");
+    /// 实际生效的偏移量，即偏移量对字母表长度取模的结果。
+    pub fn effective_shift(&self) -> i32 {
+        self.shift.rem_euclid(self.base.letters.len() as i32)
+    }
+
+    /// 加密是否与解密相同。只有偏移量为0或恰好是字母表长度的一半时才成立。
+    pub fn is_reciprocal(&self) -> bool {
+        (2 * self.effective_shift()) % self.base.letters.len() as i32 == 0
+    }
+
+    /// 设置逐行递增的偏移量。
+    pub fn set_line_increment(&mut self, line_increment: i32) {
+        self.line_increment = line_increment;
+    }
+
+    /// 按行加密：第 `i` 行（从0开始）使用偏移量 `shift + i * line_increment`。
+    /// 每行按单词分别清理，行和单词的边界都保留下来。
+    /// 各项先对字母表长度取模再相加，偏移量和行数再大也不会溢出。
+    pub fn encrypt_lines(&self, text: &str, sign: i32) -> String {
+        let len = self.base.letters.len();
+        let increment = self.line_increment.rem_euclid(len as i32) as usize;
+        text.lines()
+            .enumerate()
+            .map(|(i, line)| {
+                let shift = (self.effective_shift() as usize + i % len * increment) % len;
+                self.shift_string(line, sign * shift as i32)
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// 按行清理文本，每行的单词之间保留一个空格。
+    fn clean_lines(&self, text: &str) -> String {
+        text.lines()
+            .map(|line| {
+                line.split_whitespace()
+                    .map(|word| self.base.clean(word))
+                    .filter(|word| !word.is_empty())
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// 移动单个字符。字母表之外的字符原样返回。
+    fn encrypt_char(&self, ch: char, shift: i32) -> char {
+        match self.base.index_of(ch) {
+            Some(idx) => {
+                let new_idx =
+                    self.base
+                        .change_index(self.base.letters.len() as i32, idx as i32, shift);
+                self.base.letter_at(new_idx)
+            }
+            None => ch,
+        }
+    }
+
+    /// 给收件人的解密说明：使用的子命令和参数。凯撒密码的偏移量就是全部秘密。
+    pub fn recipe(&self) -> String {
+        let mut command = format!(
+            "cipher caesar -i {} -o <plaintext> -s {}",
+            self.base.output_file, self.shift
+        );
+        if !self.base.decrypting {
+            command.push_str(" -d");
+        }
+        if self.line_increment != 0 {
+            command.push_str(&format!(" --line_increment {}", self.line_increment));
+        }
+        if self.base.stride > 1 {
+            command.push_str(&format!(" --stride {}", self.base.stride));
         }
+        if self.base.invert {
+            command.push_str(" --invert");
+        }
+        format!("To decrypt, run:\n  {command}\n")
+    }
+
+    pub fn base(&self) -> &CipherBase<'a> {
+        &self.base
+    }
+
+    pub fn base_mut(&mut self) -> &mut CipherBase<'a> {
+        &mut self.base
+    }
+
+    /// 在内存中加密一段已清理的文本。
+    pub fn encrypt_string(&self, plaintext: &str) -> String {
+        self.shift_string(plaintext, self.effective_shift())
+    }
+
+    /// 按给定的偏移量移动文本中的每个字符，遵守 `stride` 设置。
+    /// 位置只按字母表中的字符计数，保留下来的空格和标点不占位置。
+    fn shift_string(&self, text: &str, shift: i32) -> String {
+        self.shift_from(text, shift, 0)
+    }
+
+    /// 与 `shift_string` 相同，但位置从 `start` 开始计数，用于接着前面的文本继续加密。
+    fn shift_from(&self, text: &str, shift: i32, start: usize) -> String {
+        self.base.map_letters(text, start, |ch, position| {
+            if self.base.in_stride(position) {
+                self.encrypt_char(ch, shift)
+            } else {
+                ch
+            }
+        })
+    }
+
+    /// 穷举所有偏移量，返回每个偏移量解密 `plain_text` 得到的候选明文。
+    /// 需要先调用 `get_text` 和 `clean_text`，与正常加密使用相同的清理结果。
+    pub fn brute_force(&self) -> Vec<(i32, String)> {
+        (1..self.base.letters.len() as i32)
+            .map(|shift| (shift, self.shift_string(&self.base.plain_text, -shift)))
+            .collect()
+    }
+
+    /// 与 `brute_force` 相同，但按 `scoring` 的得分排序，最像英文的候选在前。
+    /// 得分相同时偏移量较小的在前，因此结果在任何平台上都是确定的。
+    pub fn ranked_candidates(&self, scoring: Scoring) -> Vec<(i32, String)> {
+        rank_candidates(self.brute_force(), scoring)
     }
 
     pub fn encrypt(&mut self) -> std::io::Result<()> {
-        self.base.get_text()?;
-        self.base.clean_text();
+        self.run(1)
+    }
 
-        self.base.encrypted_text = self
-            .base
-            .plain_text
-            .chars()
-            .map(|ch| self.encrypt_char(&self.base.alphabet, ch, self.shift))
-            .collect();
+    /// 解密文件，与 `encrypt` 对称，使用相反的偏移量。
+    pub fn decrypt(&mut self) -> std::io::Result<()> {
+        self.run(-1)
+    }
+
+    /// `sign` 为1时加密，为-1时解密。
+    fn run(&mut self, sign: i32) -> std::io::Result<()> {
+        self.base.decrypting = sign < 0;
+        let shift = sign * self.effective_shift();
+        if self.base.stream {
+            return self.stream(shift);
+        }
+        if self.base.json_mode {
+            self.base.encrypted_text = self
+                .base
+                .transform_json(|text| self.shift_string(text, shift))?;
+            return self.base.save_file();
+        }
+
+        self.base.get_text()?;
+        if self.line_increment != 0 {
+            self.base.reject_transpose("A line increment")?;
+            self.base.plain_text = self.clean_lines(&self.base.plain_text);
+            self.base.encrypted_text = self.encrypt_lines(&self.base.plain_text, sign);
+        } else {
+            self.base.clean_text();
+            self.base.encrypted_text = self.shift_string(&self.base.plain_text, shift);
+        }
+        self.base.assert_length_preserved();
 
         self.base.save_file()
     }
+
+    /// 分块读取、移动和写出输入。每个字符只取决于它的位置，跨块时只需延续位置。
+    fn stream(&mut self, shift: i32) -> std::io::Result<()> {
+        if self.line_increment != 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Streaming does not support a line increment",
+            ));
+        }
+        let mut stream = self.base.open_stream()?;
+        let mut position = 0;
+        while let Some(chunk) = self.base.next_chunk(&mut stream)? {
+            let shifted = self.shift_from(&chunk, shift, position);
+            position += self.base.count_letters(&chunk);
+            self.base.write_chunk(&mut stream, &shifted)?;
+        }
+        stream.finish()
+    }
+}
+
+/// 按 `scoring` 的得分给候选明文排序，得分相同时偏移量较小的在前，与候选原来的顺序无关。
+fn rank_candidates(candidates: Vec<(i32, String)>, scoring: Scoring) -> Vec<(i32, String)> {
+    let mut candidates: Vec<(f64, i32, String)> = candidates
+        .into_iter()
+        .map(|(shift, plaintext)| (scoring.score(&plaintext), shift, plaintext))
+        .collect();
+    candidates.sort_by(|(score_a, shift_a, _), (score_b, shift_b, _)| {
+        score_a.total_cmp(score_b).then(shift_a.cmp(shift_b))
+    });
+    candidates
+        .into_iter()
+        .map(|(_, shift, plaintext)| (shift, plaintext))
+        .collect()
+}
+
+impl Cipher for CaesarCipher<'_> {
+    fn encrypt(&mut self) -> std::io::Result<()> {
+        CaesarCipher::encrypt(self)
+    }
+
+    fn decrypt(&mut self) -> std::io::Result<()> {
+        CaesarCipher::decrypt(self)
+    }
 }
 
 #[cfg(test)]
@@ -68,4 +270,495 @@ mod tests {
         let expected_encrypted_content = "KHOOR";
         assert_eq!(encrypted_content.trim(), expected_encrypted_content);
     }
+
+    #[test]
+    fn test_caesar_cipher_decrypt_round_trip() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        std::fs::write(input_path, "HELLO").unwrap();
+
+        CaesarCipher::new(alphabet, input_path, output_path, 3)
+            .encrypt()
+            .unwrap();
+        assert_eq!(read_to_string(output_path).unwrap(), "KHOOR");
+
+        // 使用同一个偏移量解密
+        CaesarCipher::new(alphabet, output_path, round_trip_path, 3)
+            .decrypt()
+            .unwrap();
+        assert_eq!(read_to_string(round_trip_path).unwrap(), "HELLO");
+    }
+
+    #[test]
+    fn test_caesar_brute_force() {
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        std::fs::write(input_path, "KHOOR, ZRUOG").unwrap();
+
+        let mut cipher = CaesarCipher::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", input_path, "", 0);
+        cipher.base_mut().get_text().unwrap();
+        cipher.base_mut().clean_text();
+        let candidates = cipher.brute_force();
+        assert_eq!(candidates.len(), 25);
+        assert!(candidates.contains(&(3, "HELLOWORLD".to_string())));
+    }
+
+    #[test]
+    fn test_ranked_candidates_tie_prefers_smaller_shift() {
+        let cipher = CaesarCipher::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "", "", 0);
+        // 不足三个字母时所有候选的三字母得分都相同；候选按偏移量倒序给出，
+        // 只靠稳定排序保持原有顺序是不够的
+        let candidates: Vec<(i32, String)> = (1..26)
+            .rev()
+            .map(|shift| (shift, cipher.shift_string("QZ", -shift)))
+            .collect();
+        let shifts: Vec<i32> = rank_candidates(candidates, Scoring::Trigram)
+            .into_iter()
+            .map(|(shift, _)| shift)
+            .collect();
+        assert_eq!(shifts, (1..26).collect::<Vec<i32>>());
+    }
+
+    #[test]
+    fn test_trigram_scoring_cracks_short_text() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let mut cipher = CaesarCipher::new(alphabet, "", "", 0);
+        // 只有十几个字母，单字母频率的统计意义很弱
+        cipher.base_mut().plain_text =
+            CaesarCipher::new(alphabet, "", "", 11).encrypt_string("THEHUNTINGPARTY");
+        let (shift, plaintext) = cipher.ranked_candidates(Scoring::Trigram).remove(0);
+        assert_eq!(shift, 11);
+        assert_eq!(plaintext, "THEHUNTINGPARTY");
+    }
+
+    #[test]
+    fn test_chi_squared_ranks_correct_shift_first() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let plaintext = "ITISATRUTHUNIVERSALLYACKNOWLEDGEDTHATASINGLEMANINPOSSESSION\
+                         OFAGOODFORTUNEMUSTBEINWANTOFAWIFE";
+        let mut cipher = CaesarCipher::new(alphabet, "", "", 0);
+        cipher.base_mut().plain_text =
+            CaesarCipher::new(alphabet, "", "", 7).encrypt_string(plaintext);
+
+        let ranked = cipher.ranked_candidates(Scoring::ChiSquared);
+        assert_eq!(ranked.len(), 25);
+        assert_eq!(ranked[0], (7, plaintext.to_string()));
+    }
+
+    #[test]
+    fn test_caesar_recipe() {
+        let cipher = CaesarCipher::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "in.txt", "secret.txt", 7);
+        let recipe = cipher.recipe();
+        assert!(recipe.contains("cipher caesar -i secret.txt"));
+        assert!(recipe.contains("-s 7 -d"));
+
+        // 这次运行是解密时，说明中不带 -d
+        let mut cipher = CaesarCipher::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "in.txt", "plain.txt", 7);
+        cipher.base_mut().decrypting = true;
+        assert!(!cipher.recipe().contains("-d"));
+    }
+
+    #[test]
+    fn test_caesar_then_transpose_round_trip() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        std::fs::write(input_path, "WEAREDISCOVERED").unwrap();
+
+        let mut cipher = CaesarCipher::new(alphabet, input_path, output_path, 3);
+        cipher.base_mut().transpose = Some("ZEBRAS".to_string());
+        cipher.encrypt().unwrap();
+        let ciphertext = read_to_string(output_path).unwrap();
+        assert_ne!(ciphertext, "ZHDUHGLVFRYHUHG");
+
+        // 解密时先撤销列置换，再做凯撒解密
+        let mut cipher = CaesarCipher::new(alphabet, output_path, round_trip_path, 3);
+        cipher.base_mut().transpose = Some("ZEBRAS".to_string());
+        cipher.base_mut().transpose_input = true;
+        cipher.decrypt().unwrap();
+        assert_eq!(read_to_string(round_trip_path).unwrap(), "WEAREDISCOVERED");
+
+        // 按行加密保留行结构，无法撤销列置换，直接拒绝
+        let mut cipher = CaesarCipher::new(alphabet, input_path, output_path, 3);
+        cipher.set_line_increment(1);
+        cipher.base_mut().transpose = Some("ZEBRAS".to_string());
+        assert!(cipher.encrypt().is_err());
+    }
+
+    #[test]
+    fn test_mixed_case_input() {
+        use crate::cipher::CaseMode;
+
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        std::fs::write(input_path, "Hello World").unwrap();
+
+        let mut cipher = CaesarCipher::new(alphabet, input_path, output_path, 3);
+        cipher.encrypt().unwrap();
+        assert_eq!(read_to_string(output_path).unwrap(), "KHOORZRUOG");
+
+        // 保持原样时小写字母不在字母表中，会被去掉
+        let mut cipher = CaesarCipher::new(alphabet, input_path, output_path, 3);
+        cipher.base_mut().case_mode = CaseMode::Preserve;
+        cipher.encrypt().unwrap();
+        assert_eq!(read_to_string(output_path).unwrap(), "KZ");
+    }
+
+    #[test]
+    fn test_preserve_non_alpha_round_trip() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        std::fs::write(input_path, "ATTACK AT DAWN!\nNOW, PLEASE.").unwrap();
+
+        let mut cipher = CaesarCipher::new(alphabet, input_path, output_path, 3);
+        cipher.base_mut().preserve_non_alpha = true;
+        cipher.encrypt().unwrap();
+        assert_eq!(
+            read_to_string(output_path).unwrap(),
+            "DWWDFN DW GDZQ!\nQRZ, SOHDVH."
+        );
+
+        let mut cipher = CaesarCipher::new(alphabet, output_path, round_trip_path, 3);
+        cipher.base_mut().preserve_non_alpha = true;
+        cipher.decrypt().unwrap();
+        assert_eq!(
+            read_to_string(round_trip_path).unwrap(),
+            "ATTACK AT DAWN!\nNOW, PLEASE."
+        );
+    }
+
+    #[test]
+    fn test_preserve_chars_whitelist() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        std::fs::write(input_path, "NOW, PLEASE COME.").unwrap();
+
+        // 只保留空格，逗号和句号被删除
+        let mut cipher = CaesarCipher::new(alphabet, input_path, output_path, 3);
+        cipher.base_mut().preserve_chars = Some(" ".to_string());
+        cipher.encrypt().unwrap();
+        assert_eq!(read_to_string(output_path).unwrap(), "QRZ SOHDVH FRPH");
+    }
+
+    #[test]
+    fn test_line_increment() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        std::fs::write(input_path, "ABC DEF\nABC\nABC, DEF!\n").unwrap();
+
+        // 三行的偏移量依次是1、3、5
+        let mut cipher = CaesarCipher::new(alphabet, input_path, output_path, 1);
+        cipher.set_line_increment(2);
+        cipher.encrypt().unwrap();
+        assert_eq!(
+            read_to_string(output_path).unwrap(),
+            "BCD EFG\nDEF\nFGH IJK"
+        );
+
+        let mut cipher = CaesarCipher::new(alphabet, output_path, round_trip_path, 1);
+        cipher.set_line_increment(2);
+        cipher.decrypt().unwrap();
+        assert_eq!(
+            read_to_string(round_trip_path).unwrap(),
+            "ABC DEF\nABC\nABC DEF"
+        );
+
+        // 极端的偏移量和增量不会溢出，结果与取模后的值相同
+        let mut extreme = CaesarCipher::new(alphabet, "", "", i32::MIN);
+        extreme.set_line_increment(i32::MAX);
+        let mut reduced = CaesarCipher::new(alphabet, "", "", i32::MIN.rem_euclid(26));
+        reduced.set_line_increment(i32::MAX.rem_euclid(26));
+        let text = "ABC\nABC\nABC";
+        for sign in [1, -1] {
+            assert_eq!(
+                extreme.encrypt_lines(text, sign),
+                reduced.encrypt_lines(text, sign)
+            );
+        }
+        assert_eq!(
+            extreme.encrypt_lines(&extreme.encrypt_lines(text, 1), -1),
+            text
+        );
+        assert_eq!(
+            CaesarCipher::new(alphabet, "", "", i32::MAX).encrypt_string("XYZ"),
+            CaesarCipher::new(alphabet, "", "", i32::MAX.rem_euclid(26)).encrypt_string("XYZ")
+        );
+    }
+
+    #[test]
+    fn test_caesar_stride() {
+        let mut cipher = CaesarCipher::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "", "", 1);
+        cipher.base_mut().stride = 2;
+        assert_eq!(cipher.encrypt_string("AAAA"), "BABA");
+    }
+
+    #[test]
+    fn test_mismatched_encodings_round_trip() {
+        use crate::cipher::Encoding;
+
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+
+        // Latin-1 编码的 "CAFÉ HELLO"，É 是单个字节 0xC9，不是合法的UTF-8
+        std::fs::write(input_path, b"CAF\xC9 HELLO").unwrap();
+
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let mut cipher = CaesarCipher::new(alphabet, input_path, output_path, 3);
+        cipher.base_mut().input_encoding = Encoding::Latin1;
+        cipher.base_mut().output_encoding = Encoding::Ascii;
+        cipher.encrypt().unwrap();
+        let ciphertext = std::fs::read(output_path).unwrap();
+        assert_eq!(ciphertext, b"FDIKHOOR".to_vec());
+
+        let mut cipher = CaesarCipher::new(alphabet, output_path, round_trip_path, -3);
+        cipher.base_mut().input_encoding = Encoding::Utf8;
+        cipher.base_mut().output_encoding = Encoding::Latin1;
+        cipher.encrypt().unwrap();
+        assert_eq!(read_to_string(round_trip_path).unwrap(), "CAFHELLO");
+    }
+
+    #[test]
+    fn test_lowercase_output_round_trip() {
+        use crate::cipher::CaseMode;
+
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        std::fs::write(input_path, "HELLO WORLD").unwrap();
+
+        let mut cipher = CaesarCipher::new(alphabet, input_path, output_path, 3);
+        cipher.base_mut().output_case = CaseMode::Lower;
+        cipher.encrypt().unwrap();
+        let ciphertext = read_to_string(output_path).unwrap();
+        assert_eq!(ciphertext, "khoorzruog");
+
+        // 转换回大写后解密
+        std::fs::write(output_path, ciphertext.to_uppercase()).unwrap();
+        let mut cipher = CaesarCipher::new(alphabet, output_path, round_trip_path, -3);
+        cipher.encrypt().unwrap();
+        assert_eq!(read_to_string(round_trip_path).unwrap(), "HELLOWORLD");
+    }
+
+    #[test]
+    fn test_effective_shift() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        assert_eq!(
+            CaesarCipher::new(alphabet, "input.txt", "output.txt", 30).effective_shift(),
+            4
+        );
+        assert_eq!(
+            CaesarCipher::new(alphabet, "input.txt", "output.txt", -1).effective_shift(),
+            25
+        );
+        assert_eq!(
+            CaesarCipher::new(alphabet, "input.txt", "output.txt", 3).effective_shift(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_caesar_cipher_json_round_trip() {
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().expect("Invalid input path");
+        let encrypted_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let encrypted_path = encrypted_file.path().to_str().expect("Invalid output path");
+        let decrypted_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let decrypted_path = decrypted_file.path().to_str().expect("Invalid output path");
+
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        std::fs::write(
+            input_path,
+            r#"{"name":"Alice Smith","age":30,"tags":["BOB","x-y"]}"#,
+        )
+        .expect("Failed to write to input file");
+
+        let mut cipher = CaesarCipher::new(alphabet, input_path, encrypted_path, 3);
+        cipher.base_mut().json_mode = true;
+        cipher.encrypt().expect("Encryption failed");
+
+        let encrypted: serde_json::Value =
+            serde_json::from_str(&read_to_string(encrypted_path).unwrap()).unwrap();
+        // 每个值单独处理，空格、标点和大小写都保留下来
+        assert_eq!(encrypted["name"], "Dolfh Vplwk");
+        assert_eq!(encrypted["age"], 30);
+        assert_eq!(encrypted["tags"][0], "ERE");
+        assert_eq!(encrypted["tags"][1], "a-b");
+
+        let mut cipher = CaesarCipher::new(alphabet, encrypted_path, decrypted_path, -3);
+        cipher.base_mut().json_mode = true;
+        cipher.encrypt().expect("Decryption failed");
+
+        let decrypted: serde_json::Value =
+            serde_json::from_str(&read_to_string(decrypted_path).unwrap()).unwrap();
+        assert_eq!(decrypted["name"], "Alice Smith");
+        assert_eq!(decrypted["tags"][0], "BOB");
+        assert_eq!(decrypted["tags"][1], "x-y");
+    }
+
+    #[test]
+    fn test_caesar_cipher_preserves_length() {
+        let cipher = CaesarCipher::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "", "", 11);
+        for plaintext in ["", "A", "HELLOWORLD", "ZZZZZZZZZZZZZZZZZZZZ"] {
+            assert_eq!(
+                cipher.encrypt_string(plaintext).chars().count(),
+                plaintext.chars().count()
+            );
+        }
+    }
+
+    #[test]
+    fn test_caesar_cipher_grouped_round_trip() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().expect("Invalid input path");
+        let encrypted_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let encrypted_path = encrypted_file.path().to_str().expect("Invalid output path");
+        let decrypted_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let decrypted_path = decrypted_file.path().to_str().expect("Invalid output path");
+        std::fs::write(input_path, "HELLOWORLD").expect("Failed to write to input file");
+
+        let mut cipher = CaesarCipher::new(alphabet, input_path, encrypted_path, 3);
+        cipher.base_mut().group_size = 4;
+        cipher.base_mut().group_sep = Some("-".to_string());
+        cipher.encrypt().expect("Encryption failed");
+        assert_eq!(read_to_string(encrypted_path).unwrap(), "KHOO-RZRU-OG");
+
+        let mut cipher = CaesarCipher::new(alphabet, encrypted_path, decrypted_path, -3);
+        cipher.base_mut().group_sep = Some("-".to_string());
+        cipher.encrypt().expect("Decryption failed");
+        assert_eq!(read_to_string(decrypted_path).unwrap(), "HELLOWORLD");
+    }
+
+    #[test]
+    fn test_caesar_cipher_invert_round_trip() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().expect("Invalid input path");
+        let encrypted_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let encrypted_path = encrypted_file.path().to_str().expect("Invalid output path");
+        let decrypted_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let decrypted_path = decrypted_file.path().to_str().expect("Invalid output path");
+        std::fs::write(input_path, "HELLOWORLD").expect("Failed to write to input file");
+
+        let mut cipher = CaesarCipher::new(alphabet, input_path, encrypted_path, 3);
+        cipher.base_mut().invert = true;
+        cipher.encrypt().expect("Encryption failed");
+        let encrypted = read_to_string(encrypted_path).unwrap();
+        assert_ne!(encrypted, "KHOORZRUOG");
+
+        let mut cipher = CaesarCipher::new(alphabet, encrypted_path, decrypted_path, -3);
+        cipher.base_mut().invert = true;
+        cipher.encrypt().expect("Decryption failed");
+        assert_eq!(read_to_string(decrypted_path).unwrap(), "HELLOWORLD");
+    }
+
+    #[test]
+    fn test_caesar_cipher_reciprocity() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        assert!(CaesarCipher::new(alphabet, "", "", 13).is_reciprocal());
+        assert!(CaesarCipher::new(alphabet, "", "", 0).is_reciprocal());
+        assert!(CaesarCipher::new(alphabet, "", "", -13).is_reciprocal());
+        assert!(!CaesarCipher::new(alphabet, "", "", 3).is_reciprocal());
+
+        // 字母表长度按字符计算，而不是按UTF-8字节数
+        let cyrillic = "АБВГ";
+        assert!(CaesarCipher::new(cyrillic, "", "", 2).is_reciprocal());
+        assert_eq!(
+            CaesarCipher::new(cyrillic, "", "", 1).brute_force().len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_large_input_matches_scanning_lookup() {
+        // 查表得到的结果与逐个扫描字母表得到的结果相同
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+        let text = crate::benchmark::synthetic_input(alphabet, 100_000, 7) + " .,!";
+        let cipher = CaesarCipher::new(alphabet, "", "", 17);
+        let expected: String = text
+            .chars()
+            .map(|ch| match alphabet.chars().position(|c| c == ch) {
+                Some(idx) => alphabet.chars().nth((idx + 17) % alphabet.len()).unwrap(),
+                None => ch,
+            })
+            .collect();
+        let ciphertext = cipher.encrypt_string(&text);
+        assert_eq!(ciphertext, expected);
+        // decrypt 会去掉字母表之外的字符
+        assert_eq!(
+            decrypt(alphabet, &ciphertext, 17),
+            text.trim_end_matches([' ', '.', ',', '!'])
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_parallel_matches_serial() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let text = crate::benchmark::synthetic_input(alphabet, 50_000, 9).replace("E", "E, ");
+        let cipher = CaesarCipher::new(alphabet, "", "", 5);
+        let serial = cipher
+            .base()
+            .map_letters_serial(&text, 0, |ch, _| cipher.encrypt_char(ch, 5));
+        assert_eq!(cipher.encrypt_string(&text), serial);
+    }
+
+    #[test]
+    fn test_stream_matches_whole_file() {
+        // 输入跨越多个块，其中的多字节字符可能被块边界截断
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let text = crate::benchmark::synthetic_input(alphabet, 300_000, 4).replace("Q", "Qé ");
+        assert!(text.len() > 3 * crate::cipher::STREAM_CHUNK_SIZE);
+        let input = NamedTempFile::new().unwrap();
+        let whole = NamedTempFile::new().unwrap();
+        let streamed = NamedTempFile::new().unwrap();
+        std::fs::write(input.path(), &text).unwrap();
+        let input_path = input.path().to_str().unwrap();
+
+        for (output, stream) in [(&whole, false), (&streamed, true)] {
+            let output_path = output.path().to_str().unwrap();
+            let mut cipher = CaesarCipher::new(alphabet, input_path, output_path, 7);
+            cipher.base_mut().preserve_non_alpha = true;
+            cipher.base_mut().stride = 3;
+            cipher.base_mut().stream = stream;
+            cipher.encrypt().expect("Encryption failed");
+        }
+        assert_eq!(
+            read_to_string(streamed.path()).unwrap(),
+            read_to_string(whole.path()).unwrap()
+        );
+    }
 }
@@ -0,0 +1,185 @@
+use crate::cipher::{CipherBase, char_to_index};
+use std::io;
+
+/// 字母之外补充进棋盘的两个符号：句号和数字转义符。
+const EXTRA_SYMBOLS: &str = "./";
+
+/// 跨格棋盘（VIC密码的组成部分）：第一行有两个空位，其余8格放最常用的字母，各用一位数字表示；
+/// 以两个空位的列号作为行号的另外两行放其余字符，各用两位数字表示。
+pub struct StraddlingCheckerboard<'a> {
+    base: CipherBase<'a>,
+    /// 第一行，空位为 `None`。
+    top: [Option<char>; 10],
+    /// 两个空位的列号及其对应的行。
+    rows: [(usize, Vec<char>); 2],
+    decrypt: bool,
+}
+
+impl<'a> StraddlingCheckerboard<'a> {
+    /// 用关键词打乱字母表后依次填入棋盘：先填第一行的非空位，再填两行。
+    /// 关键词中也可以出现 `.` 和 `/`，以指定它们的位置。
+    pub fn new(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        keyword: &str,
+        blanks: [usize; 2],
+        decrypt: bool,
+    ) -> io::Result<Self> {
+        if blanks[0] == blanks[1] || blanks.iter().any(|&b| b > 9) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Blank positions must be two different digits, got {blanks:?}"),
+            ));
+        }
+
+        let symbols: Vec<char> = alphabet.chars().chain(EXTRA_SYMBOLS.chars()).collect();
+        if symbols.len() != 28 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "A straddling checkerboard needs a 26-letter alphabet, got {}",
+                    alphabet.chars().count()
+                ),
+            ));
+        }
+
+        let mut mixed: Vec<char> = Vec::with_capacity(symbols.len());
+        for ch in keyword.chars().chain(symbols.iter().copied()) {
+            let ch = match char_to_index(alphabet, ch) {
+                Some(idx) => symbols[idx],
+                None => ch,
+            };
+            if symbols.contains(&ch) && !mixed.contains(&ch) {
+                mixed.push(ch);
+            }
+        }
+
+        let mut cells = mixed.into_iter();
+        let mut top = [None; 10];
+        for (col, cell) in top.iter_mut().enumerate() {
+            if !blanks.contains(&col) {
+                *cell = cells.next();
+            }
+        }
+        let mut rows = [(blanks[0], Vec::new()), (blanks[1], Vec::new())];
+        for (_, row) in rows.iter_mut() {
+            row.extend(cells.by_ref().take(10));
+        }
+
+        Ok(StraddlingCheckerboard {
+            base: CipherBase::new(alphabet, input_file, output_file),
+            top,
+            rows,
+            decrypt,
+        })
+    }
+
+    /// 把已清理的文本转换为数字串。
+    pub fn encode_string(&self, text: &str) -> String {
+        let mut digits = String::new();
+        for ch in text.chars() {
+            if let Some(col) = self.top.iter().position(|&c| c == Some(ch)) {
+                digits.push_str(&col.to_string());
+            } else if let Some((row, col)) = self
+                .rows
+                .iter()
+                .find_map(|(row, cells)| cells.iter().position(|&c| c == ch).map(|col| (row, col)))
+            {
+                digits.push_str(&format!("{row}{col}"));
+            }
+        }
+        digits
+    }
+
+    /// 把数字串还原为文本。遇到空位的列号时与下一位数字组成两位数，因此解码没有歧义。
+    /// 数字以外的字符（如空格）被忽略。
+    pub fn decode_string(&self, text: &str) -> io::Result<String> {
+        let mut digits = text
+            .chars()
+            .filter_map(|c| c.to_digit(10))
+            .map(|d| d as usize);
+        let mut decoded = String::new();
+        while let Some(digit) = digits.next() {
+            match self.rows.iter().find(|(row, _)| *row == digit) {
+                Some((_, cells)) => {
+                    let col = digits.next().ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("Row digit {digit} at the end has no column"),
+                        )
+                    })?;
+                    decoded.push(cells[col]);
+                }
+                None => decoded.push(self.top[digit].expect("Blank columns are rows")),
+            }
+        }
+        Ok(decoded)
+    }
+
+    pub fn base(&self) -> &CipherBase<'a> {
+        &self.base
+    }
+
+    pub fn base_mut(&mut self) -> &mut CipherBase<'a> {
+        &mut self.base
+    }
+
+    pub fn encrypt(&mut self) -> io::Result<()> {
+        // 解码时数字不经过 clean_text，无法先撤销置换
+        self.base.reject_transpose("The straddling checkerboard")?;
+        self.base.get_text()?;
+
+        if self.decrypt {
+            self.base.encrypted_text = self.decode_string(&self.base.plain_text)?;
+        } else {
+            self.base.clean_text();
+            self.base.encrypted_text = self.encode_string(&self.base.plain_text);
+        }
+
+        self.base.save_file()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    #[test]
+    fn test_checkerboard_documented_layout() {
+        // 维基百科中的棋盘：
+        //    0 1 2 3 4 5 6 7 8 9
+        //    E T   A O N   R I S
+        // 2  B C D F G H J K L M
+        // 6  P Q / U V W X Y Z .
+        let board = StraddlingCheckerboard::new(
+            ALPHABET,
+            "",
+            "",
+            "ETAONRISBCDFGHJKLMPQ/UVWXYZ.",
+            [2, 6],
+            false,
+        )
+        .unwrap();
+        assert_eq!(board.encode_string("ATTACKATDAWN"), "3113212731223655");
+        assert_eq!(
+            board.decode_string("3113212731223655").unwrap(),
+            "ATTACKATDAWN"
+        );
+    }
+
+    #[test]
+    fn test_checkerboard_round_trip() {
+        let board = StraddlingCheckerboard::new(ALPHABET, "", "", "cipher", [3, 7], false).unwrap();
+        let plaintext = "THEQUICKBROWNFOXJUMPSOVERTHELAZYDOG";
+        let digits = board.encode_string(plaintext);
+        assert!(digits.chars().all(|c| c.is_ascii_digit()));
+        assert_eq!(board.decode_string(&digits).unwrap(), plaintext);
+
+        // 末尾只剩行号时报错
+        assert!(board.decode_string("3").is_err());
+        assert!(StraddlingCheckerboard::new(ALPHABET, "", "", "", [3, 3], false).is_err());
+    }
+}
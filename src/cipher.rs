@@ -1,39 +1,1012 @@
+use crate::analysis::{index_of_coincidence, letter_counts};
+use crate::columnar;
+use log::{info, warn};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
 
-pub struct Cipher<'a> {
+/// 查找字符在字母表中的序号。字母表中没有小写字母时，先把字符转换为大写再查找，
+/// 这样关键词、指针等由用户输入的字母不区分大小写。
+pub fn char_to_index(alphabet: &str, ch: char) -> Option<usize> {
+    let ch = if alphabet.chars().any(char::is_lowercase) {
+        ch
+    } else {
+        ch.to_ascii_uppercase()
+    };
+    alphabet.chars().position(|c| c == ch)
+}
+
+/// 检查字母表是否可用：不能为空，也不能有重复字符。
+pub fn validate_alphabet(alphabet: &str) -> io::Result<()> {
+    if alphabet.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "The alphabet must not be empty",
+        ));
+    }
+    let mut seen = std::collections::HashSet::new();
+    if let Some(ch) = alphabet.chars().find(|&c| !seen.insert(c)) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Character '{ch}' appears more than once in the alphabet"),
+        ));
+    }
+    Ok(())
+}
+
+/// 检查分组分隔符是否可用：读取输入时会去掉分隔符，
+/// 所以它不能含有字母表中的字符（不区分大小写），否则这些字符会从正文中消失。
+pub fn validate_group_sep(alphabet: &str, group_sep: &str) -> io::Result<()> {
+    if let Some(ch) = group_sep
+        .chars()
+        .find(|&c| char_to_index(alphabet, c).is_some())
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("The group separator must not contain the alphabet character '{ch}'"),
+        ));
+    }
+    Ok(())
+}
+
+/// 列出字母表中每个字符及其序号，每行一个，例如 `A=0`。
+pub fn dump_alphabet(alphabet: &str) -> String {
+    let letters: Vec<char> = alphabet.chars().collect();
+    letters
+        .iter()
+        .enumerate()
+        .map(|(idx, ch)| format!("{ch}={idx}\n"))
+        .collect()
+}
+
+/// 北约音标字母表，用于语音传输密文。
+const PHONETIC_WORDS: [(char, &str); 26] = [
+    ('A', "Alfa"),
+    ('B', "Bravo"),
+    ('C', "Charlie"),
+    ('D', "Delta"),
+    ('E', "Echo"),
+    ('F', "Foxtrot"),
+    ('G', "Golf"),
+    ('H', "Hotel"),
+    ('I', "India"),
+    ('J', "Juliett"),
+    ('K', "Kilo"),
+    ('L', "Lima"),
+    ('M', "Mike"),
+    ('N', "November"),
+    ('O', "Oscar"),
+    ('P', "Papa"),
+    ('Q', "Quebec"),
+    ('R', "Romeo"),
+    ('S', "Sierra"),
+    ('T', "Tango"),
+    ('U', "Uniform"),
+    ('V', "Victor"),
+    ('W', "Whiskey"),
+    ('X', "X-ray"),
+    ('Y', "Yankee"),
+    ('Z', "Zulu"),
+];
+
+/// 把文本中的每个字母转写成北约音标单词，以空格分隔。音标表之外的字符原样保留。
+pub fn to_phonetic(text: &str) -> String {
+    text.chars()
+        .map(|ch| {
+            PHONETIC_WORDS
+                .iter()
+                .find(|(letter, _)| *letter == ch.to_ascii_uppercase())
+                .map_or_else(|| ch.to_string(), |(_, word)| word.to_string())
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// 把以空白分隔的北约音标单词解析回字母，不区分大小写。
+pub fn from_phonetic(text: &str) -> io::Result<String> {
+    text.split_whitespace()
+        .map(|token| {
+            let normalized = match token.to_ascii_lowercase().as_str() {
+                "alpha" => "alfa".to_string(),
+                "juliet" => "juliett".to_string(),
+                "xray" => "x-ray".to_string(),
+                other => other.to_string(),
+            };
+            PHONETIC_WORDS
+                .iter()
+                .find(|(_, word)| word.to_ascii_lowercase() == normalized)
+                .map(|(letter, _)| *letter)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Unknown phonetic word: {token}"),
+                    )
+                })
+        })
+        .collect()
+}
+
+/// 递归遍历JSON值，只变换其中的字符串值，键和结构保持不变。
+fn transform_json_strings(value: &mut Value, f: &mut dyn FnMut(&str) -> String) {
+    match value {
+        Value::String(text) => *text = f(text),
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| transform_json_strings(item, f)),
+        Value::Object(map) => map
+            .values_mut()
+            .for_each(|item| transform_json_strings(item, f)),
+        _ => {}
+    }
+}
+
+/// 元数据行的前缀。输出文件的第一行可以是 `#!cipher key=value ...`，读取时会被解析并去掉。
+const METADATA_PREFIX: &str = "#!cipher";
+
+/// 标记输出为密文的元数据键值，用于发现对密文的重复加密。
+const CIPHERTEXT_MARKER: (&str, &str) = ("content", "ciphertext");
+
+/// 元数据是否把内容标记为密文。
+fn is_marked_ciphertext(metadata: &[(String, String)]) -> bool {
+    let (key, value) = CIPHERTEXT_MARKER;
+    metadata
+        .iter()
+        .any(|(k, v)| k.eq_ignore_ascii_case(key) && v.eq_ignore_ascii_case(value))
+}
+
+/// 取出JSON文档中的元数据。元数据保存在顶层对象的 `#!cipher` 键中，
+/// 值的格式与元数据行中前缀之后的部分相同；取出后该键从文档中删除，不会被加密。
+fn take_json_metadata(value: &mut Value) -> Vec<(String, String)> {
+    if let Value::Object(map) = value
+        && map.get(METADATA_PREFIX).is_some_and(Value::is_string)
+        && let Some(Value::String(pairs)) = map.remove(METADATA_PREFIX)
+    {
+        return parse_metadata_line(&format!("{METADATA_PREFIX} {pairs}")).unwrap_or_default();
+    }
+    Vec::new()
+}
+
+/// 解析元数据行，返回键值对；不是元数据行时返回 `None`。前缀和键不区分大小写。
+fn parse_metadata_line(line: &str) -> Option<Vec<(String, String)>> {
+    let prefix = line.get(..METADATA_PREFIX.len())?;
+    if !prefix.eq_ignore_ascii_case(METADATA_PREFIX) {
+        return None;
+    }
+    Some(
+        line[METADATA_PREFIX.len()..]
+            .split_whitespace()
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (key.to_ascii_lowercase(), value.to_string()))
+            .collect(),
+    )
+}
+
+/// 输出文本的大小写。
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CaseMode {
+    Upper,
+    Lower,
+    /// 保持加密结果原有的大小写。
+    #[default]
+    Preserve,
+}
+
+impl CaseMode {
+    /// 按设置转换文本的大小写。
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            CaseMode::Upper => text.to_uppercase(),
+            CaseMode::Lower => text.to_lowercase(),
+            CaseMode::Preserve => text.to_string(),
+        }
+    }
+}
+
+/// 读写文件时使用的字符编码。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    /// ISO-8859-1，每个字节对应一个Unicode码位。
+    Latin1,
+    /// 纯ASCII；写出时字母统一转换为大写。
+    Ascii,
+}
+
+impl Encoding {
+    /// 按编码把字节解码为文本。
+    pub fn decode(self, bytes: Vec<u8>) -> io::Result<String> {
+        match self {
+            Encoding::Utf8 => {
+                String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            }
+            Encoding::Latin1 => Ok(bytes.into_iter().map(char::from).collect()),
+            Encoding::Ascii => match bytes.iter().position(|b| !b.is_ascii()) {
+                Some(idx) => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Byte {:#04x} at offset {idx} is not ASCII", bytes[idx]),
+                )),
+                None => Ok(bytes.into_iter().map(char::from).collect()),
+            },
+        }
+    }
+
+    /// 按编码把文本编码为字节，无法表示的字符报错。
+    pub fn encode(self, text: &str) -> io::Result<Vec<u8>> {
+        let unrepresentable = |ch: char| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Character '{ch}' cannot be written as {self:?}"),
+            )
+        };
+        match self {
+            Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            Encoding::Latin1 => text
+                .chars()
+                .map(|ch| u8::try_from(ch).map_err(|_| unrepresentable(ch)))
+                .collect(),
+            Encoding::Ascii => text
+                .chars()
+                .map(|ch| {
+                    if ch.is_ascii() {
+                        Ok(ch.to_ascii_uppercase() as u8)
+                    } else {
+                        Err(unrepresentable(ch))
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// 一次加密的统计信息。
+pub struct TextStats {
+    pub input_len: usize,
+    pub cleaned_len: usize,
+    pub output_len: usize,
+    pub distinct_output_chars: usize,
+    pub output_ioc: f64,
+}
+
+/// 表示标准输入或标准输出的路径。
+pub const STDIO_PATH: &str = "-";
+
+/// 分卷文件的路径：在原路径后加上三位序号，序号从1开始，例如 `output.txt.001`。
+pub fn part_path(path: &str, index: usize) -> String {
+    format!("{path}.{index:03}")
+}
+
+/// 流式处理时每次读取的字节数。
+pub const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// 流式处理中打开的输入和输出，由 `CipherBase::open_stream` 创建，逐块读写。
+pub struct TextStream {
+    reader: BufReader<Box<dyn Read>>,
+    writer: BufWriter<Box<dyn Write>>,
+    /// 上一块末尾被截断的多字节字符，留到下一块再解码。
+    pending: Vec<u8>,
+}
+
+impl TextStream {
+    /// 写出缓冲中剩余的输出。
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// 各种密码共同的接口，便于用 `Box<dyn Cipher>` 统一调度。
+/// 两个方法都走完整的文件流程：读取、清理、变换、写出。
+/// `encrypt` 总是加密、`decrypt` 总是解密，与构造时给出的方向无关。
+pub trait Cipher {
+    fn encrypt(&mut self) -> io::Result<()>;
+    fn decrypt(&mut self) -> io::Result<()>;
+}
+
+/// 各种密码共享的输入输出、清理和输出格式设置。
+#[derive(Clone)]
+pub struct CipherBase<'a> {
     pub alphabet: &'a str,
+    /// 字母表中的字符按序号排列，由序号取字符时不必再遍历字符串。
+    pub letters: Vec<char>,
+    /// 字符到序号的映射，构造时建立一次，加密每个字符时直接查表。
+    pub indices: HashMap<char, usize>,
     pub input_file: &'a str,
     pub output_file: &'a str,
     pub plain_text: String,
     pub encrypted_text: String,
+    /// 输入是否为音标单词，需要先转换回字母。
+    pub phonetic_input: bool,
+    /// 是否把密文写成音标单词。
+    pub phonetic_output: bool,
+    /// 是否把清理后的明文与密文逐字符对照输出，便于教学演示。
+    pub side_by_side: bool,
+    /// 输入是否为JSON，此时只加密其中的字符串值。
+    pub json_mode: bool,
+    /// 读入的原始输入字符数，清理之前统计。
+    pub input_len: usize,
+    /// 输出时每组的字符数，0表示不分组。
+    pub group_size: usize,
+    /// 分组之间的分隔符，清理输入时也会先去掉它。
+    /// 为 `None` 时分组用空格连接，清理输入时不去掉任何分隔符。
+    pub group_sep: Option<String>,
+    /// 是否在读入时和写出时都做一次埃特巴什变换，把它叠加在主密码之外。
+    pub invert: bool,
+    /// 字母表全为ASCII时，输入中出现非ASCII字符就报错，而不是在清理时悄悄丢掉。
+    pub strict_ascii: bool,
+    /// 只加密每隔 `stride` 个位置中的第一个字符，其余原样保留；1表示全部加密。
+    pub stride: usize,
+    /// 读取输入文件时使用的编码。
+    pub input_encoding: Encoding,
+    /// 写出输出文件时使用的编码。
+    pub output_encoding: Encoding,
+    /// 保留字母表之外的字符（空格、标点等），加密时原样通过，而不是在清理时删除。
+    pub preserve_non_alpha: bool,
+    /// 只保留这些字母表之外的字符，其余的在清理时删除。设置后优先于 `preserve_non_alpha`。
+    pub preserve_chars: Option<String>,
+    /// 清理输入前先统一的大小写。字母表全是大写时默认转换为大写，否则保持原样。
+    pub case_mode: CaseMode,
+    /// 写出的密文的大小写。
+    pub output_case: CaseMode,
+    /// 替换之后再做一次列置换的关键词，构成乘积密码。
+    pub transpose: Option<String>,
+    /// 为真时列置换作用在输入上（解密时先撤销置换），否则作用在输出上。
+    /// 加密和解密相同的密码没有解密方向，需要由调用方明确设置。
+    pub transpose_input: bool,
+    /// 大于0时把输出拆成每个最多这么多字节的分卷文件。
+    pub split_bytes: usize,
+    /// 大于0时从这么多个分卷文件依次读取输入，而不是读取单个文件。
+    pub input_parts: usize,
+    /// 分块读取、变换和写出，而不是把整个输入读入内存。只有逐字符变换的密码支持。
+    pub stream: bool,
+    /// 写在输出文件第一行的元数据。
+    pub metadata: Vec<(String, String)>,
+    /// 从输入文件第一行读到的元数据。
+    pub input_metadata: Vec<(String, String)>,
+    /// 本次运行是否在解密。解密时不标记密文，读到带密文标记的输入也不发出警告。
+    pub decrypting: bool,
 }
 
-impl<'a> Cipher<'a> {
+impl<'a> CipherBase<'a> {
     pub fn new(alphabet: &'a str, input_file: &'a str, output_file: &'a str) -> Self {
-        Cipher {
+        let letters: Vec<char> = alphabet.chars().collect();
+        let indices = letters
+            .iter()
+            .enumerate()
+            .map(|(idx, &c)| (c, idx))
+            .collect();
+        CipherBase {
             alphabet,
+            letters,
+            indices,
             input_file,
             output_file,
             plain_text: String::new(),
             encrypted_text: String::new(),
+            phonetic_input: false,
+            phonetic_output: false,
+            side_by_side: false,
+            json_mode: false,
+            input_len: 0,
+            group_size: 0,
+            group_sep: None,
+            invert: false,
+            strict_ascii: false,
+            stride: 1,
+            input_encoding: Encoding::Utf8,
+            output_encoding: Encoding::Utf8,
+            preserve_non_alpha: false,
+            preserve_chars: None,
+            case_mode: if alphabet.chars().any(char::is_lowercase) {
+                CaseMode::Preserve
+            } else {
+                CaseMode::Upper
+            },
+            output_case: CaseMode::Preserve,
+            transpose: None,
+            transpose_input: false,
+            split_bytes: 0,
+            input_parts: 0,
+            stream: false,
+            metadata: Vec::new(),
+            input_metadata: Vec::new(),
+            decrypting: false,
         }
     }
 
+    /// 字符在字母表中的序号，不在字母表中时返回 `None`。
+    pub fn index_of(&self, ch: char) -> Option<usize> {
+        self.indices.get(&ch).copied()
+    }
+
+    /// 字母表中指定序号的字符。
+    pub fn letter_at(&self, idx: usize) -> char {
+        self.letters[idx]
+    }
+
+    /// 读取输入的全部字节。输入路径为 `-` 时读取标准输入。
+    fn read_bytes(&self) -> io::Result<Vec<u8>> {
+        self.read_bytes_or(io::stdin().lock())
+    }
+
+    /// 与 `read_bytes` 相同，但输入路径为 `-` 时从 `stdin` 读取。
+    fn read_bytes_or<R: Read>(&self, mut stdin: R) -> io::Result<Vec<u8>> {
+        if self.input_file == STDIO_PATH {
+            let mut bytes = Vec::new();
+            stdin.read_to_end(&mut bytes)?;
+            return Ok(bytes);
+        }
+        if self.input_parts == 0 {
+            return fs::read(self.input_file);
+        }
+        let mut bytes = Vec::new();
+        for index in 1..=self.input_parts {
+            bytes.extend(fs::read(part_path(self.input_file, index))?);
+        }
+        Ok(bytes)
+    }
+
+    /// 按输入编码读取输入文件的全部内容。
+    pub fn read_input(&self) -> io::Result<String> {
+        self.input_encoding.decode(self.read_bytes()?)
+    }
+
     pub fn get_text(&mut self) -> std::io::Result<()> {
-        self.plain_text = fs::read_to_string(&self.input_file)?;
+        let bytes = self.read_bytes()?;
+        self.load_text(bytes)
+    }
+
+    /// 与 `get_text` 相同，但从给定的读取器读入，而不是输入文件。
+    pub fn get_text_from<R: Read>(&mut self, mut reader: R) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        self.load_text(bytes)
+    }
+
+    /// 解码读到的字节，处理元数据、注音和反转，得到待加密的文本。
+    fn load_text(&mut self, bytes: Vec<u8>) -> io::Result<()> {
+        self.plain_text = self.input_encoding.decode(bytes)?;
+        self.check_ascii(&self.plain_text, 0)?;
+        if let Some((first_line, rest)) = self.plain_text.split_once('\n')
+            && let Some(metadata) = parse_metadata_line(first_line)
+        {
+            self.input_metadata = metadata;
+            self.plain_text = rest.to_string();
+        }
+        self.warn_if_encrypted_twice();
+        if self.phonetic_input {
+            self.plain_text = from_phonetic(&self.plain_text)?;
+        }
+        self.input_len = self.plain_text.chars().count();
+        if self.invert {
+            self.plain_text = self.atbash(&self.plain_text);
+        }
+        Ok(())
+    }
+
+    /// 设置了 `strict_ascii` 且字母表全为ASCII时，检查文本中没有非ASCII字符。
+    /// `offset` 是文本第一个字符在整个输入中的位置，用于报错。
+    fn check_ascii(&self, text: &str, offset: usize) -> io::Result<()> {
+        if self.strict_ascii
+            && self.alphabet.is_ascii()
+            && let Some((idx, ch)) = text.chars().enumerate().find(|(_, c)| !c.is_ascii())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Non-ASCII character '{ch}' at position {} in the input",
+                    offset + idx
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// 加密时读到的输入已经标记为密文，提醒可能重复加密。
+    fn warn_if_encrypted_twice(&self) {
+        if let Some(message) = self.double_encryption_warning(&self.input_metadata) {
+            warn!("{message}");
+        }
+    }
+
+    /// 输入元数据为 `input_metadata` 时应发出的重复加密警告，不需要警告时为 `None`。
+    pub fn double_encryption_warning(&self, input_metadata: &[(String, String)]) -> Option<String> {
+        (!self.decrypting && is_marked_ciphertext(input_metadata)).then(|| {
+            format!(
+                "The input {} is already marked as ciphertext, it may be encrypted twice",
+                self.input_file
+            )
+        })
+    }
+
+    /// 打开输入和输出，准备分块处理：读取输入第一行的元数据，写出输出的元数据。
+    /// 需要整段文本的选项无法分块处理，设置了它们时返回错误。
+    pub fn open_stream(&mut self) -> io::Result<TextStream> {
+        let unsupported = [
+            (self.json_mode, "JSON input"),
+            (self.phonetic_input, "phonetic input"),
+            (self.phonetic_output, "phonetic output"),
+            (self.side_by_side, "side by side output"),
+            (self.group_size > 0, "grouped output"),
+            (self.transpose.is_some(), "transposition"),
+            (self.split_bytes > 0, "split output"),
+            (self.input_parts > 0, "split input"),
+        ];
+        if let Some((_, what)) = unsupported.iter().find(|(set, _)| *set) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Streaming does not support {what}"),
+            ));
+        }
+
+        let input: Box<dyn Read> = if self.input_file == STDIO_PATH {
+            Box::new(io::stdin())
+        } else {
+            Box::new(fs::File::open(self.input_file)?)
+        };
+        let mut reader = BufReader::with_capacity(STREAM_CHUNK_SIZE, input);
+        let has_metadata = reader
+            .fill_buf()?
+            .get(..METADATA_PREFIX.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(METADATA_PREFIX.as_bytes()));
+        if has_metadata {
+            let mut line = Vec::new();
+            reader.read_until(b'\n', &mut line)?;
+            let line = self.input_encoding.decode(line)?;
+            self.input_metadata =
+                parse_metadata_line(line.trim_end_matches('\n')).unwrap_or_default();
+        }
+        self.warn_if_encrypted_twice();
+
+        let output: Box<dyn Write> = if self.output_file == STDIO_PATH {
+            Box::new(io::stdout())
+        } else {
+            Box::new(fs::File::create(self.output_file)?)
+        };
+        let mut writer = BufWriter::new(output);
+        writer.write_all(&self.output_encoding.encode(&self.metadata_header())?)?;
+        self.input_len = 0;
+        Ok(TextStream {
+            reader,
+            writer,
+            pending: Vec::new(),
+        })
+    }
+
+    /// 读取下一块输入，解码、清理后返回，输入读完时返回 `None`。
+    /// 被块边界截断的多字节字符留到下一块，所以每块都由完整的字符组成。
+    pub fn next_chunk(&mut self, stream: &mut TextStream) -> io::Result<Option<String>> {
+        let buf = stream.reader.fill_buf()?;
+        let read = buf.len();
+        let mut bytes = std::mem::take(&mut stream.pending);
+        bytes.extend_from_slice(buf);
+        stream.reader.consume(read);
+        if read == 0 && bytes.is_empty() {
+            return Ok(None);
+        }
+        // 输入结束时不再等待后续字节，不完整的字符交给解码报错
+        if read > 0
+            && self.input_encoding == Encoding::Utf8
+            && let Err(e) = std::str::from_utf8(&bytes)
+            && e.error_len().is_none()
+        {
+            stream.pending = bytes.split_off(e.valid_up_to());
+        }
+
+        let text = self.input_encoding.decode(bytes)?;
+        self.check_ascii(&text, self.input_len)?;
+        self.input_len += text.chars().count();
+        let text = if self.invert {
+            self.atbash(&text)
+        } else {
+            text
+        };
+        Ok(Some(self.clean(&text)))
+    }
+
+    /// 按输出选项写出一块变换后的文本。
+    pub fn write_chunk(&self, stream: &mut TextStream, text: &str) -> io::Result<()> {
+        let text = if self.invert {
+            self.atbash(text)
+        } else {
+            text.to_string()
+        };
+        let bytes = self
+            .output_encoding
+            .encode(&self.output_case.apply(&text))?;
+        stream.writer.write_all(&bytes)
+    }
+
+    /// 把字母表中的每个字符换成它的埃特巴什补字符（第i个换成倒数第i个），其余字符不变。
+    /// 埃特巴什是对合变换，做两次即还原。
+    pub fn atbash(&self, text: &str) -> String {
+        let letters: Vec<char> = self.alphabet.chars().collect();
+        text.chars()
+            .map(|ch| match letters.iter().position(|&c| c == ch) {
+                Some(idx) => letters[letters.len() - 1 - idx],
+                None => ch,
+            })
+            .collect()
+    }
+
+    pub fn clean(&self, text: &str) -> String {
+        // 保留非字母字符时分隔符也原样保留
+        let text = match &self.group_sep {
+            Some(sep)
+                if !sep.is_empty() && !self.preserve_non_alpha && self.preserve_chars.is_none() =>
+            {
+                text.replace(sep.as_str(), "")
+            }
+            _ => text.to_string(),
+        };
+        self.case_mode
+            .apply(&text)
+            .chars()
+            .filter(|&c| self.keeps(c))
+            .collect()
+    }
+
+    /// 清理时是否保留该字符：字母表中的字符总是保留，其余的看 `preserve_chars`
+    /// 或 `preserve_non_alpha`。
+    fn keeps(&self, c: char) -> bool {
+        self.alphabet.contains(c)
+            || match &self.preserve_chars {
+                Some(chars) => chars.contains(c),
+                None => self.preserve_non_alpha,
+            }
+    }
+
+    /// 把文本按空白切成单词，每个单词分别清理，清理后为空的单词被丢弃。
+    pub fn words(&self, text: &str) -> Vec<String> {
+        text.split_whitespace()
+            .map(|word| self.clean(word))
+            .filter(|word| !word.is_empty())
+            .collect()
+    }
+
+    /// 生成抽认卡：每个单词一行，单词与 `f` 对它的变换结果之间用制表符分隔，可以直接导入 Anki。
+    pub fn flashcards<F: Fn(&str) -> String>(&self, text: &str, f: F) -> String {
+        self.words(text)
+            .iter()
+            .map(|word| format!("{word}\t{}\n", f(word)))
+            .collect()
+    }
+
+    /// 按分组设置把文本切成等长的组，用分隔符连接，例如 `KHOO-RWOR-LD`。
+    pub fn group(&self, text: &str) -> String {
+        if self.group_size == 0 {
+            return text.to_string();
+        }
+        text.chars()
+            .collect::<Vec<char>>()
+            .chunks(self.group_size)
+            .map(|chunk| chunk.iter().collect::<String>())
+            .collect::<Vec<String>>()
+            .join(self.group_sep.as_deref().unwrap_or(" "))
+    }
+
+    /// 不经过 `clean_text` 读取输入的处理方式无法撤销列置换，设置了列置换时返回错误，
+    /// 以免写出无法还原的输出。`what` 说明是哪种处理方式。
+    pub fn reject_transpose(&self, what: &str) -> io::Result<()> {
+        if self.transpose.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{what} does not support a columnar transposition"),
+            ));
+        }
         Ok(())
     }
 
     pub fn clean_text(&mut self) {
-        self.plain_text = self
+        self.plain_text = self.clean(&self.plain_text);
+        if let Some(keyword) = &self.transpose
+            && self.transpose_input
+        {
+            self.plain_text = columnar::decrypt(&self.plain_text, keyword);
+        }
+    }
+
+    /// 用自定义的逐字符变换走完整个文件流程：读取、清理、变换、写出。
+    /// `f` 接收清理后文本中的字符及其位置，便于不写新结构体就试验新的密码。
+    pub fn run_with<F: FnMut(char, usize) -> char>(&mut self, mut f: F) -> io::Result<()> {
+        self.get_text()?;
+        self.clean_text();
+        self.encrypted_text = self
             .plain_text
             .chars()
-            .filter(|c| self.alphabet.contains(*c))
+            .enumerate()
+            .map(|(i, ch)| f(ch, i))
             .collect();
+        self.save_file()
+    }
+
+    /// 读取JSON输入，把每个字符串值清理后交给 `f` 变换，返回重新序列化的JSON文本。
+    /// 输入和输出的元数据保存在顶层对象的 `#!cipher` 键中，见 `take_json_metadata`。
+    pub fn transform_json<F: FnMut(&str) -> String>(&self, f: F) -> io::Result<String> {
+        let (value, input_metadata) = self.parse_json()?;
+        if let Some(message) = self.double_encryption_warning(&input_metadata) {
+            warn!("{message}");
+        }
+        Ok(self.transform_json_input(value, f))
+    }
+
+    /// 读取JSON输入，把其中的元数据保存到 `input_metadata`，返回去掉元数据后的JSON值。
+    /// 需要在变换前检查元数据时（例如密钥指纹）用它代替 `transform_json`。
+    pub fn read_json(&mut self) -> io::Result<Value> {
+        let (value, input_metadata) = self.parse_json()?;
+        self.input_metadata = input_metadata;
+        self.warn_if_encrypted_twice();
+        Ok(value)
+    }
+
+    /// 解析JSON输入，返回JSON值和从中取出的元数据。
+    fn parse_json(&self) -> io::Result<(Value, Vec<(String, String)>)> {
+        self.reject_transpose("JSON input")?;
+        let content = self.read_input()?;
+        let mut value: Value = serde_json::from_str(&content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let input_metadata = take_json_metadata(&mut value);
+        Ok((value, input_metadata))
+    }
+
+    /// 把 `read_json` 读到的JSON值中的每个字符串交给 `f` 变换，返回重新序列化的JSON文本。
+    pub fn transform_json_input<F: FnMut(&str) -> String>(
+        &self,
+        mut value: Value,
+        mut f: F,
+    ) -> String {
+        transform_json_strings(&mut value, &mut |text| {
+            self.transform_json_value(text, &mut f)
+        });
+        if !self.metadata.is_empty() {
+            match &mut value {
+                Value::Object(map) => {
+                    map.insert(
+                        METADATA_PREFIX.to_string(),
+                        Value::String(self.metadata_pairs()),
+                    );
+                }
+                _ => warn!("Metadata can only be written to a JSON object, it is left out"),
+            }
+        }
+        value.to_string()
+    }
+
+    /// 变换JSON中的一个字符串值。与 `clean` 不同，不去掉分组分隔符，
+    /// 没有给出 `preserve_chars` 时保留字母表以外的全部字符；
+    /// 字母按 `case_mode` 转换大小写后交给 `f`，结果再按原文恢复大小写。
+    fn transform_json_value(&self, text: &str, f: &mut dyn FnMut(&str) -> String) -> String {
+        let kept: String = text
+            .chars()
+            .filter(|&c| {
+                self.preserve_chars.is_none()
+                    || self
+                        .case_mode
+                        .apply(&c.to_string())
+                        .chars()
+                        .all(|c| self.keeps(c))
+            })
+            .collect();
+        let folded = self.case_mode.apply(&kept);
+        let output = f(&folded);
+        if folded.chars().count() != kept.chars().count()
+            || output.chars().count() != kept.chars().count()
+        {
+            return output;
+        }
+        kept.chars()
+            .zip(folded.chars())
+            .zip(output.chars())
+            .map(|((original, folded), ch)| match original {
+                _ if original == folded => ch,
+                _ if original.is_lowercase() => ch.to_lowercase().next().unwrap_or(ch),
+                _ => ch.to_uppercase().next().unwrap_or(ch),
+            })
+            .collect()
+    }
+
+    /// 代换密码的密文长度必须与清理后的明文长度一致，用于及早发现字符丢失或重复。
+    pub fn assert_length_preserved(&self) {
+        debug_assert_eq!(
+            self.encrypted_text.chars().count(),
+            self.plain_text.chars().count(),
+            "Substitution cipher changed the text length"
+        );
+    }
+
+    /// 汇总本次加密的统计信息。
+    pub fn stats(&self) -> TextStats {
+        TextStats {
+            input_len: self.input_len,
+            cleaned_len: self.plain_text.chars().count(),
+            output_len: self.encrypted_text.chars().count(),
+            distinct_output_chars: letter_counts(&self.encrypted_text, self.alphabet).len(),
+            output_ioc: index_of_coincidence(&self.encrypted_text, self.alphabet),
+        }
+    }
+
+    /// 按输出选项生成最终写入文件的内容。
+    pub fn render_output(&self) -> String {
+        if self.json_mode {
+            return self.encrypted_text.clone();
+        }
+
+        let encrypted_text = match &self.transpose {
+            Some(keyword) if !self.transpose_input => {
+                columnar::encrypt(&self.encrypted_text, keyword)
+            }
+            _ => self.encrypted_text.clone(),
+        };
+        let encrypted_text = if self.invert {
+            self.atbash(&encrypted_text)
+        } else {
+            encrypted_text
+        };
+
+        let render = |text: &str| {
+            if self.phonetic_output {
+                to_phonetic(text)
+            } else {
+                text.to_string()
+            }
+        };
+
+        if self.side_by_side {
+            // 对照模式下按位置逐行输出，每行是一个明文字符和它对应的密文
+            self.plain_text
+                .chars()
+                .zip(encrypted_text.chars())
+                .map(|(p, c)| format!("{p}\t{}\n", render(&c.to_string())))
+                .collect()
+        } else if self.phonetic_output {
+            to_phonetic(&encrypted_text)
+        } else {
+            self.group(&encrypted_text)
+        }
+    }
+
+    /// 在输出的元数据中标记内容为密文。
+    pub fn mark_ciphertext(&mut self) {
+        if !self.marks_ciphertext() {
+            let (key, value) = CIPHERTEXT_MARKER;
+            self.metadata.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    /// 输出是否会被标记为密文。
+    pub fn marks_ciphertext(&self) -> bool {
+        let (key, value) = CIPHERTEXT_MARKER;
+        self.metadata.iter().any(|(k, v)| k == key && v == value)
+    }
+
+    /// 读到的输入是否带有密文标记。
+    pub fn input_is_ciphertext(&self) -> bool {
+        is_marked_ciphertext(&self.input_metadata)
+    }
+
+    /// 查找输入元数据中某个键的值，键不区分大小写。
+    pub fn input_metadata_value(&self, key: &str) -> Option<&str> {
+        self.input_metadata
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// 输出文件第一行的元数据，没有元数据或输出为JSON时为空。
+    fn metadata_header(&self) -> String {
+        let mut header = String::new();
+        if !self.metadata.is_empty() && !self.json_mode {
+            header.push_str(METADATA_PREFIX);
+            header.push(' ');
+            header.push_str(&self.metadata_pairs());
+            header.push('\n');
+        }
+        header
+    }
+
+    /// 以空格分隔的 `key=value` 形式列出输出的元数据。
+    fn metadata_pairs(&self) -> String {
+        self.metadata
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join(" ")
     }
 
     pub fn save_file(&self) -> std::io::Result<()> {
-        fs::write(&self.output_file, &self.encrypted_text)
+        let mut content = self.metadata_header();
+        if self.json_mode {
+            content.push_str(&self.render_output());
+        } else {
+            content.push_str(&self.output_case.apply(&self.render_output()));
+        }
+        let bytes = self.output_encoding.encode(&content)?;
+        if self.output_file == STDIO_PATH {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&bytes)?;
+            return stdout.flush();
+        }
+        if self.split_bytes == 0 {
+            return fs::write(self.output_file, bytes);
+        }
+
+        // 按字节切分，多字节字符可能被切开，拼接后即可还原
+        let parts: Vec<&[u8]> = if bytes.is_empty() {
+            vec![&[]]
+        } else {
+            bytes.chunks(self.split_bytes).collect()
+        };
+        for (i, part) in parts.iter().enumerate() {
+            fs::write(part_path(self.output_file, i + 1), part)?;
+        }
+        info!("Wrote {} parts of {}", parts.len(), self.output_file);
+        Ok(())
+    }
+
+    /// 文本中字母表内的字符数，即 `map_letters` 会变换的字符数。
+    pub fn count_letters(&self, text: &str) -> usize {
+        text.chars()
+            .filter(|&ch| self.index_of(ch).is_some())
+            .count()
+    }
+
+    /// 清理后文本中第 `position` 个字符是否需要加密。
+    pub fn in_stride(&self, position: usize) -> bool {
+        self.stride <= 1 || position.is_multiple_of(self.stride)
+    }
+
+    /// 用 `f` 变换文本中字母表内的每个字符，`f` 还会收到该字符在字母中的位置，
+    /// 位置从 `start` 开始计数，保留下来的空格和标点不占位置，原样输出。
+    /// 启用 `rayon` 特性时并行处理。
+    pub fn map_letters<F>(&self, text: &str, start: usize, f: F) -> String
+    where
+        F: Fn(char, usize) -> char + Sync,
+    {
+        #[cfg(feature = "rayon")]
+        return self.map_letters_parallel(text, start, f);
+        #[cfg(not(feature = "rayon"))]
+        self.map_letters_serial(text, start, f)
+    }
+
+    /// `map_letters` 的串行实现。
+    pub fn map_letters_serial<F>(&self, text: &str, start: usize, f: F) -> String
+    where
+        F: Fn(char, usize) -> char,
+    {
+        let mut position = start;
+        text.chars()
+            .map(|ch| {
+                if self.index_of(ch).is_none() {
+                    return ch;
+                }
+                position += 1;
+                f(ch, position - 1)
+            })
+            .collect()
+    }
+
+    /// `map_letters` 的并行实现：先串行算出每个字母的位置，再并行变换，结果保持原来的顺序。
+    #[cfg(feature = "rayon")]
+    pub fn map_letters_parallel<F>(&self, text: &str, start: usize, f: F) -> String
+    where
+        F: Fn(char, usize) -> char + Sync,
+    {
+        use rayon::prelude::*;
+
+        let mut position = start;
+        let letters: Vec<(char, Option<usize>)> = text
+            .chars()
+            .map(|ch| {
+                if self.index_of(ch).is_none() {
+                    return (ch, None);
+                }
+                position += 1;
+                (ch, Some(position - 1))
+            })
+            .collect();
+        letters
+            .par_iter()
+            .map(|&(ch, position)| position.map_or(ch, |i| f(ch, i)))
+            .collect()
     }
 
     pub fn change_index(&self, alphabet_len: i32, index: i32, shift: i32) -> usize {
@@ -45,9 +1018,285 @@ impl<'a> Cipher<'a> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_char_to_index() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        assert_eq!(char_to_index(alphabet, 'M'), Some(12));
+        assert_eq!(char_to_index(alphabet, 'm'), Some(12));
+        assert_eq!(char_to_index(alphabet, '1'), None);
+
+        // 字母表本身含有小写字母时区分大小写
+        assert_eq!(char_to_index("abcABC", 'a'), Some(0));
+        assert_eq!(char_to_index("abcABC", 'A'), Some(3));
+    }
+
+    #[test]
+    fn test_validate_alphabet() {
+        assert!(validate_alphabet("ABCDEFGHIJKLMNOPQRSTUVWXYZ").is_ok());
+        assert!(validate_alphabet("").is_err());
+        assert!(validate_alphabet("ABCA").is_err());
+    }
+
+    #[test]
+    fn test_read_bytes_from_stdin_path() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let cipher = CipherBase::new(alphabet, STDIO_PATH, STDIO_PATH);
+        let bytes = cipher.read_bytes_or(io::Cursor::new(b"HELLO")).unwrap();
+        assert_eq!(bytes, b"HELLO");
+
+        // 给出文件路径时不读取标准输入
+        let input_file = tempfile::NamedTempFile::new().expect("Failed to create temporary file");
+        let input_path = input_file.path().to_str().unwrap();
+        std::fs::write(input_path, "WORLD").unwrap();
+        let cipher = CipherBase::new(alphabet, input_path, STDIO_PATH);
+        let bytes = cipher.read_bytes_or(io::Cursor::new(b"HELLO")).unwrap();
+        assert_eq!(bytes, b"WORLD");
+    }
+
+    #[test]
+    fn test_validate_group_sep() {
+        assert!(validate_group_sep("ABCDEFGHIJKLMNOPQRSTUVWXYZ", " ").is_ok());
+        assert!(validate_group_sep("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "").is_ok());
+        assert!(validate_group_sep("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "-X-").is_err());
+        // 小写字母在去掉分隔符之后才转成大写，同样不能用
+        assert!(validate_group_sep("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "x").is_err());
+        assert!(validate_group_sep("0123456789", "-").is_ok());
+        assert!(validate_group_sep("0123456789", "0").is_err());
+    }
+
+    #[test]
+    fn test_encodings() {
+        assert_eq!(Encoding::Latin1.decode(vec![b'C', 0xC9]).unwrap(), "CÉ");
+        assert!(Encoding::Utf8.decode(vec![b'C', 0xC9]).is_err());
+        assert!(Encoding::Ascii.decode(vec![b'C', 0xC9]).is_err());
+
+        assert_eq!(Encoding::Latin1.encode("CÉ").unwrap(), vec![b'C', 0xC9]);
+        assert!(Encoding::Latin1.encode("C€").is_err());
+        assert_eq!(Encoding::Ascii.encode("khoor").unwrap(), b"KHOOR".to_vec());
+        assert!(Encoding::Ascii.encode("É").is_err());
+    }
+
+    #[test]
+    fn test_double_encryption_detected_by_marker() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = tempfile::NamedTempFile::new().expect("Failed to create temporary file");
+        let input_path = input_file.path().to_str().unwrap();
+        let once_file = tempfile::NamedTempFile::new().expect("Failed to create temporary file");
+        let once_path = once_file.path().to_str().unwrap();
+        let twice_file = tempfile::NamedTempFile::new().expect("Failed to create temporary file");
+        let twice_path = twice_file.path().to_str().unwrap();
+        std::fs::write(input_path, "HELLO").unwrap();
+
+        let mut cipher = CipherBase::new(alphabet, input_path, once_path);
+        cipher.mark_ciphertext();
+        cipher.get_text().unwrap();
+        assert!(!cipher.input_is_ciphertext());
+        assert_eq!(
+            cipher.double_encryption_warning(&cipher.input_metadata),
+            None
+        );
+        cipher.encrypted_text = cipher.plain_text.clone();
+        cipher.save_file().unwrap();
+
+        // 再次加密带标记的文件时会发出警告，输出是否标记不影响
+        let mut cipher = CipherBase::new(alphabet, once_path, twice_path);
+        cipher.get_text().unwrap();
+        assert!(cipher.input_is_ciphertext());
+        assert_eq!(cipher.plain_text, "HELLO");
+        let warning = cipher.double_encryption_warning(&cipher.input_metadata);
+        assert!(warning.is_some_and(|message| message.contains("encrypted twice")));
+
+        // 解密带标记的文件是正常用法，不发出警告
+        cipher.decrypting = true;
+        assert_eq!(
+            cipher.double_encryption_warning(&cipher.input_metadata),
+            None
+        );
+    }
+
+    #[test]
+    fn test_json_output_carries_ciphertext_marker() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = tempfile::NamedTempFile::new().expect("Failed to create temporary file");
+        let input_path = input_file.path().to_str().unwrap();
+        std::fs::write(input_path, r#"{"text": "HELLO"}"#).unwrap();
+
+        let mut cipher = CipherBase::new(alphabet, input_path, "");
+        cipher.json_mode = true;
+        cipher.mark_ciphertext();
+        let output = cipher.transform_json(|text| text.to_string()).unwrap();
+        let value: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(value["#!cipher"], "content=ciphertext");
+        assert_eq!(value["text"], "HELLO");
+
+        // 读取时取出元数据，它不会被当作正文变换
+        let mut value = value;
+        let metadata = take_json_metadata(&mut value);
+        assert!(is_marked_ciphertext(&metadata));
+        assert_eq!(value, serde_json::json!({"text": "HELLO"}));
+    }
+
+    #[test]
+    fn test_dyn_cipher_round_trip() {
+        use crate::caesar::CaesarCipher;
+        use crate::enigma::EnigmaMachine;
+        use crate::polyalphabetic::PolyalphabeticCipher;
+        use crate::railfence::RailFenceCipher;
+        use crate::substitution::SubstitutionCipher;
+
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = tempfile::NamedTempFile::new().expect("Failed to create temporary file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = tempfile::NamedTempFile::new().expect("Failed to create temporary file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = tempfile::NamedTempFile::new().expect("Failed to create temporary file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        std::fs::write(input_path, "ATTACKATDAWN").unwrap();
+
+        // 每种密码构造两次：一个加密，一个解密
+        // 构造时给出的方向与调用的方法相反，方法名决定实际的方向
+        fn make<'a>(
+            alphabet: &'a str,
+            input: &'a str,
+            output: &'a str,
+            decrypt: bool,
+        ) -> Vec<Box<dyn Cipher + 'a>> {
+            vec![
+                Box::new(CaesarCipher::new(alphabet, input, output, 3)),
+                Box::new(
+                    PolyalphabeticCipher::new(alphabet, input, output, "LEMON", decrypt).unwrap(),
+                ),
+                Box::new(
+                    SubstitutionCipher::new(
+                        alphabet,
+                        input,
+                        output,
+                        "QWERTYUIOPASDFGHJKLZXCVBNM",
+                        decrypt,
+                    )
+                    .unwrap(),
+                ),
+                Box::new(RailFenceCipher::new(alphabet, input, output, 3, decrypt).unwrap()),
+                Box::new(EnigmaMachine::from_seed(alphabet, input, output, 3, 1).unwrap()),
+            ]
+        }
+
+        for (mut encrypt, mut decrypt) in make(alphabet, input_path, output_path, true)
+            .into_iter()
+            .zip(make(alphabet, output_path, round_trip_path, false))
+        {
+            encrypt.encrypt().unwrap();
+            assert_ne!(
+                std::fs::read_to_string(output_path).unwrap(),
+                "ATTACKATDAWN"
+            );
+            decrypt.decrypt().unwrap();
+            assert_eq!(
+                std::fs::read_to_string(round_trip_path).unwrap(),
+                "ATTACKATDAWN"
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_text_from_reader() {
+        // 输入路径为 "-" 时读取标准输入；这里直接注入读取器
+        let mut cipher = CipherBase::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", STDIO_PATH, STDIO_PATH);
+        cipher.get_text_from(&b"HELLO, WORLD\n"[..]).unwrap();
+        assert_eq!(cipher.plain_text, "HELLO, WORLD\n");
+        cipher.clean_text();
+        assert_eq!(cipher.plain_text, "HELLOWORLD");
+    }
+
+    #[test]
+    fn test_flashcards_one_row_per_word() {
+        use crate::caesar::CaesarCipher;
+
+        let cipher = CaesarCipher::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "", "", 3);
+        let cards = cipher
+            .base()
+            .flashcards("Hello, world!\n  attack  42 ", |word| {
+                cipher.encrypt_string(word)
+            });
+        // 只有标点或数字的单词清理后为空，不生成卡片
+        let rows: Vec<Vec<&str>> = cards.lines().map(|row| row.split('\t').collect()).collect();
+        assert_eq!(
+            rows,
+            vec![
+                vec!["HELLO", "KHOOR"],
+                vec!["WORLD", "ZRUOG"],
+                vec!["ATTACK", "DWWDFN"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_with_matches_caesar() {
+        use crate::caesar::CaesarCipher;
+
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = tempfile::NamedTempFile::new().expect("Failed to create temporary file");
+        let input_path = input_file.path().to_str().unwrap();
+        let custom_file = tempfile::NamedTempFile::new().expect("Failed to create temporary file");
+        let custom_path = custom_file.path().to_str().unwrap();
+        let caesar_file = tempfile::NamedTempFile::new().expect("Failed to create temporary file");
+        let caesar_path = caesar_file.path().to_str().unwrap();
+        std::fs::write(input_path, "HELLO, WORLD").unwrap();
+
+        // 用闭包实现的凯撒密码
+        let mut cipher = CipherBase::new(alphabet, input_path, custom_path);
+        cipher
+            .run_with(|ch, _| {
+                let idx = char_to_index(alphabet, ch).unwrap();
+                alphabet.chars().nth((idx + 3) % alphabet.len()).unwrap()
+            })
+            .unwrap();
+
+        CaesarCipher::new(alphabet, input_path, caesar_path, 3)
+            .encrypt()
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(custom_path).unwrap(),
+            std::fs::read_to_string(caesar_path).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_metadata_header_round_trip() {
+        let output_file = tempfile::NamedTempFile::new().expect("Failed to create temporary file");
+        let output_path = output_file.path().to_str().unwrap();
+
+        let mut cipher = CipherBase::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "input.txt", output_path);
+        cipher.encrypted_text = "KHOOR".to_string();
+        cipher
+            .metadata
+            .push(("fingerprint".to_string(), "1a2b3c".to_string()));
+        cipher.save_file().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output_path).unwrap(),
+            "#!cipher fingerprint=1a2b3c\nKHOOR"
+        );
+
+        // 读回时解析元数据并从正文中去掉
+        let mut cipher = CipherBase::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", output_path, "output.txt");
+        cipher.get_text().unwrap();
+        assert_eq!(cipher.plain_text, "KHOOR");
+        assert_eq!(cipher.input_metadata_value("FINGERPRINT"), Some("1a2b3c"));
+    }
+
+    #[test]
+    fn test_dump_alphabet() {
+        let dump = dump_alphabet("ABCDEFGHIJKLMNOPQRSTUVWXYZ");
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 26);
+        assert_eq!(lines[0], "A=0");
+        assert_eq!(lines[1], "B=1");
+        assert_eq!(lines[25], "Z=25");
+    }
+
     #[test]
     fn test_change_index() {
-        let cipher = Cipher::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "input.txt", "output.txt");
+        let cipher = CipherBase::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "input.txt", "output.txt");
 
         // 测试正常情况
         assert_eq!(cipher.change_index(26, 0, 1), 1); // 0 + 1 = 1
@@ -59,4 +1308,88 @@ mod tests {
         assert_eq!(cipher.change_index(26, 5, -1), 4); // 5 - 1 = 4, 4 % 26 = 4
         assert_eq!(cipher.change_index(26, 5, -25), 6); // 5 - 25 = -20, -20 % 26 = 6
     }
+
+    #[test]
+    fn test_phonetic_round_trip() {
+        assert_eq!(to_phonetic("ABC"), "Alfa Bravo Charlie");
+        assert_eq!(from_phonetic("Alfa Bravo Charlie").unwrap(), "ABC");
+        assert_eq!(from_phonetic("x-ray YANKEE zulu").unwrap(), "XYZ");
+        assert!(from_phonetic("Alfa Banana").is_err());
+    }
+
+    #[test]
+    fn test_side_by_side_output() {
+        let mut cipher = CipherBase::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "input.txt", "output.txt");
+        cipher.plain_text = "HELLO, WORLD".to_string();
+        cipher.clean_text();
+        cipher.encrypted_text = "KHOORZRUOG".to_string();
+        cipher.side_by_side = true;
+
+        let output = cipher.render_output();
+        let rows: Vec<&str> = output.lines().collect();
+        assert_eq!(rows.len(), cipher.plain_text.chars().count());
+        for row in &rows {
+            let columns: Vec<&str> = row.split('\t').collect();
+            assert_eq!(columns.len(), 2);
+            assert!(columns.iter().all(|column| !column.is_empty()));
+        }
+        assert_eq!(rows[0], "H\tK");
+    }
+
+    #[test]
+    fn test_stats_cleaned_length() {
+        let input_file = tempfile::NamedTempFile::new().expect("Failed to create temporary file");
+        let input_path = input_file.path().to_str().unwrap();
+        std::fs::write(input_path, "HELLO, WORLD!").unwrap();
+
+        let mut cipher = CipherBase::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", input_path, "output.txt");
+        cipher.get_text().unwrap();
+        cipher.clean_text();
+        cipher.encrypted_text = "KHOORZRUOG".to_string();
+
+        let stats = cipher.stats();
+        assert_eq!(stats.input_len, 13);
+        assert_eq!(stats.cleaned_len, cipher.plain_text.chars().count());
+        assert_eq!(stats.cleaned_len, 10);
+        assert_eq!(stats.output_len, 10);
+        assert_eq!(stats.distinct_output_chars, 7);
+    }
+
+    #[test]
+    fn test_group_output() {
+        let mut cipher = CipherBase::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "input.txt", "output.txt");
+        cipher.encrypted_text = "KHOORZRUOG".to_string();
+        assert_eq!(cipher.render_output(), "KHOORZRUOG");
+
+        cipher.group_size = 4;
+        assert_eq!(cipher.render_output(), "KHOO RZRU OG");
+
+        cipher.group_sep = Some("-".to_string());
+        assert_eq!(cipher.render_output(), "KHOO-RZRU-OG");
+        assert_eq!(cipher.clean("KHOO-RZRU-OG"), "KHOORZRUOG");
+    }
+
+    #[test]
+    fn test_strict_ascii() {
+        let input_file = tempfile::NamedTempFile::new().expect("Failed to create temporary file");
+        let input_path = input_file.path().to_str().unwrap();
+        std::fs::write(input_path, "CAFÉ").unwrap();
+
+        let mut cipher = CipherBase::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", input_path, "output.txt");
+        cipher.get_text().unwrap();
+        cipher.clean_text();
+        assert_eq!(cipher.plain_text, "CAF");
+
+        // 严格模式下带重音的字符直接报错
+        cipher.strict_ascii = true;
+        assert!(cipher.get_text().is_err());
+    }
+
+    #[test]
+    fn test_atbash() {
+        let cipher = CipherBase::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "input.txt", "output.txt");
+        assert_eq!(cipher.atbash("ABCXYZ"), "ZYXCBA");
+        assert_eq!(cipher.atbash("A-B"), "Z-Y");
+        assert_eq!(cipher.atbash(&cipher.atbash("HELLOWORLD")), "HELLOWORLD");
+    }
 }
@@ -0,0 +1,183 @@
+use crate::cipher::{Cipher, CipherBase};
+use crate::error::{CipherError, Result};
+use std::io;
+
+/// 列置换的读列顺序：按关键词字母排序，相同字母按出现位置排序。
+/// 返回值的第 `i` 项是第 `i` 个被读出的列。
+pub fn column_order(keyword: &str) -> Vec<usize> {
+    let letters: Vec<char> = keyword.chars().collect();
+    let mut order: Vec<usize> = (0..letters.len()).collect();
+    order.sort_by_key(|&col| letters[col]);
+    order
+}
+
+/// 每一列的长度。文本长度不能被列数整除时，最后一行不满，前面几列多一个字符。
+fn column_lengths(len: usize, width: usize) -> Vec<usize> {
+    (0..width)
+        .map(|col| len / width + usize::from(col < len % width))
+        .collect()
+}
+
+/// 列置换加密：按行写成与关键词等宽的表格，再按关键词字母顺序逐列读出。
+/// 关键词为空时原样返回。
+///
+/// ```
+/// assert_eq!(cipher::columnar::encrypt("WEAREDISCOVERED", "ZEBRAS"), "EVACDESERODEWIR");
+/// ```
+pub fn encrypt(text: &str, keyword: &str) -> String {
+    let width = keyword.chars().count();
+    if width == 0 {
+        return text.to_string();
+    }
+    let chars: Vec<char> = text.chars().collect();
+    column_order(keyword)
+        .into_iter()
+        .flat_map(|col| chars.iter().skip(col).step_by(width))
+        .collect()
+}
+
+/// 列置换解密，是 `encrypt` 的逆过程。
+pub fn decrypt(text: &str, keyword: &str) -> String {
+    let width = keyword.chars().count();
+    if width == 0 {
+        return text.to_string();
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let lengths = column_lengths(chars.len(), width);
+
+    let mut columns: Vec<&[char]> = vec![&[]; width];
+    let mut start = 0;
+    for col in column_order(keyword) {
+        columns[col] = &chars[start..start + lengths[col]];
+        start += lengths[col];
+    }
+    (0..chars.len())
+        .map(|i| columns[i % width][i / width])
+        .collect()
+}
+
+/// 列置换密码：明文按行写在关键词下面，按关键词字母顺序逐列读出。
+/// 最后一行不满时不补字符，各列长度由文本长度推算，所以密文与明文等长。
+pub struct ColumnarCipher<'a> {
+    base: CipherBase<'a>,
+    keyword: String,
+    decrypt: bool,
+}
+
+impl<'a> ColumnarCipher<'a> {
+    /// 关键词为空时没有列可读，返回错误。
+    pub fn new(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        keyword: &str,
+        decrypt: bool,
+    ) -> Result<Self> {
+        if keyword.is_empty() {
+            return Err(CipherError::InvalidInput(
+                "The columnar keyword must not be empty".to_string(),
+            ));
+        }
+        Ok(ColumnarCipher {
+            base: CipherBase::new(alphabet, input_file, output_file),
+            keyword: keyword.to_string(),
+            decrypt,
+        })
+    }
+
+    /// 在内存中加密（或按构造时的设置解密）一段文本。
+    pub fn encrypt_string(&self, text: &str) -> String {
+        if self.decrypt {
+            decrypt(text, &self.keyword)
+        } else {
+            encrypt(text, &self.keyword)
+        }
+    }
+
+    pub fn base(&self) -> &CipherBase<'a> {
+        &self.base
+    }
+
+    pub fn base_mut(&mut self) -> &mut CipherBase<'a> {
+        &mut self.base
+    }
+
+    pub fn encrypt(&mut self) -> io::Result<()> {
+        self.base.get_text()?;
+        self.base.clean_text();
+
+        self.base.encrypted_text = self.encrypt_string(&self.base.plain_text);
+        self.base.assert_length_preserved();
+
+        self.base.save_file()
+    }
+}
+
+impl Cipher for ColumnarCipher<'_> {
+    fn encrypt(&mut self) -> io::Result<()> {
+        let decrypt = std::mem::replace(&mut self.decrypt, false);
+        let result = ColumnarCipher::encrypt(self);
+        self.decrypt = decrypt;
+        result
+    }
+
+    fn decrypt(&mut self) -> io::Result<()> {
+        let decrypt = std::mem::replace(&mut self.decrypt, true);
+        let result = ColumnarCipher::encrypt(self);
+        self.decrypt = decrypt;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_order_ties() {
+        // 相同的字母按出现位置排序
+        assert_eq!(column_order("ZEBRAS"), vec![4, 2, 1, 3, 5, 0]);
+        assert_eq!(column_order("BAB"), vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn test_columnar_round_trip() {
+        let plaintext = "WEAREDISCOVEREDFLEEATONCE";
+        for keyword in [
+            "",
+            "A",
+            "ZEBRAS",
+            "LEMON",
+            "KEYKEY",
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+        ] {
+            let ciphertext = encrypt(plaintext, keyword);
+            assert_eq!(ciphertext.len(), plaintext.len());
+            assert_eq!(
+                decrypt(&ciphertext, keyword),
+                plaintext,
+                "keyword {keyword}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_columnar_cipher_repeated_letters() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        // 三列 B A B，最后一行只有G：先读A下面的 BE，再按出现位置读两个B列 ADG、CF
+        let encrypt = ColumnarCipher::new(alphabet, "", "", "BAB", false).unwrap();
+        let decrypt = ColumnarCipher::new(alphabet, "", "", "BAB", true).unwrap();
+        assert_eq!(encrypt.encrypt_string("ABCDEFG"), "BEADGCF");
+        assert_eq!(decrypt.encrypt_string("BEADGCF"), "ABCDEFG");
+
+        // 多个重复字母，且最后一行不满
+        let encrypt = ColumnarCipher::new(alphabet, "", "", "TOMATO", false).unwrap();
+        let decrypt = ColumnarCipher::new(alphabet, "", "", "TOMATO", true).unwrap();
+        let plaintext = "WEAREDISCOVEREDFLEEATONCE";
+        let ciphertext = encrypt.encrypt_string(plaintext);
+        assert_eq!(ciphertext, "ROFOACDTESEADEECWIREEEVLN");
+        assert_eq!(decrypt.encrypt_string(&ciphertext), plaintext);
+
+        assert!(ColumnarCipher::new(alphabet, "", "", "", false).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use clap::Command;
+use log::{info, warn};
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// 从命令行参数中找出 `--config` 指定的配置文件路径。
+fn find_config_path(args: &[String]) -> Option<String> {
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--config" {
+            return iter.next().cloned();
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(path.to_string());
+        }
+    }
+    None
+}
+
+/// 读取配置文件。扩展名为 `.toml` 时按TOML解析，否则按JSON解析。
+pub fn load_config(path: &str) -> io::Result<Value> {
+    let content = fs::read_to_string(path)?;
+    let is_toml = Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+
+    if is_toml {
+        let table: toml::Value =
+            toml::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        serde_json::to_value(table).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    } else {
+        serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// 把单个配置值转换为命令行参数的文本形式。
+fn value_to_args(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) => vec![s.clone()],
+        Value::Array(items) => items.iter().flat_map(value_to_args).collect(),
+        Value::Null => vec![],
+        other => vec![other.to_string()],
+    }
+}
+
+/// 把配置文件中与子命令对应的一节展开成命令行参数，插入到子命令名之后。
+/// 配置文件按子命令分节，每节的键与参数名一致，例如：
+///
+/// ```toml
+/// [caesar]
+/// shift = 5
+/// ```
+///
+/// 由于展开的参数位于用户输入的参数之前，命令行中给出的同名参数会覆盖配置文件中的值。
+pub fn apply_config(cli: &Command, args: Vec<String>) -> io::Result<Vec<String>> {
+    let Some(config_path) = find_config_path(&args) else {
+        return Ok(args);
+    };
+    info!("Reading default arguments from {config_path}");
+    let config = load_config(&config_path)?;
+    let sections = config.as_object().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Config file must contain a table of subcommands",
+        )
+    })?;
+
+    for name in sections.keys() {
+        if cli.find_subcommand(name).is_none() {
+            warn!("Unknown subcommand in config file: {name}");
+        }
+    }
+
+    let Some((position, subcommand)) = args
+        .iter()
+        .enumerate()
+        .skip(1)
+        .find_map(|(i, arg)| cli.find_subcommand(arg).map(|sub| (i, sub)))
+    else {
+        return Ok(args);
+    };
+    let Some(section) = sections
+        .get(subcommand.get_name())
+        .and_then(|section| section.as_object())
+    else {
+        return Ok(args);
+    };
+
+    let mut injected: Vec<String> = Vec::new();
+    for (key, value) in section {
+        let Some(arg) = subcommand
+            .get_arguments()
+            .find(|arg| arg.get_id().as_str() == key.as_str())
+        else {
+            warn!(
+                "Unknown key in config section [{}]: {key}",
+                subcommand.get_name()
+            );
+            continue;
+        };
+        let Some(long) = arg.get_long() else {
+            warn!("Config key {key} cannot be set from a config file");
+            continue;
+        };
+
+        if arg.get_action().takes_values() {
+            injected.push(format!("--{long}"));
+            injected.extend(value_to_args(value));
+        } else if value.as_bool() == Some(true) {
+            injected.push(format!("--{long}"));
+        }
+    }
+
+    let mut merged = args;
+    merged.splice(position + 1..position + 1, injected);
+    Ok(merged)
+}
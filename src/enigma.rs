@@ -1,30 +1,145 @@
-use crate::cipher::Cipher;
+use crate::cipher::{Cipher, CipherBase, char_to_index};
+use crate::error::{CipherError, Result};
 use log::{error, info, warn};
-use rand::{Rng, rng, seq::SliceRandom};
-use serde_json;
-use std::collections::{HashMap, HashSet};
+use rand::{Rng, SeedableRng, rng, rngs::StdRng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Result, Write};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+/// 国防军恩尼格玛的标准转子I到V，按字母A到Z的顺序给出每个触点连到的字母，
+/// 最后是缺口所在的位置：转子从这个位置步进时带动左边的转子。
+const HISTORICAL_ROTORS: [(&str, &str, char); 5] = [
+    ("I", "EKMFLGDQVZNTOWYHXUSPAIBRCJ", 'Q'),
+    ("II", "AJDKSIRUXBLHWTMCQGZNPYFVOE", 'E'),
+    ("III", "BDFHJLCPRTXVZNYEIWGAKMUSQO", 'V'),
+    ("IV", "ESOVPZJAYQUIRHXLNFTGKDCMWB", 'J'),
+    ("V", "VZBRGITYUPSDNHLXAWMJQOFEKC", 'Z'),
+];
+
+/// 历史转子使用的字母表。
+const HISTORICAL_ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// 历史转子的接线，换算成偏移量的形式：从第 `i` 个触点进入的字符前进 `forward[i]` 格，
+/// 反向穿过时从第 `i` 个触点进入的字符前进 `backward[i]` 格。
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+struct Wiring {
+    forward: Vec<usize>,
+    backward: Vec<usize>,
+}
+
+impl Wiring {
+    /// 由字母排列形式的接线换算出正反两个方向的偏移量。
+    fn from_permutation(alphabet: &str, permutation: &str) -> Self {
+        let len = alphabet.len();
+        let targets: Vec<usize> = permutation
+            .chars()
+            .map(|c| alphabet.find(c).expect("Wiring uses the alphabet"))
+            .collect();
+        let mut forward = vec![0; len];
+        let mut backward = vec![0; len];
+        for (contact, &target) in targets.iter().enumerate() {
+            forward[contact] = (target + len - contact) % len;
+            backward[target] = (contact + len - target) % len;
+        }
+        Wiring { forward, backward }
+    }
+
+    /// 检查正反两个方向的接线是否互逆：从某个触点正向走到的触点，反向必须走回原处。
+    fn is_consistent(&self, len: usize) -> bool {
+        self.forward.len() == len
+            && self.backward.len() == len
+            && self.forward.iter().enumerate().all(|(contact, &shift)| {
+                let target = (contact + shift) % len;
+                shift < len
+                    && self.backward[target] < len
+                    && (target + self.backward[target]) % len == contact
+            })
+    }
+}
 
 /// 转子，恩尼格玛的一种核心部件，一般有3个或更多。
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 struct Rotor {
     /// 密码本，上面是乱序的偏移量。
+    #[serde(default)]
     order: Vec<usize>,
     /// 指向密码本上特定偏移量的指针。
     cursor: usize,
+    /// 历史转子的接线。设置后偏移量取决于字符进入的触点，而不是密码本。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wiring: Option<Wiring>,
+    /// 缺口所在的指针位置。为空时按密码本回绕进位，否则按缺口步进。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    notch: Vec<usize>,
 }
 
 impl Rotor {
     /// 设置一个转子，包括其密码本和指针。
     fn new(order: Vec<usize>, cursor: usize) -> Self {
-        Rotor { order, cursor }
+        Rotor {
+            order,
+            cursor,
+            wiring: None,
+            notch: Vec::new(),
+        }
+    }
+
+    /// 按名称（I到V）取出一个历史转子，指针位于A。
+    fn historical(name: &str) -> Result<Self> {
+        let (_, permutation, notch) = HISTORICAL_ROTORS
+            .iter()
+            .find(|(rotor_name, _, _)| rotor_name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                CipherError::InvalidRotorConfig(format!(
+                    "Unknown rotor {name}, expected one of I, II, III, IV, V"
+                ))
+            })?;
+        Ok(Rotor {
+            order: Vec::new(),
+            cursor: 0,
+            wiring: Some(Wiring::from_permutation(HISTORICAL_ALPHABET, permutation)),
+            notch: HISTORICAL_ALPHABET.find(*notch).into_iter().collect(),
+        })
+    }
+
+    /// 指针是否停在缺口上。
+    fn at_notch(&self) -> bool {
+        self.notch.contains(&self.cursor)
+    }
+
+    /// 转子一圈的位置数：历史转子是字母表长度，其余转子是密码本长度。
+    fn positions(&self) -> usize {
+        match &self.wiring {
+            Some(wiring) => wiring.forward.len(),
+            None => self.order.len(),
+        }
+    }
+
+    /// 序号为 `idx` 的字符穿过转子后的序号。`sign` 为1时正向穿过，为-1时反向穿过。
+    /// 环设置 `ring` 把接线相对指针往回转，效果与指针往前少转 `ring` 格相同。
+    fn pass(&self, idx: usize, sign: i32, len: usize, ring: usize) -> usize {
+        let positions = self.positions();
+        let offset = (self.cursor + positions - ring % positions) % positions;
+        let shift = match &self.wiring {
+            None => self.order[offset] as i32 * sign,
+            Some(wiring) => {
+                let contact = (idx + offset) % len;
+                if sign > 0 {
+                    wiring.forward[contact] as i32
+                } else {
+                    wiring.backward[contact] as i32
+                }
+            }
+        };
+        (idx as i32 + shift).rem_euclid(len as i32) as usize
     }
 
     /// 生成密码本，其值在1到字母表长度减1的范围内，并且是乱序的。
-    fn generate_order(&self, alphabet: &str) -> Result<Vec<usize>> {
+    fn generate_order<R: Rng + ?Sized>(&self, alphabet: &str, rng: &mut R) -> Result<Vec<usize>> {
         let mut order: Vec<usize> = (1..alphabet.len()).collect::<Vec<usize>>();
-        order.shuffle(&mut rng());
+        order.shuffle(rng);
         Ok(order)
     }
 
@@ -40,6 +155,18 @@ impl Rotor {
             );
         }
 
+        // 偏移量必须在1到字母表长度减1之间，0会让字母在该位置映射到自身
+        if let Some(&offset) = order_vec
+            .iter()
+            .find(|&&offset| offset == 0 || offset >= alphabet.len())
+        {
+            error!("Invalid offset {offset} in order vector: {order_vec:?}");
+            return Err(CipherError::InvalidRotorConfig(format!(
+                "Rotor offset {offset} is out of range, expected 1 to {}",
+                alphabet.len() - 1
+            )));
+        }
+
         // 检查密码本中是否存在重复元素
         let mut seen = HashSet::new();
         for &item in order_vec {
@@ -56,40 +183,395 @@ impl Rotor {
     }
 
     /// 生成转子的指针。
-    fn generate_cursor(&self) -> usize {
-        rand::rng().random_range(0..self.order.len())
+    fn generate_cursor<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        rng.random_range(0..self.positions())
+    }
+
+    /// 字母形式的指针：字母在字母表中的序号对转子一圈的位置数取模。
+    /// 密码本转子只有字母表长度减1个位置，所以最后一个字母与第一个字母落在同一位置。
+    fn letter_cursor(&self, idx: usize) -> usize {
+        idx % self.positions()
     }
 
     /// 设置转子的指针，需要做合法性校验。
     fn set_cursor(&self, cursor: usize) -> Result<usize> {
-        if cursor < self.order.len() {
+        if cursor < self.positions() {
             Ok(cursor)
         } else {
-            Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "Invalid cursor",
+            Err(CipherError::InvalidRotorConfig(
+                "Invalid cursor".to_string(),
             ))
         }
     }
 
-    /// 转子的步进。
-    fn step(&mut self) {
-        self.cursor = (self.cursor + 1) % self.order.len();
+    /// 镜像转子：每个位置上的偏移量换成其逆元，使字符穿过原转子再穿过它后还原。
+    /// 密码本长度比字母表少1，所以偏移量对密码本长度加1取模。
+    fn inverted(&self) -> Rotor {
+        let modulus = self.order.len() + 1;
+        let order = self
+            .order
+            .iter()
+            .map(|&shift| (modulus - shift % modulus) % modulus)
+            .collect();
+        // 历史转子的镜像就是把正反两个方向的接线对调
+        let wiring = self.wiring.as_ref().map(|wiring| Wiring {
+            forward: wiring.backward.clone(),
+            backward: wiring.forward.clone(),
+        });
+        Rotor {
+            order,
+            cursor: self.cursor,
+            wiring,
+            notch: self.notch.clone(),
+        }
+    }
+
+    /// 转子的步进，每次前进 `step_size` 格。返回这一步是否越过了密码本的末尾，
+    /// 越过时需要带动下一个转子。
+    fn step(&mut self, step_size: usize) -> bool {
+        let len = self.positions();
+        let next = self.cursor + step_size % len;
+        self.cursor = next % len;
+        next >= len
+    }
+}
+
+/// 指针文件中的一个指针：数字直接是指针位置，字母要按转子的位置数换算。
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CursorSetting {
+    Number(usize),
+    /// 字母在字母表中的序号。
+    Letter(usize),
+}
+
+/// 解析插线板文件的内容，每行一对字母，例如 `A-C`。
+fn parse_plugboard(content: &str) -> Result<HashMap<char, char>> {
+    let mut plugboard: HashMap<char, char> = HashMap::new();
+
+    for line in content.lines() {
+        if let Some((left, right)) = line.split_once('-') {
+            let left = left
+                .trim()
+                .chars()
+                .next()
+                .ok_or_else(|| {
+                    CipherError::InvalidPlugboard("Invalid plugboard format".to_string())
+                })?
+                .to_ascii_uppercase();
+            let right = right
+                .trim()
+                .chars()
+                .next()
+                .ok_or_else(|| {
+                    CipherError::InvalidPlugboard("Invalid plugboard format".to_string())
+                })?
+                .to_ascii_uppercase();
+
+            // 检查重复键
+            if plugboard.contains_key(&left) {
+                error!(
+                    "Duplicate key found in plugboard: {}. Key already exists.",
+                    left
+                );
+                return Err(CipherError::InvalidPlugboard(
+                    "Duplicate key in plugboard".to_string(),
+                ));
+            }
+
+            // 检查重复值
+            if plugboard.values().any(|&v| v == right) {
+                error!(
+                    "Duplicate value found in plugboard: {}. Value already exists.",
+                    right
+                );
+                return Err(CipherError::InvalidPlugboard(
+                    "Duplicate value in plugboard".to_string(),
+                ));
+            }
+
+            plugboard.insert(left, right);
+            plugboard.insert(right, left);
+        } else {
+            return Err(CipherError::InvalidPlugboard(
+                "Invalid plugboard format".to_string(),
+            ));
+        }
+    }
+
+    Ok(plugboard)
+}
+
+/// 解析反射器文件的内容：第一行是一个JSON对象。
+fn parse_reflector(content: &str) -> Result<HashMap<char, char>> {
+    let reflector_str = content
+        .lines()
+        .next()
+        .ok_or_else(|| CipherError::InvalidReflector("File is empty".to_string()))?;
+    serde_json::from_str(reflector_str).map_err(|e| CipherError::InvalidReflector(e.to_string()))
+}
+
+/// 解析命令行上给出的反射器配对，如 `AB CD EF`，每一项恰好是两个字母。
+fn parse_reflector_pairs(pairs: &[String]) -> Result<HashMap<char, char>> {
+    let mut reflector = HashMap::new();
+    for pair in pairs {
+        let letters: Vec<char> = pair.chars().map(|c| c.to_ascii_uppercase()).collect();
+        let [left, right] = letters[..] else {
+            error!("Invalid reflector pair: {pair}");
+            return Err(CipherError::InvalidReflector(format!(
+                "Invalid reflector pair {pair}, expected two letters"
+            )));
+        };
+        if reflector.contains_key(&left) || reflector.contains_key(&right) {
+            error!("Letter used in more than one reflector pair: {pair}");
+            return Err(CipherError::InvalidReflector(format!(
+                "Reflector pair {pair} reuses a letter from an earlier pair"
+            )));
+        }
+        reflector.insert(left, right);
+        reflector.insert(right, left);
+    }
+    Ok(reflector)
+}
+
+/// 检查一组字母配对：每个字母都在字母表中，不与自身配对，并且配对是对称的。
+/// `invalid` 决定报告成插线板还是反射器的错误。
+fn check_pairing(
+    alphabet: &str,
+    pairs: &HashMap<char, char>,
+    invalid: fn(String) -> CipherError,
+) -> Result<()> {
+    let mut keys: Vec<&char> = pairs.keys().collect();
+    keys.sort();
+    for &left in keys {
+        let right = pairs[&left];
+        let problem = if !alphabet.contains(left) || !alphabet.contains(right) {
+            format!("{left}-{right} is not within the alphabet")
+        } else if left == right {
+            format!("{left} is connected to itself")
+        } else if pairs.get(&right) != Some(&left) {
+            format!("{left}-{right} is not matched by {right}-{left}")
+        } else {
+            continue;
+        };
+        let err = invalid(problem);
+        error!("{err}");
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// 需要规范化的配置文件类型。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigKind {
+    Plugboard,
+    Reflector,
+}
+
+/// 校验插线板或反射器文件，并以规范形式原地改写：字母转为大写，配对按字母排序。
+/// 校验失败时返回错误，文件保持不变。
+pub fn normalize_config(alphabet: &str, file: &str, kind: ConfigKind) -> Result<()> {
+    let content = std::fs::read_to_string(file)?;
+    let uppercase = !alphabet.chars().any(char::is_lowercase);
+    let normalize = |ch: char| {
+        if uppercase {
+            ch.to_ascii_uppercase()
+        } else {
+            ch
+        }
+    };
+
+    let normalized = match kind {
+        ConfigKind::Plugboard => {
+            let plugboard = parse_plugboard(&content)?;
+            check_pairing(alphabet, &plugboard, CipherError::InvalidPlugboard)?;
+            let mut pairs: Vec<(char, char)> =
+                plugboard.into_iter().filter(|(l, r)| l < r).collect();
+            pairs.sort();
+            pairs
+                .iter()
+                .map(|(left, right)| format!("{left}-{right}\n"))
+                .collect::<String>()
+        }
+        ConfigKind::Reflector => {
+            let reflector: HashMap<char, char> = parse_reflector(&content)?
+                .into_iter()
+                .map(|(left, right)| (normalize(left), normalize(right)))
+                .collect();
+            check_pairing(alphabet, &reflector, CipherError::InvalidReflector)?;
+            EnigmaMachine::check_reflector_coverage(alphabet, &reflector)?;
+            let sorted: std::collections::BTreeMap<char, char> = reflector.into_iter().collect();
+            format!("{}\n", serde_json::to_string(&sorted)?)
+        }
+    };
+
+    info!("Rewriting {file} in canonical form");
+    Ok(std::fs::write(file, normalized)?)
+}
+
+/// 演示模式下随机生成的插线板连线数，与历史上常用的10对一致。
+const DEMO_PLUGBOARD_PAIRS: usize = 10;
+
+/// 反射器的来源。
+#[derive(Clone, Copy, Debug)]
+pub enum ReflectorSource<'s> {
+    /// 随机生成，并保存到该文件。
+    Generate(&'s str),
+    /// 从该文件载入。
+    File(&'s str),
+    /// 直接给出的字母配对，如 `["AB", "CD"]`。
+    Pairs(&'s [String]),
+}
+
+impl<'s> ReflectorSource<'s> {
+    /// 按 `--reflector_from` 的取值选择生成或载入。
+    pub fn from_flag(reflector_from: &str, reflector_file: &'s str) -> Self {
+        if reflector_from == "m" {
+            ReflectorSource::Generate(reflector_file)
+        } else {
+            ReflectorSource::File(reflector_file)
+        }
+    }
+}
+
+/// 字符经反射器返回时穿过转子的顺序，用于诊断加解密模型。
+/// 只对历史转子起作用：密码本转子只是把字符移动若干格，偏移量相加与顺序无关。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RotorPassOrder {
+    /// 返回时仍按第一个转子到最后一个转子的顺序，这是原来的行为。
+    #[default]
+    Same,
+    /// 返回时从最后一个转子倒序穿回第一个转子，与实际机器的电流路径一致。
+    Reversed,
+}
+
+/// 报文指示器：历史上操作员在报文开头把报文密钥连续加密两次。
+#[derive(Clone, Debug, PartialEq)]
+pub enum Indicator {
+    /// 加密时把给定的报文密钥加密两次放在密文开头，再以该密钥作为转子起始位置。
+    Write(String),
+    /// 解密时从密文开头读出报文密钥，并以它作为其余密文的转子起始位置。
+    Read,
+}
+
+/// 无线电报文格式：四个字母一组，开头一行是组数，有报文指示器时跟在组数后面。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RadioFormat {
+    /// 加密时把密文写成无线电报文。
+    Write,
+    /// 解密时先从无线电报文中取出指示器和密文，去掉分组和报头。
+    Read,
+}
+
+/// 无线电报文每组的字母数。
+const RADIO_GROUP_SIZE: usize = 4;
+/// 无线电报文每行的组数。
+const RADIO_GROUPS_PER_LINE: usize = 10;
+
+/// 把密文写成无线电报文，例如 `3 = QSZVZG =` 后接 `ABCD EFGH IJ`。
+/// `indicator` 为空时报头只有组数。
+pub fn to_radio_format(indicator: &str, text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let groups: Vec<String> = chars
+        .chunks(RADIO_GROUP_SIZE)
+        .map(|group| group.iter().collect())
+        .collect();
+    let mut message = if indicator.is_empty() {
+        format!("{} =\n", groups.len())
+    } else {
+        format!("{} = {indicator} =\n", groups.len())
+    };
+    for line in groups.chunks(RADIO_GROUPS_PER_LINE) {
+        message.push_str(&line.join(" "));
+        message.push('\n');
+    }
+    message
+}
+
+/// 解析无线电报文，返回指示器与去掉分组后的密文连在一起的文本。
+/// 报头中的组数与正文不符时返回 `InvalidData` 错误。
+pub fn parse_radio_format(message: &str) -> Result<String> {
+    let invalid = CipherError::InvalidInput;
+    let (header, body) = message.split_once('\n').unwrap_or((message, ""));
+    let fields: Vec<&str> = header
+        .split('=')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .collect();
+    let (count, indicator) = match fields.as_slice() {
+        [count] => (count, ""),
+        [count, indicator] => (count, *indicator),
+        _ => return Err(invalid(format!("Invalid radio message header {header:?}"))),
+    };
+    let count: usize = count
+        .parse()
+        .map_err(|e| invalid(format!("Invalid group count {count:?}: {e}")))?;
+    let text: String = body.chars().filter(|c| !c.is_whitespace()).collect();
+    let groups = text.chars().count().div_ceil(RADIO_GROUP_SIZE);
+    if groups != count {
+        return Err(invalid(format!(
+            "The header announces {count} groups, the message has {groups}"
+        )));
     }
+    Ok(format!("{indicator}{text}"))
+}
+
+/// `save_state` 写出的机器状态：反射器、各转子的密码本和指针、插线板，以及决定电路走向的设置。
+/// 映射按字母排序写出，同一台机器总是得到相同的文件。镜像转子保存的是镜像之后的接线。
+#[derive(Serialize, Deserialize)]
+struct MachineState {
+    reflector: BTreeMap<char, char>,
+    rotors: Vec<Rotor>,
+    plugboard: BTreeMap<char, char>,
+    #[serde(default)]
+    ring_settings: Vec<usize>,
+    #[serde(default)]
+    rotor_pass_order: RotorPassOrder,
+    #[serde(default)]
+    step_first: bool,
+    #[serde(default = "default_step_size")]
+    step_size: usize,
+    #[serde(default)]
+    single_step: bool,
+    #[serde(default)]
+    plugboard2: BTreeMap<char, char>,
+}
+
+/// 旧的状态文件没有记录步进格数，按默认的1格处理。
+fn default_step_size() -> usize {
+    1
 }
 
-/// 恩尼格玛机的一种实现方式，它包含一个Cipher结构体，并且追加了反射器、转子序列和插线板这些新字段。
+/// 恩尼格玛机的一种实现方式，它包含一个CipherBase结构体，并且追加了反射器、转子序列和插线板这些新字段。
+#[derive(Clone)]
 pub struct EnigmaMachine<'a> {
-    base: Cipher<'a>,
+    base: CipherBase<'a>,
     reflector: HashMap<char, char>,
     rotors: Vec<Rotor>,
     plugboard: HashMap<char, char>,
+    /// 实验性的第二块插线板，位于最后一个转子与反射器之间，为空时不起作用。
+    plugboard2: HashMap<char, char>,
+    rotor_pass_order: RotorPassOrder,
+    indicator: Option<Indicator>,
+    /// 机器设置完成时各转子的指针，加密过程中的步进不会改变它。
+    initial_cursors: Vec<usize>,
+    /// 实验性设置：转子每次步进的格数，默认为1。
+    step_size: usize,
+    /// 为真时按键先步进转子再接通电路，与历史上的机器一致；否则先替换再步进。
+    step_first: bool,
+    /// 各转子的环设置，顺序与转子相同，默认全为0。
+    ring_settings: Vec<usize>,
+    /// 教学用的简化模式：只有第一个转子步进，不向后进位。
+    single_step: bool,
+    /// 是否读写无线电报文格式。
+    radio_format: Option<RadioFormat>,
 }
 
 impl<'a> EnigmaMachine<'a> {
     /// 创建一个恩尼格玛机，设置其反射器、转子序列和插线板。
     /// 反射器和转子序列可以是生成的，也可以是载入的。
     /// 插线板是由人工设置的，该恩尼格玛机自动载入。
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         alphabet: &'a str,
         input_file: &'a str,
@@ -101,45 +583,335 @@ impl<'a> EnigmaMachine<'a> {
         plugboard_file: &str,
         reflector_from: &str,
         rotors_from: &str,
+    ) -> Result<Self> {
+        Self::with_reflector(
+            alphabet,
+            input_file,
+            output_file,
+            ReflectorSource::from_flag(reflector_from, reflector_file),
+            rotor_num,
+            passwords_file,
+            rotors_cursor_file,
+            plugboard_file,
+            rotors_from,
+        )
+    }
+
+    /// 与 `new` 相同，但反射器可以来自任意一种 `ReflectorSource`。
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_reflector(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        reflector: ReflectorSource,
+        rotor_num: usize,
+        passwords_file: &str,
+        rotors_cursor_file: &str,
+        plugboard_file: &str,
+        rotors_from: &str,
+    ) -> Result<Self> {
+        if matches!(reflector, ReflectorSource::Generate(_)) || rotors_from == "m" {
+            let mut files = Vec::new();
+            if let ReflectorSource::Generate(file) | ReflectorSource::File(file) = reflector {
+                files.push(("reflector_file", file));
+            }
+            files.extend([
+                ("passwords_file", passwords_file),
+                ("rotors_cursor_file", rotors_cursor_file),
+                ("plugboard_file", plugboard_file),
+            ]);
+            Self::check_distinct_files(&files)?;
+        }
+
+        let mut enigma = Self::blank(alphabet, input_file, output_file, rotor_num);
+
+        enigma.reflector = enigma.set_reflector(reflector, alphabet)?;
+        enigma.rotors = enigma.set_rotors(
+            alphabet,
+            rotor_num,
+            passwords_file,
+            rotors_cursor_file,
+            rotors_from,
+        )?;
+        enigma.plugboard = enigma.set_plugboard(plugboard_file)?;
+        enigma.remember_initial_cursors();
+
+        Ok(enigma)
+    }
+
+    /// 用历史转子组装一台机器。`rotor_names` 按从左到右的顺序给出各槽位上的转子，
+    /// 如 `["I", "II", "III"]`，最右边的转子转得最快。所有转子从A开始，
+    /// 返回路径按相反的顺序穿过转子，按键时先步进再加密。只支持A到Z的字母表。
+    pub fn historical(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        rotor_names: &[String],
+        reflector: ReflectorSource,
+        plugboard_file: &str,
+    ) -> Result<Self> {
+        if alphabet != HISTORICAL_ALPHABET {
+            return Err(CipherError::InvalidInput(format!(
+                "Historical rotors need the alphabet {HISTORICAL_ALPHABET}"
+            )));
+        }
+        if rotor_names.is_empty() {
+            return Err(CipherError::InvalidRotorConfig(
+                "At least one rotor is needed".to_string(),
+            ));
+        }
+
+        let mut enigma = Self::blank(alphabet, input_file, output_file, rotor_names.len());
+        enigma.reflector = enigma.set_reflector(reflector, alphabet)?;
+        enigma.rotors = rotor_names
+            .iter()
+            .rev()
+            .map(|name| Rotor::historical(name))
+            .collect::<Result<Vec<Rotor>>>()?;
+        enigma.plugboard = enigma.set_plugboard(plugboard_file)?;
+        enigma.rotor_pass_order = RotorPassOrder::Reversed;
+        enigma.step_first = true;
+        enigma.remember_initial_cursors();
+
+        Ok(enigma)
+    }
+
+    /// 从一个种子确定性地生成反射器、转子和插线板，不读写任何配置文件。
+    /// 相同的种子总是得到相同的机器，便于教学演示时复现。
+    pub fn from_seed(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        rotor_num: usize,
+        seed: u64,
+    ) -> Result<Self> {
+        info!("Building machine from demo seed {seed}");
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut enigma = Self::blank(alphabet, input_file, output_file, rotor_num);
+
+        enigma.reflector = enigma.random_reflector(alphabet, &mut rng)?;
+        for rotor in enigma.rotors.iter_mut() {
+            rotor.order = rotor.generate_order(alphabet, &mut rng)?;
+            rotor.cursor = rotor.generate_cursor(&mut rng);
+        }
+        let pairs = DEMO_PLUGBOARD_PAIRS.min(alphabet.chars().count() / 2);
+        enigma.plugboard = enigma.random_plugboard(alphabet, pairs, &mut rng);
+        enigma.remember_initial_cursors();
+
+        Ok(enigma)
+    }
+
+    /// 把整台机器的当前状态写入一个JSON文件，代替反射器、密码本、指针和插线板四个文件。
+    /// 转子指针取当前位置，加密前保存就能用 `load_state` 复现这台机器。
+    /// 环设置、镜像转子、步进方式和第二块插线板等设置也一并保存。
+    pub fn save_state(&self, state_file: &str) -> Result<()> {
+        let state = MachineState {
+            reflector: self.reflector.iter().map(|(&k, &v)| (k, v)).collect(),
+            rotors: self.rotors.clone(),
+            plugboard: self.plugboard.iter().map(|(&k, &v)| (k, v)).collect(),
+            ring_settings: self.ring_settings.clone(),
+            rotor_pass_order: self.rotor_pass_order,
+            step_first: self.step_first,
+            step_size: self.step_size,
+            single_step: self.single_step,
+            plugboard2: self.plugboard2.iter().map(|(&k, &v)| (k, v)).collect(),
+        };
+        info!("Saving machine state to {state_file}");
+        std::fs::write(state_file, serde_json::to_string_pretty(&state)? + "\n")?;
+        Ok(())
+    }
+
+    /// 从 `save_state` 写出的文件组装一台机器，并做与分别载入各文件时相同的校验。
+    pub fn load_state(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        state_file: &str,
+    ) -> Result<Self> {
+        info!("Loading machine state from {state_file}");
+        let state: MachineState = serde_json::from_str(&std::fs::read_to_string(state_file)?)
+            .map_err(|e| {
+                CipherError::InvalidInput(format!("Failed to parse state file {state_file}: {e}"))
+            })?;
+        if state.rotors.is_empty() {
+            return Err(CipherError::InvalidRotorConfig(
+                "At least one rotor is needed".to_string(),
+            ));
+        }
+
+        let mut enigma = Self::blank(alphabet, input_file, output_file, state.rotors.len());
+        enigma.reflector = state.reflector.into_iter().collect();
+        check_pairing(alphabet, &enigma.reflector, CipherError::InvalidReflector)?;
+        Self::check_reflector_coverage(alphabet, &enigma.reflector)?;
+
+        enigma.rotors = state
+            .rotors
+            .into_iter()
+            .map(|mut rotor| {
+                match &rotor.wiring {
+                    Some(wiring) => {
+                        let len = alphabet.chars().count();
+                        if wiring.forward.len() != len || wiring.backward.len() != len {
+                            return Err(CipherError::InvalidRotorConfig(format!(
+                                "Rotor wiring must have {len} contacts"
+                            )));
+                        }
+                        if !wiring.is_consistent(len) {
+                            return Err(CipherError::InvalidRotorConfig(
+                                "The backward rotor wiring does not undo the forward wiring"
+                                    .to_string(),
+                            ));
+                        }
+                    }
+                    None => rotor.order = rotor.set_order(alphabet, &rotor.order)?,
+                }
+                rotor.cursor = rotor.set_cursor(rotor.cursor)?;
+                Ok(rotor)
+            })
+            .collect::<Result<Vec<Rotor>>>()?;
+
+        enigma.plugboard = state.plugboard.into_iter().collect();
+        check_pairing(alphabet, &enigma.plugboard, CipherError::InvalidPlugboard)?;
+        enigma.plugboard2 = state.plugboard2.into_iter().collect();
+        check_pairing(alphabet, &enigma.plugboard2, CipherError::InvalidPlugboard)?;
+        enigma.set_step_size(state.step_size)?;
+        enigma.single_step = state.single_step;
+        if !state.ring_settings.is_empty() {
+            enigma.set_ring_settings(state.ring_settings)?;
+        }
+        enigma.rotor_pass_order = state.rotor_pass_order;
+        enigma.step_first = state.step_first;
+        enigma.remember_initial_cursors();
+
+        Ok(enigma)
+    }
+
+    /// 各转子当前的指针，顺序与指针文件相同（转得最快的转子在前）。
+    /// 加密之后读取，就是下一段报文接着加密时的起始位置。
+    pub fn rotor_positions(&self) -> Vec<usize> {
+        self.rotors.iter().map(|rotor| rotor.cursor).collect()
+    }
+
+    /// 记录各转子当前的指针，作为机器的初始设置。
+    fn remember_initial_cursors(&mut self) {
+        self.initial_cursors = self.rotors.iter().map(|rotor| rotor.cursor).collect();
+    }
+
+    /// 比较两台机器的设置是否相同：反射器、转子密码本、环设置、初始指针和插线板。
+    /// 加密过程中转子的步进不影响比较结果。
+    pub fn config_eq(&self, other: &EnigmaMachine) -> bool {
+        self.reflector == other.reflector
+            && self.rotors.len() == other.rotors.len()
+            && self
+                .rotors
+                .iter()
+                .zip(&other.rotors)
+                .all(|(a, b)| a.order == b.order && a.wiring == b.wiring && a.notch == b.notch)
+            && self.ring_settings == other.ring_settings
+            && self.initial_cursors == other.initial_cursors
+            && self.plugboard == other.plugboard
+            && self.plugboard2 == other.plugboard2
+    }
+
+    /// 创建一个尚未设置反射器、转子和插线板的空白机器。
+    fn blank(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        rotor_num: usize,
     ) -> Self {
-        let mut enigma = EnigmaMachine {
-            base: Cipher::new(alphabet, input_file, output_file),
+        EnigmaMachine {
+            base: CipherBase::new(alphabet, input_file, output_file),
             reflector: HashMap::new(),
             rotors: vec![Rotor::new(vec![], 0); rotor_num],
             plugboard: HashMap::new(),
-        };
+            plugboard2: HashMap::new(),
+            rotor_pass_order: RotorPassOrder::Same,
+            indicator: None,
+            initial_cursors: Vec::new(),
+            step_size: 1,
+            step_first: false,
+            ring_settings: vec![0; rotor_num],
+            single_step: false,
+            radio_format: None,
+        }
+    }
 
-        enigma.reflector = enigma
-            .set_reflector(reflector_from, alphabet, reflector_file)
-            .unwrap();
-        enigma.rotors = enigma
-            .set_rotors(
-                alphabet,
-                rotor_num,
-                passwords_file,
-                rotors_cursor_file,
-                rotors_from,
-            )
-            .unwrap();
-        enigma.plugboard = enigma.set_plugboard(plugboard_file).unwrap();
+    /// 随机选取若干对字母作为插线板连线。
+    fn random_plugboard<R: Rng + ?Sized>(
+        &self,
+        alphabet: &str,
+        pairs: usize,
+        rng: &mut R,
+    ) -> HashMap<char, char> {
+        let mut plugs: Vec<char> = alphabet.chars().collect();
+        plugs.shuffle(rng);
 
-        enigma
+        let mut plugboard: HashMap<char, char> = HashMap::new();
+        for pair in plugs.chunks_exact(2).take(pairs) {
+            plugboard.insert(pair[0], pair[1]);
+            plugboard.insert(pair[1], pair[0]);
+        }
+        plugboard
+    }
+
+    /// 生成模式下会写入配置文件，若两个角色指向同一路径，写入会互相覆盖，因此必须各不相同。
+    fn check_distinct_files(files: &[(&str, &str)]) -> Result<()> {
+        for (i, (role, path)) in files.iter().enumerate() {
+            for (other_role, other_path) in &files[i + 1..] {
+                if Path::new(path) == Path::new(other_path) {
+                    error!("{role} and {other_role} both point to {path}");
+                    return Err(CipherError::InvalidInput(format!(
+                        "{role} and {other_role} must be different files, both are {path}"
+                    )));
+                }
+            }
+        }
+        Ok(())
     }
 
-    /// 设置反射器，分生成和载入两种方式。
+    /// 设置反射器，可以生成、从文件载入，或由直接给出的配对构成。
     fn set_reflector(
         &self,
-        reflector_from: &str,
+        source: ReflectorSource,
         alphabet: &str,
-        reflector_file: &str,
     ) -> Result<HashMap<char, char>> {
-        if reflector_from == "m" {
-            info!("Creating reflector and save it to: {}", reflector_file);
-            self.create_reflector(alphabet, reflector_file)
-        } else {
-            info!("Reading reflector from: {}", reflector_file);
-            self.load_reflector(reflector_file)
+        let reflector = match source {
+            ReflectorSource::Generate(reflector_file) => {
+                info!("Creating reflector and save it to: {}", reflector_file);
+                self.create_reflector(alphabet, reflector_file)?
+            }
+            ReflectorSource::File(reflector_file) => {
+                info!("Reading reflector from: {}", reflector_file);
+                self.load_reflector(reflector_file)?
+            }
+            ReflectorSource::Pairs(pairs) => {
+                info!("Building reflector from {} pairs", pairs.len());
+                let reflector = parse_reflector_pairs(pairs)?;
+                check_pairing(alphabet, &reflector, CipherError::InvalidReflector)?;
+                reflector
+            }
+        };
+        Self::check_reflector_coverage(alphabet, &reflector)?;
+        Ok(reflector)
+    }
+
+    /// 反射器必须恰好覆盖整个字母表，否则遗漏的字符会原样穿过反射器，破坏可逆性。
+    fn check_reflector_coverage(alphabet: &str, reflector: &HashMap<char, char>) -> Result<()> {
+        let expected: HashSet<char> = alphabet.chars().collect();
+        let actual: HashSet<char> = reflector.keys().copied().collect();
+        if expected == actual {
+            return Ok(());
         }
+
+        let mut missing: Vec<char> = expected.difference(&actual).copied().collect();
+        let mut extra: Vec<char> = actual.difference(&expected).copied().collect();
+        missing.sort();
+        extra.sort();
+        error!("Reflector does not cover the alphabet. Missing: {missing:?}, extra: {extra:?}");
+        Err(CipherError::InvalidReflector(format!(
+            "Reflector does not cover the alphabet. Missing: {missing:?}, extra: {extra:?}"
+        )))
     }
 
     /// 创建一个反射器，并记录到文件中。
@@ -148,8 +920,29 @@ impl<'a> EnigmaMachine<'a> {
         alphabet: &str,
         reflector_file: &str,
     ) -> Result<HashMap<char, char>> {
+        let reflector = self.random_reflector(alphabet, &mut rng())?;
+
+        let reflector_str = serde_json::to_string(&reflector)?;
+        let mut file = File::create(reflector_file)?;
+        file.write_all(reflector_str.as_bytes())?;
+
+        Ok(reflector)
+    }
+
+    /// 把打乱后的字母两两配对，得到一个随机反射器。
+    fn random_reflector<R: Rng + ?Sized>(
+        &self,
+        alphabet: &str,
+        rng: &mut R,
+    ) -> Result<HashMap<char, char>> {
+        if !alphabet.chars().count().is_multiple_of(2) {
+            return Err(CipherError::InvalidReflector(
+                "A reflector pairs up letters, so the alphabet length must be even".to_string(),
+            ));
+        }
+
         let mut plugs: Vec<char> = alphabet.chars().collect();
-        plugs.shuffle(&mut rng());
+        plugs.shuffle(rng);
 
         let num = plugs.len() / 2;
         let mut reflector: HashMap<char, char> = HashMap::new();
@@ -160,26 +953,18 @@ impl<'a> EnigmaMachine<'a> {
             reflector.insert(right, left);
         }
 
-        let reflector_str = serde_json::to_string(&reflector)?;
-        let mut file = File::create(reflector_file)?;
-        file.write_all(reflector_str.as_bytes())?;
-
         Ok(reflector)
     }
 
-    /// 载入一个反射器，是从文件读取的。
+    /// 载入一个反射器，是从文件读取的。手工编辑过的文件可能把字母映射到自身
+    /// 或不对称，这样的反射器会破坏“字母不会加密成自身”的性质，因此载入时就拒绝。
     fn load_reflector(&self, reflector_file: &str) -> Result<HashMap<char, char>> {
-        let file = File::open(reflector_file)?;
-        let reader = BufReader::new(file);
-
-        let reflector_str = reader
-            .lines()
-            .next()
-            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "File is empty"))??;
-
-        let reflector: HashMap<char, char> = serde_json::from_str(&reflector_str)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-
+        let reflector = parse_reflector(&std::fs::read_to_string(reflector_file)?)?;
+        check_pairing(
+            self.base.alphabet,
+            &reflector,
+            CipherError::InvalidReflector,
+        )?;
         Ok(reflector)
     }
 
@@ -221,11 +1006,11 @@ impl<'a> EnigmaMachine<'a> {
         for _ in 0..rotor_num {
             let mut rotor = Rotor::new(vec![], 0);
 
-            rotor.order = rotor.generate_order(alphabet).unwrap();
+            rotor.order = rotor.generate_order(alphabet, &mut rng())?;
             let order_str = serde_json::to_string(&rotor.order)?;
             passwords_file.write_all(format!("{}\n", order_str).as_bytes())?;
 
-            rotor.cursor = rotor.generate_cursor();
+            rotor.cursor = rotor.generate_cursor(&mut rng());
             rotors_cursor_file.write_all(format!("{}\n", rotor.cursor).as_bytes())?;
 
             rotors.push(rotor);
@@ -241,18 +1026,24 @@ impl<'a> EnigmaMachine<'a> {
         passwords_file: &str,
         rotors_cursor_file: &str,
     ) -> Result<Vec<Rotor>> {
-        let passwords_file = File::open(passwords_file)?;
-        let passwords_reader = BufReader::new(passwords_file);
+        let passwords_reader = BufReader::new(File::open(passwords_file)?);
+        // 编辑器常在文件末尾多留空行，空行不算一个转子
         let passwords: Vec<Vec<usize>> = passwords_reader
             .lines()
-            .map(|line| {
-                serde_json::from_str::<Vec<usize>>(&line.expect("Failed to read line"))
-                    .expect("Failed to parse order")
+            .enumerate()
+            .filter(|(_, line)| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+            .map(|(i, line)| {
+                serde_json::from_str::<Vec<usize>>(&line?).map_err(|e| {
+                    CipherError::InvalidRotorConfig(format!(
+                        "Failed to parse rotor order on line {} of {passwords_file}: {e}",
+                        i + 1
+                    ))
+                })
             })
-            .collect();
+            .collect::<Result<_>>()?;
 
         // 检查每个 Vec<usize> 的长度是否一致
-        let expected_length = passwords[0].len();
+        let expected_length = passwords.first().map_or(0, Vec::len);
         for (i, order_vec) in passwords.iter().enumerate() {
             if order_vec.len() != expected_length {
                 warn!(
@@ -265,21 +1056,12 @@ impl<'a> EnigmaMachine<'a> {
             }
         }
 
-        let rotors_cursor_file = File::open(rotors_cursor_file)?;
-        let cursors_reader = BufReader::new(rotors_cursor_file);
-        let cursors: Vec<usize> = cursors_reader
-            .lines()
-            .map(|line| {
-                line.expect("Failed to read line")
-                    .parse()
-                    .expect("Failed to parse cursor")
-            })
-            .collect();
+        let cursors_content = std::fs::read_to_string(rotors_cursor_file)?;
+        let cursors = self.parse_cursors(alphabet, &cursors_content)?;
 
         if passwords.len() != rotor_num || cursors.len() != rotor_num {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "The number of rotors does not match the expected number",
+            return Err(CipherError::InvalidRotorConfig(
+                "The number of rotors does not match the expected number".to_string(),
             ));
         }
 
@@ -287,124 +1069,426 @@ impl<'a> EnigmaMachine<'a> {
         for i in 0..rotor_num {
             let mut rotor: Rotor = Rotor::new(vec![], 0);
             rotor.order = rotor.set_order(alphabet, &passwords[i])?;
-            rotor.cursor = rotor.set_cursor(cursors[i])?;
+            rotor.cursor = match cursors[i] {
+                CursorSetting::Number(cursor) => rotor.set_cursor(cursor)?,
+                CursorSetting::Letter(idx) => rotor.letter_cursor(idx),
+            };
             rotors.push(rotor);
         }
 
         Ok(rotors)
     }
 
+    /// 解析指针文件。支持两种格式：每行一个数字，或者只有一行字母（如 `MAT`），
+    /// 字母按 `Rotor::letter_cursor` 换算成对应转子的指针。空行被忽略。
+    fn parse_cursors(&self, alphabet: &str, content: &str) -> Result<Vec<CursorSetting>> {
+        let lines: Vec<(usize, &str)> = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .collect();
+        if let [(_, line)] = lines[..]
+            && line
+                .trim()
+                .chars()
+                .all(|c| !c.is_ascii_digit() && char_to_index(alphabet, c).is_some())
+        {
+            info!("Reading rotor cursors as letters: {}", line.trim());
+            return Ok(line
+                .trim()
+                .chars()
+                .map(|c| CursorSetting::Letter(char_to_index(alphabet, c).unwrap()))
+                .collect());
+        }
+
+        lines
+            .iter()
+            .map(|(i, line)| {
+                line.trim().parse().map(CursorSetting::Number).map_err(|e| {
+                    CipherError::InvalidRotorConfig(format!(
+                        "Failed to parse cursor {line:?} on line {}: {e}",
+                        i + 1
+                    ))
+                })
+            })
+            .collect()
+    }
+
     /// 从相应配置文件载入插线板。需要做一些合法性校验。
     fn set_plugboard(&self, plugboard_file: &str) -> Result<HashMap<char, char>> {
-        let mut plugboard: HashMap<char, char> = HashMap::new();
+        parse_plugboard(&std::fs::read_to_string(plugboard_file)?)
+    }
 
-        let file = File::open(plugboard_file)?;
-        let reader = BufReader::new(file);
-        for line in reader.lines() {
-            let line = line?;
-            if let Some((left, right)) = line.split_once('-') {
-                let left = left
-                    .trim()
-                    .chars()
-                    .next()
-                    .ok_or_else(|| {
-                        io::Error::new(io::ErrorKind::InvalidData, "Invalid plugboard format")
-                    })?
-                    .to_ascii_uppercase();
-                let right = right
-                    .trim()
-                    .chars()
-                    .next()
-                    .ok_or_else(|| {
-                        io::Error::new(io::ErrorKind::InvalidData, "Invalid plugboard format")
-                    })?
-                    .to_ascii_uppercase();
-
-                // 检查重复键
-                if plugboard.contains_key(&left) {
-                    error!(
-                        "Duplicate key found in plugboard: {}. Key already exists.",
-                        left
-                    );
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "Duplicate key in plugboard",
-                    ));
-                }
+    /// 使用插线板，字符如果能转换就转换，不能转换则保持原样。
+    fn use_plugboard(&self, ch: char) -> char {
+        *self.plugboard.get(&ch).unwrap_or(&ch)
+    }
 
-                // 检查重复值
-                if plugboard.values().any(|&v| v == right) {
-                    error!(
-                        "Duplicate value found in plugboard: {}. Value already exists.",
-                        right
-                    );
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "Duplicate value in plugboard",
-                    ));
-                }
+    /// 载入第二块插线板。它不符合历史上的恩尼格玛，只用于实验。
+    pub fn load_plugboard2(&mut self, plugboard2_file: &str) -> Result<()> {
+        info!("Setting second plugboard from {plugboard2_file}");
+        self.plugboard2 = self.set_plugboard(plugboard2_file)?;
+        Ok(())
+    }
 
-                plugboard.insert(left, right);
-                plugboard.insert(right, left);
-            } else {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "Invalid plugboard format",
-                ));
-            }
-        }
+    /// 使用第二块插线板，规则与第一块相同。
+    fn use_plugboard2(&self, ch: char) -> char {
+        *self.plugboard2.get(&ch).unwrap_or(&ch)
+    }
 
-        Ok(plugboard)
+    /// 加密是否与解密相同。只要反射器和插线板都是对称的，恩尼格玛就是自反的。
+    pub fn is_reciprocal(&self) -> bool {
+        let is_symmetric =
+            |map: &HashMap<char, char>| map.iter().all(|(key, value)| map.get(value) == Some(key));
+        is_symmetric(&self.reflector)
+            && is_symmetric(&self.plugboard)
+            && is_symmetric(&self.plugboard2)
     }
 
-    /// 使用插线板，字符如果能转换就转换，不能转换则保持原样。
-    fn use_plugboard(&self, ch: char) -> char {
-        *self.plugboard.get(&ch).unwrap_or(&ch)
+    /// 获取底层的CipherBase，以便读取文本和统计信息。
+    pub fn base(&self) -> &CipherBase<'a> {
+        &self.base
+    }
+
+    /// 获取底层的CipherBase，以便调整输入输出选项。
+    pub fn base_mut(&mut self) -> &mut CipherBase<'a> {
+        &mut self.base
     }
 
     /// 核心的加密过程。这里存在许多副作用。
     pub fn encrypt(&mut self) -> std::io::Result<()> {
+        if self.base.stream {
+            return self.stream();
+        }
+        if self.base.json_mode {
+            return self.encrypt_json();
+        }
         self.base.get_text()?;
+        if self.radio_format == Some(RadioFormat::Read) {
+            self.base.plain_text = parse_radio_format(&self.base.plain_text)?;
+        }
         self.base.clean_text();
 
-        info!("Encrypting text...");
+        let prefix = self.apply_indicator()?;
 
-        let plain_text: Vec<char> = self.base.plain_text.chars().collect();
-        for c in plain_text {
-            let mut ch = self.use_plugboard(c);
-            ch = self.encipher_and_decipher(ch, 1);
-            ch = self.use_reflector(ch);
-            ch = self.encipher_and_decipher(ch, -1);
-            ch = self.use_plugboard(ch);
+        info!("Encrypting text...");
 
-            self.base.encrypted_text.push(ch);
-            self.link_and_move_rotors(0)?;
+        let plain_text = self.base.plain_text.clone();
+        self.base.encrypted_text = self.encrypt_string(&plain_text)?;
+        self.base.assert_length_preserved();
+        if self.radio_format == Some(RadioFormat::Write) {
+            self.base.encrypted_text = to_radio_format(&prefix, &self.base.encrypted_text);
+        } else {
+            self.base.encrypted_text.insert_str(0, &prefix);
         }
         self.base.save_file()
     }
 
-    /// 字符通过转子进行加密的过程。
-    fn encipher_and_decipher(&self, mut ch: char, sign: i32) -> char {
-        for rotor in &self.rotors {
-            let shift = rotor.order[rotor.cursor] as i32 * sign;
-            let idx = self.base.alphabet.chars().position(|c| c == ch).unwrap();
-            let new_idx =
-                ((idx as i32 + shift).rem_euclid(self.base.alphabet.len() as i32)) as usize;
-            ch = self.base.alphabet.chars().nth(new_idx).unwrap();
+    /// 只加密JSON文档中的字符串值，转子状态按文档顺序在各个值之间延续。
+    /// 指示器和电报格式需要整段报文，不能用于JSON。
+    fn encrypt_json(&mut self) -> std::io::Result<()> {
+        if self.indicator.is_some() || self.radio_format.is_some() {
+            return Err(CipherError::InvalidInput(
+                "JSON input does not support message indicators or the radio format".to_string(),
+            )
+            .into());
         }
-        ch
-    }
+        let base = self.base.clone();
+        let mut error = None;
+        let encrypted = base.transform_json(|text| {
+            self.encrypt_string(text).unwrap_or_else(|e| {
+                error.get_or_insert(e);
+                String::new()
+            })
+        })?;
+        if let Some(e) = error {
+            return Err(e.into());
+        }
+        self.base.encrypted_text = encrypted;
+        self.base.save_file()
+    }
+
+    /// 分块读取、加密和写出输入，转子状态在块之间延续。
+    /// 指示器和电报格式需要整段报文，不能分块处理。
+    fn stream(&mut self) -> std::io::Result<()> {
+        if self.indicator.is_some() || self.radio_format.is_some() {
+            return Err(CipherError::InvalidInput(
+                "Streaming does not support message indicators or the radio format".to_string(),
+            )
+            .into());
+        }
+        let mut stream = self.base.open_stream()?;
+        while let Some(chunk) = self.base.next_chunk(&mut stream)? {
+            let encrypted = self.encrypt_string(&chunk)?;
+            self.base.write_chunk(&mut stream, &encrypted)?;
+        }
+        stream.finish()
+    }
+
+    /// 在内存中加密一段已清理的文本，不读写文件。恩尼格玛是自反的，解密也用它。
+    /// 转子从当前位置开始，每个字符后步进，所以连续调用相当于加密一段连续的文本。
+    pub fn encrypt_string(&mut self, plaintext: &str) -> Result<String> {
+        plaintext.chars().map(|c| self.encrypt_char(c)).collect()
+    }
+
+    /// 加密单个字符，随后步进转子。字母表之外的字符原样返回，也不步进转子。
+    fn encrypt_char(&mut self, c: char) -> Result<char> {
+        if self.base.index_of(c).is_none() {
+            return Ok(c);
+        }
+        if self.step_first {
+            self.link_and_move_rotors(0)?;
+            return Ok(self.substitute(c));
+        }
+        let ch = self.substitute(c);
+        self.link_and_move_rotors(0)?;
+        Ok(ch)
+    }
+
+    /// 按转子当前的位置替换单个字符，不步进转子。
+    fn substitute(&self, c: char) -> char {
+        let mut ch = self.use_plugboard(c);
+        ch = self.encipher_and_decipher(ch, 1);
+        ch = self.use_plugboard2(ch);
+        ch = self.use_reflector(ch);
+        ch = self.use_plugboard2(ch);
+        ch = self.encipher_and_decipher(ch, -1);
+        self.use_plugboard(ch)
+    }
+
+    /// 密钥编排表：依次列出前 `steps` 个转子位置上整个字母表被替换成的结果。
+    /// 在机器的副本上步进，不影响机器本身，也不读取输入。
+    pub fn key_schedule(&self, steps: usize) -> Result<Vec<String>> {
+        let mut machine = self.clone();
+        let mut schedule = Vec::with_capacity(steps);
+        for _ in 0..steps {
+            if machine.step_first {
+                machine.link_and_move_rotors(0)?;
+            }
+            schedule.push(
+                self.base
+                    .alphabet
+                    .chars()
+                    .map(|c| machine.substitute(c))
+                    .collect(),
+            );
+            if !machine.step_first {
+                machine.link_and_move_rotors(0)?;
+            }
+        }
+        Ok(schedule)
+    }
+
+    /// 设置报文指示器，`None` 表示不使用。
+    pub fn set_indicator(&mut self, indicator: Option<Indicator>) {
+        self.indicator = indicator;
+    }
+
+    /// 设置无线电报文格式，`None` 表示普通的连续密文。
+    pub fn set_radio_format(&mut self, radio_format: Option<RadioFormat>) {
+        self.radio_format = radio_format;
+    }
+
+    /// 按报文指示器处理报文开头，并把转子调整到报文密钥指定的位置。
+    /// 返回需要放在密文开头的指示器；读取指示器时会把它从待解密的文本中去掉。
+    fn apply_indicator(&mut self) -> Result<String> {
+        let rotor_num = self.rotors.len();
+        match self.indicator.clone() {
+            None => Ok(String::new()),
+            Some(Indicator::Write(key)) => {
+                let cursors = self.key_cursors(&key)?;
+                // 按字母表中的写法加密密钥，使小写的密钥也能使用
+                let key: String = self
+                    .key_letters(&key)?
+                    .into_iter()
+                    .map(|idx| self.base.letter_at(idx))
+                    .collect();
+                let prefix = key
+                    .repeat(2)
+                    .chars()
+                    .map(|c| self.encrypt_char(c))
+                    .collect::<Result<String>>()?;
+                self.set_cursors(&cursors);
+                Ok(prefix)
+            }
+            Some(Indicator::Read) => {
+                let text: Vec<char> = self.base.plain_text.chars().collect();
+                if text.len() < 2 * rotor_num {
+                    return Err(CipherError::InvalidInput(
+                        "The text is too short to contain an indicator".to_string(),
+                    ));
+                }
+                let doubled = text[..2 * rotor_num]
+                    .iter()
+                    .map(|&c| self.encrypt_char(c))
+                    .collect::<Result<String>>()?;
+                let (first, second) = doubled.split_at(rotor_num);
+                if first != second {
+                    return Err(CipherError::InvalidInput(format!(
+                        "The indicator halves differ: {first} and {second}"
+                    )));
+                }
+                info!("Read message key {first} from indicator");
+                let cursors = self.key_cursors(first)?;
+                self.set_cursors(&cursors);
+                self.base.plain_text = text[2 * rotor_num..].iter().collect();
+                Ok(String::new())
+            }
+        }
+    }
+
+    /// 报文密钥中每个字母在字母表中的序号，每个转子对应一个字母。
+    fn key_letters(&self, key: &str) -> Result<Vec<usize>> {
+        if key.chars().count() != self.rotors.len() {
+            return Err(CipherError::InvalidInput(format!(
+                "The message key {key:?} must have one letter per rotor ({})",
+                self.rotors.len()
+            )));
+        }
+        key.chars()
+            .map(|c| {
+                char_to_index(self.base.alphabet, c).ok_or_else(|| {
+                    CipherError::InvalidInput(format!(
+                        "Message key letter '{c}' not found in alphabet"
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    /// 把报文密钥转换为每个转子的指针，字母按 `Rotor::letter_cursor` 换算。
+    fn key_cursors(&self, key: &str) -> Result<Vec<usize>> {
+        Ok(self
+            .key_letters(key)?
+            .into_iter()
+            .zip(&self.rotors)
+            .map(|(idx, rotor)| rotor.letter_cursor(idx))
+            .collect())
+    }
+
+    /// 把各转子的指针设为给定的值。
+    fn set_cursors(&mut self, cursors: &[usize]) {
+        for (rotor, &cursor) in self.rotors.iter_mut().zip(cursors) {
+            rotor.cursor = cursor;
+        }
+    }
+
+    /// 设置返回路径上穿过转子的顺序。
+    pub fn set_rotor_pass_order(&mut self, order: RotorPassOrder) {
+        self.rotor_pass_order = order;
+    }
+
+    /// 字符通过转子进行加密的过程。`sign` 为负时表示经反射器返回的路径。
+    fn encipher_and_decipher(&self, mut ch: char, sign: i32) -> char {
+        let rotors: Vec<(usize, &Rotor)> =
+            if sign < 0 && self.rotor_pass_order == RotorPassOrder::Reversed {
+                self.rotors.iter().enumerate().rev().collect()
+            } else {
+                self.rotors.iter().enumerate().collect()
+            };
+        for (i, rotor) in rotors {
+            let ring = self.ring_settings.get(i).copied().unwrap_or(0);
+            let idx = self
+                .base
+                .index_of(ch)
+                .expect("Rotors only see alphabet characters");
+            let new_idx = rotor.pass(idx, sign, self.base.letters.len(), ring);
+            ch = self.base.letter_at(new_idx);
+        }
+        ch
+    }
+
+    /// 把所有转子换成各自的镜像转子。
+    pub fn invert_rotors(&mut self) {
+        self.rotors = self.rotors.iter().map(Rotor::inverted).collect();
+    }
+
+    /// 设置各转子的环设置，顺序与转子相同（与指针文件一致，转得最快的转子在前）。
+    /// 个数必须等于转子数，每个值都要小于转子一圈的位置数。
+    pub fn set_ring_settings(&mut self, ring_settings: Vec<usize>) -> Result<()> {
+        if ring_settings.len() != self.rotors.len() {
+            return Err(CipherError::InvalidRotorConfig(format!(
+                "Expected {} ring settings, got {}",
+                self.rotors.len(),
+                ring_settings.len()
+            )));
+        }
+        for (rotor, &ring) in self.rotors.iter().zip(&ring_settings) {
+            if ring >= rotor.positions() {
+                return Err(CipherError::InvalidRotorConfig(format!(
+                    "Ring setting {ring} is out of range 0..{}",
+                    rotor.positions()
+                )));
+            }
+        }
+        self.ring_settings = ring_settings;
+        Ok(())
+    }
+
+    /// 打开或关闭简化步进：打开后只有第一个转子步进，其余转子始终停在初始位置。
+    pub fn set_single_step(&mut self, single_step: bool) {
+        self.single_step = single_step;
+    }
+
+    /// 设置转子每次步进的格数。
+    pub fn set_step_size(&mut self, step_size: usize) -> Result<()> {
+        if step_size == 0 {
+            return Err(CipherError::InvalidInput(
+                "The step size must be at least 1".to_string(),
+            ));
+        }
+        self.step_size = step_size;
+        Ok(())
+    }
 
     /// 恩尼格玛极有特色的转子步进方式，其中存在连接关系。
+    /// 带缺口的转子（历史转子）按缺口步进，其余转子在回绕时带动下一个转子。
     pub fn link_and_move_rotors(&mut self, i: usize) -> std::io::Result<()> {
-        self.rotors[i].step();
+        if i == 0 && self.rotors.iter().all(|rotor| !rotor.notch.is_empty()) {
+            self.move_rotors_by_notches();
+            return Ok(());
+        }
+        let wrapped = self.rotors[i].step(self.step_size);
         info!("Rotor {i} Stepped");
-        if self.rotors[i].cursor == 0 && i < self.rotors.len() - 1 {
+        if wrapped && !self.single_step && i < self.rotors.len() - 1 {
             info!("Linking rotor {} to rotor {}", i, i + 1);
             self.link_and_move_rotors(i + 1)?;
         }
         Ok(())
     }
+
+    /// 按缺口步进：第一个转子每次都步进；右边的转子停在缺口上时带动这个转子；
+    /// 这个转子自己停在缺口上时，会在带动左边转子的同时自己也步进一格，
+    /// 这就是中间转子的“双步进”。最左边的转子左侧没有棘爪，所以不会双步进。
+    /// 先判断所有转子是否步进，再一起步进。
+    fn move_rotors_by_notches(&mut self) {
+        let len = self.rotors.len();
+        let moves: Vec<bool> = (0..len)
+            .map(|i| {
+                i == 0
+                    || (!self.single_step
+                        && (self.rotors[i - 1].at_notch()
+                            || (i + 1 < len && self.rotors[i].at_notch())))
+            })
+            .collect();
+        for (i, rotor) in self.rotors.iter_mut().enumerate() {
+            if moves[i] {
+                rotor.step(self.step_size);
+                info!("Rotor {i} Stepped");
+            }
+        }
+    }
+}
+
+/// 恩尼格玛是自反的，解密与加密是同一个过程。
+impl Cipher for EnigmaMachine<'_> {
+    fn encrypt(&mut self) -> std::io::Result<()> {
+        EnigmaMachine::encrypt(self)
+    }
+
+    fn decrypt(&mut self) -> std::io::Result<()> {
+        EnigmaMachine::encrypt(self)
+    }
 }
 
 #[cfg(test)]
@@ -466,7 +1550,8 @@ mod reflector_tests {
             "plugboard.txt",
             "m", // 手动创建反射器
             "M",
-        );
+        )
+        .unwrap();
 
         test_reflector(alphabet, &enigma.reflector);
     }
@@ -487,40 +1572,664 @@ mod reflector_tests {
             "plugboard.txt",
             "M", // 读取反射器
             "M",
+        )
+        .unwrap();
+
+        test_reflector(alphabet, &enigma.reflector);
+    }
+
+    #[test]
+    fn test_incomplete_reflector_rejected() {
+        use tempfile::NamedTempFile;
+
+        let reflector_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let reflector_path = reflector_file.path().to_str().unwrap();
+        std::fs::write(reflector_path, r#"{"A":"B","B":"A","C":"D","D":"C"}"#).unwrap();
+
+        let result = EnigmaMachine::new(
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+            "input.txt",
+            "output.txt",
+            reflector_path,
+            3,
+            "passwords.txt",
+            "rotors_cursor.txt",
+            "plugboard.txt",
+            "M",
+            "M",
+        );
+
+        let err = result
+            .err()
+            .expect("Incomplete reflector should be rejected");
+        assert!(matches!(err, CipherError::InvalidReflector(_)), "{err}");
+    }
+
+    #[test]
+    fn test_broken_reflector_file_rejected() {
+        use tempfile::NamedTempFile;
+
+        // 从正确的反射器B出发，分别制造一个不动点和一处不对称
+        let pairs = "AY BR CU DH EQ FS GL IP JX KN MO TZ VW";
+        let mut reflector: HashMap<String, String> = HashMap::new();
+        for pair in pairs.split(' ') {
+            let (left, right) = pair.split_at(1);
+            reflector.insert(left.to_string(), right.to_string());
+            reflector.insert(right.to_string(), left.to_string());
+        }
+        let mut fixed_point = reflector.clone();
+        fixed_point.insert("A".to_string(), "A".to_string());
+        let mut asymmetric = reflector.clone();
+        asymmetric.insert("A".to_string(), "B".to_string());
+
+        for broken in [fixed_point, asymmetric] {
+            let reflector_file = NamedTempFile::new().expect("Failed to create temporary file");
+            let reflector_path = reflector_file.path().to_str().unwrap();
+            std::fs::write(reflector_path, serde_json::to_string(&broken).unwrap()).unwrap();
+
+            let err = EnigmaMachine::with_reflector(
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+                "input.txt",
+                "output.txt",
+                ReflectorSource::File(reflector_path),
+                3,
+                "passwords.txt",
+                "rotors_cursor.txt",
+                "plugboard.txt",
+                "M",
+            )
+            .err()
+            .expect("Broken reflector should be rejected");
+            assert!(matches!(err, CipherError::InvalidReflector(_)), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_identical_files_rejected_when_generating() {
+        use tempfile::NamedTempFile;
+
+        let shared_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let shared_path = shared_file.path().to_str().unwrap();
+
+        let result = EnigmaMachine::new(
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+            "input.txt",
+            "output.txt",
+            "reflector.txt",
+            3,
+            shared_path,
+            shared_path,
+            "plugboard.txt",
+            "M",
+            "m", // 生成转子，密码本与指针文件相同
         );
 
+        let err = result.err().expect("Identical paths should be rejected");
+        assert!(matches!(err, CipherError::InvalidInput(_)), "{err}");
+    }
+
+    #[test]
+    fn test_reflector_from_inline_pairs() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let pairs: Vec<String> = "AY BR CU DH EQ FS GL IP JX KN MO TZ VW"
+            .split(' ')
+            .map(String::from)
+            .collect();
+
+        let mut enigma = EnigmaMachine::with_reflector(
+            alphabet,
+            "input.txt",
+            "output.txt",
+            ReflectorSource::Pairs(&pairs),
+            3,
+            "passwords.txt",
+            "rotors_cursor.txt",
+            "plugboard.txt",
+            "M",
+        )
+        .unwrap();
+
         test_reflector(alphabet, &enigma.reflector);
+        assert_eq!(enigma.reflector[&'A'], 'Y');
+        assert!(enigma.is_reciprocal());
+
+        // 由内联配对构成的机器仍然可以自我解密
+        let mut decryptor = enigma.clone();
+        let ciphertext = enigma.encrypt_string("HELLOWORLD").unwrap();
+        assert_eq!(decryptor.encrypt_string(&ciphertext).unwrap(), "HELLOWORLD");
+    }
+
+    #[test]
+    fn test_invalid_inline_reflector_pairs_rejected() {
+        let build = |pairs: &str| {
+            let pairs: Vec<String> = pairs.split(' ').map(String::from).collect();
+            EnigmaMachine::with_reflector(
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+                "input.txt",
+                "output.txt",
+                ReflectorSource::Pairs(&pairs),
+                3,
+                "passwords.txt",
+                "rotors_cursor.txt",
+                "plugboard.txt",
+                "M",
+            )
+            .err()
+        };
+
+        // 不足13对、字母重复、自配对、多于两个字母，都应被拒绝
+        assert!(build("AY BR CU").is_some());
+        assert!(build("AY BR CU DH EQ FS GL IP JX KN MO TZ VA").is_some());
+        assert!(build("AA BR CU DH EQ FS GL IP JX KN MO TZ VW").is_some());
+        assert!(build("AYB R CU DH EQ FS GL IP JX KN MO TZ VW").is_some());
     }
 }
 
 #[cfg(test)]
 mod rotor_tests {
+    use super::integration_tests::historical_machine_b;
     use super::*;
 
     #[test]
     fn test_rotor_generate_order() {
         let mut rotor = Rotor::new(vec![], 0);
-        rotor.order = rotor.generate_order("ABCDEFGHIJKLMNOPQRSTUVWXYZ").unwrap();
+        rotor.order = rotor
+            .generate_order("ABCDEFGHIJKLMNOPQRSTUVWXYZ", &mut rng())
+            .unwrap();
         assert_eq!(rotor.order.len(), 25);
-        assert!(rotor.order.iter().all(|&x| x >= 1 && x <= 25));
+        assert!(rotor.order.iter().all(|&x| (1..=25).contains(&x)));
     }
 
     #[test]
     fn test_rotor_generate_cursor() {
         let mut rotor = Rotor::new(vec![], 0);
-        rotor.order = rotor.generate_order("ABCDEFGHIJKLMNOPQRSTUVWXYZ").unwrap();
-        rotor.cursor = rotor.generate_cursor();
+        rotor.order = rotor
+            .generate_order("ABCDEFGHIJKLMNOPQRSTUVWXYZ", &mut rng())
+            .unwrap();
+        rotor.cursor = rotor.generate_cursor(&mut rng());
         assert!(rotor.cursor < rotor.order.len());
     }
 
+    #[test]
+    fn test_load_cursors_from_letters() {
+        use tempfile::NamedTempFile;
+
+        let cursor_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let cursor_path = cursor_file.path().to_str().unwrap();
+        std::fs::write(cursor_path, "MAT\n").unwrap();
+
+        let enigma = EnigmaMachine::new(
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+            "input.txt",
+            "output.txt",
+            "reflector.txt",
+            3,
+            "passwords.txt",
+            cursor_path,
+            "plugboard.txt",
+            "M",
+            "M",
+        )
+        .unwrap();
+
+        let cursors: Vec<usize> = enigma.rotors.iter().map(|rotor| rotor.cursor).collect();
+        assert_eq!(cursors, vec![12, 0, 19]);
+
+        // 小写字母与大写字母得到相同的指针
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        assert_eq!(
+            enigma.parse_cursors(alphabet, "m").unwrap(),
+            enigma.parse_cursors(alphabet, "M").unwrap()
+        );
+        assert_eq!(
+            enigma.parse_cursors(alphabet, "mat").unwrap(),
+            [12, 0, 19].map(CursorSetting::Letter)
+        );
+
+        // 密码本转子一圈只有25个位置，Z回到与A相同的位置
+        std::fs::write(cursor_path, "MAZ\n").unwrap();
+        let enigma = EnigmaMachine::new(
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+            "input.txt",
+            "output.txt",
+            "reflector.txt",
+            3,
+            "passwords.txt",
+            cursor_path,
+            "plugboard.txt",
+            "M",
+            "M",
+        )
+        .unwrap();
+        assert_eq!(enigma.rotor_positions(), vec![12, 0, 0]);
+        // 数字形式的指针仍然不能超出转子的位置数
+        std::fs::write(cursor_path, "12\n0\n25\n").unwrap();
+        assert!(
+            EnigmaMachine::new(
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+                "input.txt",
+                "output.txt",
+                "reflector.txt",
+                3,
+                "passwords.txt",
+                cursor_path,
+                "plugboard.txt",
+                "M",
+                "M",
+            )
+            .is_err()
+        );
+
+        // 字母个数与转子数不一致时报错
+        std::fs::write(cursor_path, "MA\n").unwrap();
+        let result = EnigmaMachine::new(
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+            "input.txt",
+            "output.txt",
+            "reflector.txt",
+            3,
+            "passwords.txt",
+            cursor_path,
+            "plugboard.txt",
+            "M",
+            "M",
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_load_rotors_ignores_blank_lines() {
+        use tempfile::NamedTempFile;
+
+        let passwords_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let passwords_path = passwords_file.path().to_str().unwrap();
+        let cursor_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let cursor_path = cursor_file.path().to_str().unwrap();
+        let passwords = std::fs::read_to_string("passwords.txt").unwrap();
+        std::fs::write(passwords_path, format!("{passwords}\n  \n")).unwrap();
+        std::fs::write(cursor_path, "0\n13\n19\n\n   \n").unwrap();
+
+        let enigma = EnigmaMachine::new(
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+            "input.txt",
+            "output.txt",
+            "reflector.txt",
+            3,
+            passwords_path,
+            cursor_path,
+            "plugboard.txt",
+            "M",
+            "M",
+        )
+        .unwrap();
+
+        let cursors: Vec<usize> = enigma.rotors.iter().map(|rotor| rotor.cursor).collect();
+        assert_eq!(cursors, vec![0, 13, 19]);
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        assert_eq!(
+            enigma.parse_cursors(alphabet, "MAT\n\n").unwrap(),
+            [12, 0, 19].map(CursorSetting::Letter)
+        );
+    }
+
+    #[test]
+    fn test_malformed_rotor_files_are_errors() {
+        use tempfile::NamedTempFile;
+
+        let passwords_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let passwords_path = passwords_file.path().to_str().unwrap();
+        let cursor_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let cursor_path = cursor_file.path().to_str().unwrap();
+        let load = || {
+            EnigmaMachine::new(
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+                "input.txt",
+                "output.txt",
+                "reflector.txt",
+                3,
+                passwords_path,
+                cursor_path,
+                "plugboard.txt",
+                "M",
+                "M",
+            )
+            .err()
+            .expect("Malformed rotor files should be rejected")
+        };
+
+        // 第二行被截断的密码本
+        let passwords = std::fs::read_to_string("passwords.txt").unwrap();
+        let first = passwords.lines().next().unwrap();
+        std::fs::write(passwords_path, format!("{first}\n[3, 7, 1\n")).unwrap();
+        std::fs::write(cursor_path, "0\n13\n19\n").unwrap();
+        let err = load();
+        assert!(matches!(err, CipherError::InvalidRotorConfig(_)), "{err}");
+        assert!(err.to_string().contains("line 2"), "{err}");
+
+        // 空的密码本不再因取第一行而崩溃
+        std::fs::write(passwords_path, "").unwrap();
+        assert!(matches!(load(), CipherError::InvalidRotorConfig(_)));
+
+        // 指针文件第三行不是数字，空行也计入行号
+        std::fs::write(passwords_path, &passwords).unwrap();
+        std::fs::write(cursor_path, "0\n\nthirteen\n19\n").unwrap();
+        let err = load();
+        assert!(matches!(err, CipherError::InvalidRotorConfig(_)), "{err}");
+        assert!(err.to_string().contains("line 3"), "{err}");
+    }
+
     #[test]
     fn test_rotor_step() {
         let mut rotor = Rotor::new(vec![1, 2, 3, 4, 5], 0);
-        rotor.step();
+        assert!(!rotor.step(1));
+        assert_eq!(rotor.cursor, 1);
+        assert!(!rotor.step(1));
+        assert_eq!(rotor.cursor, 2);
+    }
+
+    #[test]
+    fn test_rotor_and_inverse_compose_to_identity() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let mut enigma = EnigmaMachine::blank(alphabet, "", "", 2);
+        let mut rotor = Rotor::new(vec![], 0);
+        rotor.order = rotor.generate_order(alphabet, &mut rng()).unwrap();
+        rotor.cursor = 7;
+        enigma.rotors = vec![rotor.clone(), rotor.inverted()];
+
+        for ch in alphabet.chars() {
+            assert_eq!(enigma.encipher_and_decipher(ch, 1), ch);
+        }
+        // 镜像两次得到原转子
+        assert_eq!(rotor.inverted().inverted().order, rotor.order);
+    }
+
+    #[test]
+    fn test_rotor_order_rejects_zero_offset() {
+        use tempfile::NamedTempFile;
+
+        let alphabet = "ABCDEF";
+        let rotor = Rotor::new(vec![], 0);
+        assert!(rotor.set_order(alphabet, &vec![1, 2, 3, 4, 5]).is_ok());
+        assert!(rotor.set_order(alphabet, &vec![0, 2, 3, 4, 5]).is_err());
+        assert!(rotor.set_order(alphabet, &vec![1, 2, 3, 4, 6]).is_err());
+
+        // 从文件载入含有0的密码本时报错
+        let passwords_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let passwords_path = passwords_file.path().to_str().unwrap();
+        let cursor_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let cursor_path = cursor_file.path().to_str().unwrap();
+        std::fs::write(passwords_path, "[1,2,0,4,5]\n").unwrap();
+        std::fs::write(cursor_path, "0\n").unwrap();
+        let enigma = EnigmaMachine::blank(alphabet, "", "", 1);
+        assert!(
+            enigma
+                .load_rotors(alphabet, 1, passwords_path, cursor_path)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_rotor_step_size() {
+        // 每次前进两格，越过末尾时报告进位，即使没有恰好落在0上
+        let mut rotor = Rotor::new(vec![1, 2, 3, 4, 5], 0);
+        assert!(!rotor.step(2));
+        assert_eq!(rotor.cursor, 2);
+        assert!(!rotor.step(2));
+        assert_eq!(rotor.cursor, 4);
+        assert!(rotor.step(2));
         assert_eq!(rotor.cursor, 1);
-        rotor.step();
+        // 步长大于密码本长度时按取模处理
+        assert!(!rotor.step(6));
         assert_eq!(rotor.cursor, 2);
     }
+
+    #[test]
+    fn test_step_size_carries_to_next_rotor() {
+        let mut enigma = EnigmaMachine::blank("ABCDEF", "", "", 2);
+        enigma.rotors = vec![
+            Rotor::new(vec![1, 2, 3, 4, 5], 3),
+            Rotor::new(vec![1, 2, 3, 4, 5], 0),
+        ];
+        enigma.set_step_size(2).unwrap();
+        assert!(enigma.set_step_size(0).is_err());
+
+        enigma.link_and_move_rotors(0).unwrap();
+        assert_eq!(enigma.rotors[0].cursor, 0);
+        assert_eq!(enigma.rotors[1].cursor, 2);
+        enigma.link_and_move_rotors(0).unwrap();
+        assert_eq!(enigma.rotors[0].cursor, 2);
+        assert_eq!(enigma.rotors[1].cursor, 2);
+    }
+
+    #[test]
+    fn test_single_step_only_moves_first_rotor() {
+        let mut enigma =
+            EnigmaMachine::from_seed("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "", "", 3, 7).unwrap();
+        enigma.set_single_step(true);
+        let cursors: Vec<usize> = enigma.rotors.iter().map(|rotor| rotor.cursor).collect();
+
+        // 远超一圈的长度，第一个转子多次回绕，其余转子仍不动
+        let plaintext = "A".repeat(200);
+        enigma.encrypt_string(&plaintext).unwrap();
+        assert_eq!(enigma.rotors[0].cursor, (cursors[0] + 200) % 25);
+        for (rotor, &cursor) in enigma.rotors.iter().zip(&cursors).skip(1) {
+            assert_eq!(rotor.cursor, cursor);
+        }
+    }
+
+    #[test]
+    fn test_historical_wiring_offsets() {
+        // 转子I把A接到E、把B接到K；反向时E回到A
+        let rotor = Rotor::historical("I").unwrap();
+        let wiring = rotor.wiring.as_ref().unwrap();
+        assert_eq!(wiring.forward[0], 4);
+        assert_eq!(wiring.forward[1], 9);
+        assert_eq!(wiring.backward[4], 22);
+        assert_eq!(rotor.pass(0, 1, 26, 0), 4);
+        assert_eq!(rotor.pass(4, -1, 26, 0), 0);
+        assert_eq!(rotor.positions(), 26);
+        assert!(matches!(
+            Rotor::historical("VI"),
+            Err(CipherError::InvalidRotorConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_historical_rotors_reproduce_published_vector() {
+        // 转子I-II-III，反射器B，指针AAA，无插线：AAAAAAAAAA 加密为 BDZGOWCXLT
+        let mut enigma = historical_machine_b();
+        let mut decryptor = enigma.clone();
+
+        assert_eq!(enigma.encrypt_string("AAAAAAAAAA").unwrap(), "BDZGOWCXLT");
+        assert_eq!(
+            decryptor.encrypt_string("BDZGOWCXLT").unwrap(),
+            "AAAAAAAAAA"
+        );
+    }
+
+    #[test]
+    fn test_historical_large_input_round_trip() {
+        let mut enigma = historical_machine_b();
+        let mut decryptor = enigma.clone();
+
+        // 长文本的开头仍与公开的测试向量一致，整段可以解回原文
+        let text =
+            "A".repeat(10) + &crate::benchmark::synthetic_input(HISTORICAL_ALPHABET, 50_000, 3);
+        let ciphertext = enigma.encrypt_string(&text).unwrap();
+        assert!(ciphertext.starts_with("BDZGOWCXLT"));
+        assert_eq!(decryptor.encrypt_string(&ciphertext).unwrap(), text);
+    }
+
+    #[test]
+    fn test_stream_matches_whole_file() {
+        use tempfile::NamedTempFile;
+
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let text = crate::benchmark::synthetic_input(alphabet, 300_000, 8);
+        let input = NamedTempFile::new().unwrap();
+        let whole = NamedTempFile::new().unwrap();
+        let streamed = NamedTempFile::new().unwrap();
+        std::fs::write(input.path(), &text).unwrap();
+        let input_path = input.path().to_str().unwrap();
+
+        // 转子状态在块之间延续，结果与一次读入整个文件相同
+        for (output, stream) in [(&whole, false), (&streamed, true)] {
+            let output_path = output.path().to_str().unwrap();
+            let mut enigma =
+                EnigmaMachine::from_seed(alphabet, input_path, output_path, 3, 42).unwrap();
+            enigma.base_mut().stream = stream;
+            enigma.encrypt().expect("Encryption failed");
+        }
+        assert_eq!(
+            std::fs::read_to_string(streamed.path()).unwrap(),
+            std::fs::read_to_string(whole.path()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_historical_ring_settings() {
+        // 同样的设置，环设置改为BBB：AAAAA 加密为 EWTYX
+        let mut enigma = historical_machine_b();
+        enigma.set_ring_settings(vec![1, 1, 1]).unwrap();
+        assert_eq!(enigma.encrypt_string("AAAAA").unwrap(), "EWTYX");
+    }
+
+    #[test]
+    fn test_ring_settings_change_ciphertext_only() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let plaintext = "ATTACKATDAWNONTHEEASTERNFRONT";
+        let build = |rings: Vec<usize>| {
+            let mut enigma = EnigmaMachine::from_seed(alphabet, "", "", 3, 42).unwrap();
+            enigma.set_ring_settings(rings).unwrap();
+            enigma
+        };
+
+        let plain = build(vec![0, 0, 0]).encrypt_string(plaintext).unwrap();
+        let ringed = build(vec![3, 0, 7]).encrypt_string(plaintext).unwrap();
+        assert_ne!(plain, ringed);
+
+        // 只要环设置相同，同一台机器仍然可以自我解密
+        assert_eq!(
+            build(vec![3, 0, 7]).encrypt_string(&ringed).unwrap(),
+            plaintext
+        );
+
+        // 个数与转子数不符，或超出范围，都应被拒绝
+        let mut enigma = build(vec![0, 0, 0]);
+        assert!(matches!(
+            enigma.set_ring_settings(vec![0, 0]),
+            Err(CipherError::InvalidRotorConfig(_))
+        ));
+        assert!(matches!(
+            enigma.set_ring_settings(vec![0, 0, 25]),
+            Err(CipherError::InvalidRotorConfig(_))
+        ));
+        assert!(enigma.set_ring_settings(vec![0, 0, 24]).is_ok());
+    }
+
+    #[test]
+    fn test_notch_stepping_double_steps_middle_rotor() {
+        let mut enigma = historical_machine_b();
+        let mut from_start = enigma.clone();
+
+        // 从左到右为 ADU：III 在 V 处带动 II，II 停在缺口 E 上，下一次按键时连同 I 一起步进
+        let alphabet: Vec<char> = HISTORICAL_ALPHABET.chars().collect();
+        let letters = |enigma: &EnigmaMachine| -> String {
+            enigma
+                .rotors
+                .iter()
+                .rev()
+                .map(|rotor| alphabet[rotor.cursor])
+                .collect()
+        };
+        enigma.set_cursors(&[20, 3, 0]);
+        let mut positions = Vec::new();
+        for _ in 0..5 {
+            enigma.encrypt_string("A").unwrap();
+            positions.push(letters(&enigma));
+        }
+        assert_eq!(positions, ["ADV", "AEW", "BFX", "BFY", "BFZ"]);
+
+        // 从 AAA 开始的30个A跨过了转子III的缺口
+        assert_eq!(
+            from_start.encrypt_string(&"A".repeat(30)).unwrap(),
+            "BDZGOWCXLTKSBTMCDLPBMUQOFXYHCX"
+        );
+        assert_eq!(letters(&from_start), "ABE");
+    }
+
+    #[test]
+    fn test_historical_rotors_need_standard_alphabet() {
+        use tempfile::NamedTempFile;
+
+        let plugboard = NamedTempFile::new().unwrap();
+        let names = vec!["I".to_string()];
+        let result = EnigmaMachine::historical(
+            "ABCDEF",
+            "",
+            "",
+            &names,
+            ReflectorSource::Pairs(&["AB".to_string(), "CD".to_string(), "EF".to_string()]),
+            plugboard.path().to_str().unwrap(),
+        );
+        assert!(result.is_err());
+    }
+}
+
+#[cfg(test)]
+mod normalize_tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    #[test]
+    fn test_normalize_plugboard() {
+        let file = NamedTempFile::new().expect("Failed to create temporary file");
+        let path = file.path().to_str().unwrap();
+        std::fs::write(path, "z-d\n c - a\nQ-h").unwrap();
+
+        normalize_config(ALPHABET, path, ConfigKind::Plugboard).unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "A-C\nD-Z\nH-Q\n");
+    }
+
+    #[test]
+    fn test_invalid_plugboard_left_untouched() {
+        let file = NamedTempFile::new().expect("Failed to create temporary file");
+        let path = file.path().to_str().unwrap();
+        for content in ["B-A\nC-C\n", "A-B\nA-C\n", "A-1\n"] {
+            std::fs::write(path, content).unwrap();
+            let err = normalize_config(ALPHABET, path, ConfigKind::Plugboard).unwrap_err();
+            assert!(matches!(err, CipherError::InvalidPlugboard(_)), "{err}");
+            assert_eq!(std::fs::read_to_string(path).unwrap(), content);
+        }
+    }
+
+    #[test]
+    fn test_normalize_reflector() {
+        let file = NamedTempFile::new().expect("Failed to create temporary file");
+        let path = file.path().to_str().unwrap();
+        let mut pairs: Vec<String> = Vec::new();
+        for (left, right) in ALPHABET.chars().zip(ALPHABET.chars().rev()) {
+            pairs.push(format!("\"{}\":\"{right}\"", left.to_ascii_lowercase()));
+        }
+        pairs.reverse();
+        std::fs::write(path, format!("{{{}}}", pairs.join(","))).unwrap();
+
+        normalize_config(ALPHABET, path, ConfigKind::Reflector).unwrap();
+        let content = std::fs::read_to_string(path).unwrap();
+        assert!(content.starts_with("{\"A\":\"Z\",\"B\":\"Y\""));
+        assert!(content.ends_with("\"Z\":\"A\"}\n"));
+
+        // 不对称的反射器报错，文件不变
+        std::fs::write(path, r#"{"A":"B","B":"C","C":"A"}"#).unwrap();
+        let err = normalize_config("ABC", path, ConfigKind::Reflector).unwrap_err();
+        assert!(matches!(err, CipherError::InvalidReflector(_)), "{err}");
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap(),
+            r#"{"A":"B","B":"C","C":"A"}"#
+        );
+    }
 }
 
 #[cfg(test)]
@@ -543,7 +2252,8 @@ mod integration_tests {
             "plugboard.txt",
             "M",
             "M",
-        );
+        )
+        .unwrap();
 
         enigma.encrypt().unwrap();
 
@@ -563,4 +2273,549 @@ mod integration_tests {
         }
         assert!(!have_same_char, "It is not a Enigma!");
     }
+
+    /// 转子I-II-III、反射器B、不接插线的历史机器，指针都在A。
+    pub(super) fn historical_machine_b() -> EnigmaMachine<'static> {
+        use tempfile::NamedTempFile;
+
+        let plugboard = NamedTempFile::new().unwrap();
+        let pairs: Vec<String> = "AY BR CU DH EQ FS GL IP JX KN MO TZ VW"
+            .split(' ')
+            .map(String::from)
+            .collect();
+        let names: Vec<String> = ["I", "II", "III"].map(String::from).to_vec();
+        EnigmaMachine::historical(
+            HISTORICAL_ALPHABET,
+            "",
+            "",
+            &names,
+            ReflectorSource::Pairs(&pairs),
+            plugboard.path().to_str().unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn machine_from_repo_files<'a>(input: &'a str, output: &'a str) -> EnigmaMachine<'a> {
+        EnigmaMachine::new(
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+            input,
+            output,
+            "reflector.txt",
+            3,
+            "passwords.txt",
+            "rotors_cursor.txt",
+            "plugboard.txt",
+            "M",
+            "M",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_split_output_reassembles() {
+        use crate::cipher::part_path;
+
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let input_path = dir.path().join("input.txt");
+        let input_path = input_path.to_str().unwrap();
+        let single_path = dir.path().join("single.txt");
+        let single_path = single_path.to_str().unwrap();
+        let split_path = dir.path().join("split.txt");
+        let split_path = split_path.to_str().unwrap();
+        let round_trip_path = dir.path().join("round_trip.txt");
+        let round_trip_path = round_trip_path.to_str().unwrap();
+        std::fs::write(input_path, "ATTACKATDAWNONTHEEASTERNFRONT").unwrap();
+
+        let mut enigma = EnigmaMachine::from_seed(alphabet, input_path, single_path, 3, 2).unwrap();
+        enigma.encrypt().unwrap();
+
+        let mut enigma = EnigmaMachine::from_seed(alphabet, input_path, split_path, 3, 2).unwrap();
+        enigma.base_mut().split_bytes = 8;
+        enigma.encrypt().unwrap();
+
+        // 29个字节拆成4个分卷，拼接后与单个文件相同
+        let parts: Vec<String> = (1..=4)
+            .map(|i| std::fs::read_to_string(part_path(split_path, i)).unwrap())
+            .collect();
+        assert!(!std::path::Path::new(&part_path(split_path, 5)).exists());
+        assert_eq!(parts[3].len(), 5);
+        assert_eq!(
+            parts.concat(),
+            std::fs::read_to_string(single_path).unwrap()
+        );
+
+        // 转子状态跨分卷连续，从分卷读入即可解密
+        let mut enigma =
+            EnigmaMachine::from_seed(alphabet, split_path, round_trip_path, 3, 2).unwrap();
+        enigma.base_mut().input_parts = 4;
+        enigma.encrypt().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(round_trip_path).unwrap(),
+            "ATTACKATDAWNONTHEEASTERNFRONT"
+        );
+    }
+
+    #[test]
+    fn test_encrypt_string_in_memory() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let mut enigma = EnigmaMachine::from_seed(alphabet, "", "", 3, 5).unwrap();
+        let ciphertext = enigma.encrypt_string("HELLOWORLD").unwrap();
+        assert_eq!(ciphertext.len(), 10);
+        assert_ne!(ciphertext, "HELLOWORLD");
+
+        // 分两次加密与一次加密整段文本相同
+        let mut enigma = EnigmaMachine::from_seed(alphabet, "", "", 3, 5).unwrap();
+        let first = enigma.encrypt_string("HELLO").unwrap();
+        let second = enigma.encrypt_string("WORLD").unwrap();
+        assert_eq!(first + &second, ciphertext);
+
+        let mut enigma = EnigmaMachine::from_seed(alphabet, "", "", 3, 5).unwrap();
+        assert_eq!(enigma.encrypt_string(&ciphertext).unwrap(), "HELLOWORLD");
+    }
+
+    #[test]
+    fn test_preserve_non_alpha_round_trip() {
+        use tempfile::NamedTempFile;
+
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        std::fs::write(input_path, "ATTACK AT DAWN!").unwrap();
+
+        let mut enigma = EnigmaMachine::from_seed(alphabet, input_path, output_path, 3, 9).unwrap();
+        enigma.base_mut().preserve_non_alpha = true;
+        enigma.encrypt().unwrap();
+        let ciphertext = std::fs::read_to_string(output_path).unwrap();
+        assert_eq!(ciphertext.len(), 15);
+        assert_eq!(&ciphertext[6..7], " ");
+        assert!(ciphertext.ends_with('!'));
+
+        let mut enigma =
+            EnigmaMachine::from_seed(alphabet, output_path, round_trip_path, 3, 9).unwrap();
+        enigma.base_mut().preserve_non_alpha = true;
+        enigma.encrypt().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(round_trip_path).unwrap(),
+            "ATTACK AT DAWN!"
+        );
+    }
+
+    #[test]
+    fn test_second_plugboard() {
+        use tempfile::NamedTempFile;
+
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let plain_output = NamedTempFile::new().expect("Failed to create temporary output file");
+        let plain_output_path = plain_output.path().to_str().unwrap();
+        let double_output = NamedTempFile::new().expect("Failed to create temporary output file");
+        let double_output_path = double_output.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        let plugboard2_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let plugboard2_path = plugboard2_file.path().to_str().unwrap();
+
+        std::fs::write(input_path, "ATTACKATDAWNONTHEEASTERNFRONT").unwrap();
+        std::fs::write(plugboard2_path, "B-K\nM-T\nO-S\n").unwrap();
+
+        let mut enigma = machine_from_repo_files(input_path, plain_output_path);
+        enigma.encrypt().unwrap();
+
+        let mut enigma = machine_from_repo_files(input_path, double_output_path);
+        enigma.load_plugboard2(plugboard2_path).unwrap();
+        enigma.encrypt().unwrap();
+
+        let single = std::fs::read_to_string(plain_output_path).unwrap();
+        let double = std::fs::read_to_string(double_output_path).unwrap();
+        assert_ne!(
+            single, double,
+            "The second plugboard should change the output"
+        );
+
+        // 两块插线板都是对称的，机器仍然自反
+        let mut enigma = machine_from_repo_files(double_output_path, round_trip_path);
+        enigma.load_plugboard2(plugboard2_path).unwrap();
+        enigma.encrypt().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(round_trip_path).unwrap(),
+            "ATTACKATDAWNONTHEEASTERNFRONT"
+        );
+    }
+
+    #[test]
+    fn test_demo_seed_is_reproducible() {
+        use tempfile::NamedTempFile;
+
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        std::fs::write(input_path, "ATTACKATDAWNONTHEEASTERNFRONT").unwrap();
+
+        let encrypt_with_seed = |seed: u64| {
+            let output_file = NamedTempFile::new().expect("Failed to create temporary file");
+            let output_path = output_file.path().to_str().unwrap();
+            let mut enigma = EnigmaMachine::from_seed(
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+                input_path,
+                output_path,
+                3,
+                seed,
+            )
+            .unwrap();
+            assert_eq!(enigma.plugboard.len(), 2 * DEMO_PLUGBOARD_PAIRS);
+            enigma.encrypt().unwrap();
+            std::fs::read_to_string(output_path).unwrap()
+        };
+
+        assert_eq!(encrypt_with_seed(42), encrypt_with_seed(42));
+        assert_ne!(encrypt_with_seed(42), encrypt_with_seed(43));
+    }
+
+    #[test]
+    fn test_enigma_reciprocity() {
+        let mut enigma = machine_from_repo_files("input.txt", "output.txt");
+        assert!(enigma.is_reciprocal());
+
+        enigma.reflector.insert('A', 'Q');
+        assert!(!enigma.is_reciprocal());
+    }
+
+    #[test]
+    fn test_rotor_pass_order() {
+        use tempfile::NamedTempFile;
+
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        std::fs::write(input_path, "ATTACKATDAWNONTHEEASTERNFRONT").unwrap();
+
+        let encrypt_with_order = |order: RotorPassOrder| {
+            let output_file = NamedTempFile::new().expect("Failed to create temporary file");
+            let output_path = output_file.path().to_str().unwrap();
+            let mut enigma = machine_from_repo_files(input_path, output_path);
+            enigma.set_rotor_pass_order(order);
+            assert_eq!(enigma.rotors.len(), 3);
+            enigma.encrypt().unwrap();
+            std::fs::read_to_string(output_path).unwrap()
+        };
+
+        // 密码本转子只是按偏移量移动字符，偏移量相加与顺序无关，两种顺序结果相同
+        assert_eq!(
+            encrypt_with_order(RotorPassOrder::Same),
+            encrypt_with_order(RotorPassOrder::Reversed)
+        );
+
+        // 历史转子的接线是置换，返回路径的顺序会改变结果
+        let mut reversed = historical_machine_b();
+        let mut same = reversed.clone();
+        same.set_rotor_pass_order(RotorPassOrder::Same);
+        let plaintext = "ATTACKATDAWNONTHEEASTERNFRONT";
+        assert_ne!(
+            same.encrypt_string(plaintext).unwrap(),
+            reversed.encrypt_string(plaintext).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_indicator_round_trip() {
+        // 含Z的报文密钥：密码本转子上Z与A落在同一位置
+        for key in ["KEY", "KEZ"] {
+            indicator_round_trip(key);
+        }
+    }
+
+    fn indicator_round_trip(key: &str) {
+        use tempfile::NamedTempFile;
+
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        std::fs::write(input_path, "ATTACKATDAWN").unwrap();
+
+        let mut enigma = machine_from_repo_files(input_path, output_path);
+        enigma.set_indicator(Some(Indicator::Write(key.to_string())));
+        enigma.encrypt().unwrap();
+        let ciphertext = std::fs::read_to_string(output_path).unwrap();
+        assert_eq!(ciphertext.len(), 6 + 12);
+
+        // 指示器之后的密文应与直接从报文密钥位置开始加密的结果相同
+        let mut direct = machine_from_repo_files(input_path, round_trip_path);
+        let cursors = direct.key_cursors(key).unwrap();
+        direct.set_cursors(&cursors);
+        direct.encrypt().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(round_trip_path).unwrap(),
+            ciphertext[6..]
+        );
+
+        let mut enigma = machine_from_repo_files(output_path, round_trip_path);
+        enigma.set_indicator(Some(Indicator::Read));
+        enigma.encrypt().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(round_trip_path).unwrap(),
+            "ATTACKATDAWN"
+        );
+    }
+
+    #[test]
+    fn test_radio_format_groups_and_parses() {
+        let message = to_radio_format("QSZVZG", "ABCDEFGHIJ");
+        assert_eq!(message, "3 = QSZVZG =\nABCD EFGH IJ\n");
+        assert_eq!(parse_radio_format(&message).unwrap(), "QSZVZGABCDEFGHIJ");
+
+        // 每行十组，没有指示器时报头只有组数
+        let message = to_radio_format("", &"A".repeat(44));
+        assert_eq!(message.lines().count(), 3);
+        assert!(message.starts_with("11 =\n"));
+        assert_eq!(parse_radio_format(&message).unwrap(), "A".repeat(44));
+
+        let err = parse_radio_format("4 =\nABCD EFGH IJ\n").unwrap_err();
+        assert!(matches!(err, CipherError::InvalidInput(_)), "{err}");
+        assert!(parse_radio_format("ABCD EFGH\n").is_err());
+    }
+
+    #[test]
+    fn test_radio_format_round_trip() {
+        use tempfile::NamedTempFile;
+
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        std::fs::write(input_path, "ATTACKATDAWN").unwrap();
+
+        let mut enigma = machine_from_repo_files(input_path, output_path);
+        enigma.set_indicator(Some(Indicator::Write("KEY".to_string())));
+        enigma.set_radio_format(Some(RadioFormat::Write));
+        enigma.encrypt().unwrap();
+        let message = std::fs::read_to_string(output_path).unwrap();
+        let (header, body) = message.split_once('\n').unwrap();
+        assert!(header.starts_with("3 = "));
+        assert!(body.split_whitespace().all(|group| group.len() == 4));
+
+        let mut enigma = machine_from_repo_files(output_path, round_trip_path);
+        enigma.set_indicator(Some(Indicator::Read));
+        enigma.set_radio_format(Some(RadioFormat::Read));
+        enigma.encrypt().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(round_trip_path).unwrap(),
+            "ATTACKATDAWN"
+        );
+    }
+
+    #[test]
+    fn test_key_schedule() {
+        let enigma = machine_from_repo_files("input.txt", "output.txt");
+        let schedule = enigma.key_schedule(5).unwrap();
+        assert_eq!(schedule.len(), 5);
+
+        // 第一个位置就是未步进机器的替换表
+        let unstepped: String = enigma
+            .base
+            .alphabet
+            .chars()
+            .map(|c| enigma.substitute(c))
+            .collect();
+        assert_eq!(schedule[0], unstepped);
+        assert_ne!(schedule[0], schedule[1]);
+
+        // 机器本身没有被步进
+        assert_eq!(enigma.key_schedule(1).unwrap()[0], schedule[0]);
+    }
+
+    #[test]
+    fn test_config_eq_after_reload() {
+        use tempfile::NamedTempFile;
+
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let reflector_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let reflector_path = reflector_file.path().to_str().unwrap();
+        let passwords_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let passwords_path = passwords_file.path().to_str().unwrap();
+        let cursor_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let cursor_path = cursor_file.path().to_str().unwrap();
+        std::fs::write(input_path, "ATTACKATDAWN").unwrap();
+
+        let build = |from: &str| {
+            EnigmaMachine::new(
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+                input_path,
+                output_path,
+                reflector_path,
+                3,
+                passwords_path,
+                cursor_path,
+                "plugboard.txt",
+                from,
+                from,
+            )
+            .unwrap()
+        };
+
+        // 生成的机器把密码本以JSON写入文件，重新载入后设置应当相同
+        let mut generated = build("m");
+        let reloaded = build("M");
+        assert!(generated.config_eq(&reloaded));
+
+        // 步进之后仍然相同
+        generated.encrypt().unwrap();
+        assert!(generated.config_eq(&reloaded));
+
+        let mut changed = build("M");
+        changed.plugboard.insert('Y', 'Z');
+        assert!(!changed.config_eq(&reloaded));
+    }
+
+    #[test]
+    fn test_state_file_round_trip() {
+        use tempfile::NamedTempFile;
+
+        let state_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let state_path = state_file.path().to_str().unwrap();
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let plaintext = "ATTACKATDAWNONTHEEASTERNFRONT";
+
+        let mut original = EnigmaMachine::from_seed(alphabet, "", "", 3, 7).unwrap();
+        original.set_ring_settings(vec![1, 2, 3]).unwrap();
+        original.save_state(state_path).unwrap();
+        let mut loaded = EnigmaMachine::load_state(alphabet, "", "", state_path).unwrap();
+        assert!(loaded.config_eq(&original));
+        assert_eq!(
+            loaded.encrypt_string(plaintext).unwrap(),
+            original.encrypt_string(plaintext).unwrap()
+        );
+
+        // 镜像转子、步进方式和第二块插线板都在状态中，载入后不必再设置
+        let mut original = EnigmaMachine::from_seed(alphabet, "", "", 3, 7).unwrap();
+        original.invert_rotors();
+        original.set_step_size(3).unwrap();
+        original.set_single_step(true);
+        original.set_rotor_pass_order(RotorPassOrder::Reversed);
+        original.plugboard2 = HashMap::from([('A', 'B'), ('B', 'A')]);
+        original.save_state(state_path).unwrap();
+        let mut loaded = EnigmaMachine::load_state(alphabet, "", "", state_path).unwrap();
+        assert!(loaded.config_eq(&original));
+        assert_eq!(
+            loaded.encrypt_string(plaintext).unwrap(),
+            original.encrypt_string(plaintext).unwrap()
+        );
+
+        // 历史转子的接线、缺口和步进方式也一并保存
+        let historical = historical_machine_b();
+        historical.save_state(state_path).unwrap();
+        let mut loaded = EnigmaMachine::load_state(alphabet, "", "", state_path).unwrap();
+        assert_eq!(loaded.encrypt_string("AAAAAAAAAA").unwrap(), "BDZGOWCXLT");
+
+        // 正反两个方向不互逆的接线在载入时被拒绝
+        let content = std::fs::read_to_string(state_path).unwrap();
+        let mut state: MachineState = serde_json::from_str(&content).unwrap();
+        let wiring = state.rotors[0].wiring.as_mut().unwrap();
+        wiring.backward.swap(0, 1);
+        std::fs::write(state_path, serde_json::to_string(&state).unwrap()).unwrap();
+        let err = EnigmaMachine::load_state(alphabet, "", "", state_path)
+            .err()
+            .expect("Inconsistent wiring must be rejected");
+        assert!(matches!(err, CipherError::InvalidRotorConfig(_)), "{err}");
+        std::fs::write(state_path, content).unwrap();
+
+        // 手工改坏的反射器在载入时被拒绝
+        let content = std::fs::read_to_string(state_path).unwrap();
+        std::fs::write(
+            state_path,
+            content.replacen("\"A\": \"Y\"", "\"A\": \"A\"", 1),
+        )
+        .unwrap();
+        let err = EnigmaMachine::load_state(alphabet, "", "", state_path)
+            .err()
+            .expect("A self-paired reflector must be rejected");
+        assert!(matches!(err, CipherError::InvalidReflector(_)), "{err}");
+    }
+
+    #[test]
+    fn test_enigma_json_round_trip() {
+        use tempfile::NamedTempFile;
+
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let input_path = input_file.path().to_str().unwrap();
+        let encrypted_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let encrypted_path = encrypted_file.path().to_str().unwrap();
+        let decrypted_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let decrypted_path = decrypted_file.path().to_str().unwrap();
+        let document = r#"{"id":7,"note":"Attack at dawn","to":["Bob"]}"#;
+        std::fs::write(input_path, document).unwrap();
+
+        let mut enigma =
+            EnigmaMachine::from_seed(alphabet, input_path, encrypted_path, 3, 5).unwrap();
+        enigma.base_mut().json_mode = true;
+        enigma.encrypt().unwrap();
+        let encrypted: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(encrypted_path).unwrap()).unwrap();
+        assert_eq!(encrypted["id"], 7);
+        assert_ne!(encrypted["note"], "Attack at dawn");
+
+        // 转子在各个值之间连续步进，按同样的顺序解密即可还原
+        let mut enigma =
+            EnigmaMachine::from_seed(alphabet, encrypted_path, decrypted_path, 3, 5).unwrap();
+        enigma.base_mut().json_mode = true;
+        enigma.encrypt().unwrap();
+        let decrypted: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(decrypted_path).unwrap()).unwrap();
+        assert_eq!(
+            decrypted,
+            serde_json::from_str::<serde_json::Value>(document).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rotor_positions_after_encryption() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let mut enigma = EnigmaMachine::from_seed(alphabet, "", "", 3, 11).unwrap();
+        let start = enigma.rotor_positions();
+        enigma.encrypt_string(&"A".repeat(60)).unwrap();
+
+        // 每个转子一圈25格，指针像里程表一样进位
+        let positions = alphabet.len() - 1;
+        let total = start[0] + positions * (start[1] + positions * start[2]) + 60;
+        let expected = vec![
+            total % positions,
+            total / positions % positions,
+            total / positions / positions % positions,
+        ];
+        assert_eq!(enigma.rotor_positions(), expected);
+        // 字母表之外的字符不步进转子
+        enigma.encrypt_string("123").unwrap();
+        assert_eq!(enigma.rotor_positions(), expected);
+    }
+
+    #[test]
+    fn test_enigma_preserves_length() {
+        use tempfile::NamedTempFile;
+
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        std::fs::write(input_path, "THE QUICK BROWN FOX, 42 TIMES!").unwrap();
+
+        let mut enigma = machine_from_repo_files(input_path, output_path);
+        enigma.encrypt().unwrap();
+
+        assert_eq!(enigma.base.plain_text, "THEQUICKBROWNFOXTIMES");
+        assert_eq!(
+            enigma.base.encrypted_text.chars().count(),
+            enigma.base.plain_text.chars().count()
+        );
+    }
 }
@@ -0,0 +1,99 @@
+use std::{error, fmt, io};
+
+/// 密码库的错误类型：读写失败，或者某项设置不合法。
+/// 设置错误按部件分开，调用方可以按变体区分具体是哪里出了问题。
+#[derive(Debug)]
+pub enum CipherError {
+    /// 读写文件失败。
+    Io(io::Error),
+    /// 关键词中出现了字母表之外的字符。
+    InvalidKeyword(char),
+    /// 插线板设置不合法。
+    InvalidPlugboard(String),
+    /// 反射器设置不合法。
+    InvalidReflector(String),
+    /// 转子的密码本、指针、环设置或名称不合法。
+    InvalidRotorConfig(String),
+    /// 其他不合法的参数或输入，例如空关键词、报文密钥或报文格式。
+    InvalidInput(String),
+}
+
+pub type Result<T> = std::result::Result<T, CipherError>;
+
+impl CipherError {
+    /// 对应的 `io::ErrorKind`：读写错误保持原样，其余都视为不合法的数据。
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            CipherError::Io(e) => e.kind(),
+            CipherError::InvalidKeyword(_) | CipherError::InvalidInput(_) => {
+                io::ErrorKind::InvalidInput
+            }
+            _ => io::ErrorKind::InvalidData,
+        }
+    }
+}
+
+impl fmt::Display for CipherError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CipherError::Io(e) => write!(f, "{e}"),
+            CipherError::InvalidKeyword(ch) => {
+                write!(f, "Keyword character '{ch}' not found in alphabet")
+            }
+            CipherError::InvalidPlugboard(msg) => write!(f, "Invalid plugboard: {msg}"),
+            CipherError::InvalidReflector(msg) => write!(f, "Invalid reflector: {msg}"),
+            CipherError::InvalidRotorConfig(msg) => write!(f, "Invalid rotor config: {msg}"),
+            CipherError::InvalidInput(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl error::Error for CipherError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CipherError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CipherError {
+    fn from(e: io::Error) -> Self {
+        CipherError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for CipherError {
+    fn from(e: serde_json::Error) -> Self {
+        CipherError::Io(e.into())
+    }
+}
+
+/// 命令行和 `Cipher` 特征仍然使用 `io::Result`，这里让 `?` 可以直接转换。
+impl From<CipherError> for io::Error {
+    fn from(e: CipherError) -> Self {
+        match e {
+            CipherError::Io(e) => e,
+            other => io::Error::new(other.kind(), other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_error_round_trip() {
+        let err = CipherError::from(io::Error::new(io::ErrorKind::NotFound, "missing"));
+        assert!(matches!(err, CipherError::Io(_)));
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::NotFound);
+
+        // 设置错误转换成 io::Error 后仍能取回原来的变体
+        let err = io::Error::from(CipherError::InvalidKeyword(' '));
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let inner = err.into_inner().unwrap().downcast::<CipherError>().unwrap();
+        assert!(matches!(*inner, CipherError::InvalidKeyword(' ')));
+    }
+}
@@ -0,0 +1,120 @@
+use crate::cipher::CipherBase;
+use crate::square::{KeySquare, locate};
+use std::io;
+
+/// 四方密码：左上和右下是普通字母方阵，右上和左下是两个关键词生成的方阵，
+/// 每次加密一对字母。
+pub struct FourSquareCipher<'a> {
+    base: CipherBase<'a>,
+    plain_square: KeySquare,
+    upper_right: KeySquare,
+    lower_left: KeySquare,
+    decrypt: bool,
+}
+
+impl<'a> FourSquareCipher<'a> {
+    pub fn new(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        keyword1: &str,
+        keyword2: &str,
+        decrypt: bool,
+    ) -> io::Result<Self> {
+        Ok(FourSquareCipher {
+            base: CipherBase::new(alphabet, input_file, output_file),
+            plain_square: KeySquare::new(alphabet, "", 5)?,
+            upper_right: KeySquare::new(alphabet, keyword1, 5)?,
+            lower_left: KeySquare::new(alphabet, keyword2, 5)?,
+            decrypt,
+        })
+    }
+
+    /// 加密一对字母：第一个字母在左上方阵定行，第二个字母在右下方阵定行，
+    /// 结果分别取右上方阵和左下方阵中对应行列交叉处的字母。
+    fn encrypt_pair(&self, first: char, second: char) -> (char, char) {
+        let (r1, c1) = locate(&self.plain_square, first);
+        let (r2, c2) = locate(&self.plain_square, second);
+        (self.upper_right.at(r1, c2), self.lower_left.at(r2, c1))
+    }
+
+    /// 解密一对字母，是 `encrypt_pair` 的逆过程。
+    fn decrypt_pair(&self, first: char, second: char) -> (char, char) {
+        let (r1, c2) = locate(&self.upper_right, first);
+        let (r2, c1) = locate(&self.lower_left, second);
+        (self.plain_square.at(r1, c1), self.plain_square.at(r2, c2))
+    }
+
+    /// 在内存中加密（或按构造时的设置解密）一段文本。
+    pub fn encrypt_string(&self, text: &str) -> String {
+        self.plain_square
+            .digraphs(text)
+            .chunks(2)
+            .flat_map(|pair| {
+                let (a, b) = if self.decrypt {
+                    self.decrypt_pair(pair[0], pair[1])
+                } else {
+                    self.encrypt_pair(pair[0], pair[1])
+                };
+                [a, b]
+            })
+            .collect()
+    }
+
+    pub fn base(&self) -> &CipherBase<'a> {
+        &self.base
+    }
+
+    pub fn base_mut(&mut self) -> &mut CipherBase<'a> {
+        &mut self.base
+    }
+
+    pub fn encrypt(&mut self) -> std::io::Result<()> {
+        self.base.get_text()?;
+        self.base.clean_text();
+
+        self.base.encrypted_text = self.encrypt_string(&self.base.plain_text);
+
+        self.base.save_file()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square::keyed_cells;
+
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    #[test]
+    fn test_four_square_wikipedia_example() {
+        // 维基百科的例子：方阵省略Q而不是合并I/J，关键词为EXAMPLE和KEYWORD
+        let letters: Vec<char> = ALPHABET.chars().filter(|&c| c != 'Q').collect();
+        let square = |keyword| KeySquare::from_cells(5, keyed_cells(keyword, &letters, |c| c));
+        let mut cipher = FourSquareCipher::new(ALPHABET, "", "", "", "", false).unwrap();
+        cipher.plain_square = square("");
+        cipher.upper_right = square("EXAMPLE");
+        cipher.lower_left = square("KEYWORD");
+        assert_eq!(
+            cipher.encrypt_string("HELPMEOBIWANKENOBI"),
+            "FYGMKYHOBXMFKKKIMD"
+        );
+
+        cipher.decrypt = true;
+        assert_eq!(
+            cipher.encrypt_string("FYGMKYHOBXMFKKKIMD"),
+            "HELPMEOBIWANKENOBI"
+        );
+    }
+
+    #[test]
+    fn test_four_square_round_trip() {
+        let encrypt = FourSquareCipher::new(ALPHABET, "", "", "CIPHER", "SQUARE", false).unwrap();
+        let decrypt = FourSquareCipher::new(ALPHABET, "", "", "CIPHER", "SQUARE", true).unwrap();
+
+        let ciphertext = encrypt.encrypt_string("JUSTATEST");
+        assert_eq!(ciphertext.len(), 10);
+        // J并入I，奇数长度补X
+        assert_eq!(decrypt.encrypt_string(&ciphertext), "IUSTATESTX");
+    }
+}
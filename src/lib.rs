@@ -0,0 +1,27 @@
+//! 古典密码工具集：凯撒密码、多表密码、恩尼格玛以及若干方阵类密码。
+//! 命令行程序只是这些模块的一层包装，也可以直接在其他程序中使用。
+
+pub mod a1z26;
+pub mod affine;
+pub mod analysis;
+pub mod atbash;
+pub mod benchmark;
+pub mod bifid;
+pub mod caesar;
+pub mod checkerboard;
+pub mod cipher;
+pub mod columnar;
+pub mod config;
+pub mod enigma;
+pub mod error;
+pub mod foursquare;
+pub mod otp;
+pub mod playfair;
+pub mod polyalphabetic;
+pub mod railfence;
+pub mod rename;
+pub mod scytale;
+pub mod square;
+pub mod substitution;
+pub mod trifid;
+pub mod twosquare;
@@ -1,16 +1,165 @@
-mod caesar;
-mod cipher;
-mod enigma;
-mod polyalphabetic;
+use ::cipher::{
+    a1z26, affine, analysis, atbash, benchmark, bifid, caesar, checkerboard, cipher, columnar,
+    config, enigma, foursquare, otp, playfair, polyalphabetic, railfence, rename, scytale,
+    substitution, trifid, twosquare,
+};
+use clap::{Arg, ArgMatches, Command, parser::ValueSource};
+use log::{info, warn};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
 
-use clap::{Arg, Command};
+/// 可选的cargo特性及其是否编译进来，供 `--features` 列出。
+const OPTIONAL_FEATURES: &[(&str, bool)] = &[("rayon", cfg!(feature = "rayon"))];
 
-fn main() -> std::io::Result<()> {
-    let alphabet: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
-
-    let matches = Command::new("cipher")
-        .version("0.1.0")
+fn build_cli() -> Command {
+    let mut cli = Command::new("cipher")
+        .version(env!("CARGO_PKG_VERSION"))
         .about("A multi-functional cipher tool")
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .global(true)
+                .help("Read default argument values from a TOML or JSON config file"),
+        )
+        .arg(
+            Arg::new("phonetic")
+                .long("phonetic")
+                .global(true)
+                .action(clap::ArgAction::SetTrue)
+                .help("Write the ciphertext as NATO phonetic words"),
+        )
+        .arg(
+            Arg::new("side_by_side")
+                .long("side_by_side")
+                .global(true)
+                .action(clap::ArgAction::SetTrue)
+                .help("Write each cleaned plaintext character next to its ciphertext"),
+        )
+        .arg(
+            Arg::new("stats")
+                .long("stats")
+                .global(true)
+                .action(clap::ArgAction::SetTrue)
+                .help("Print ciphertext statistics to stderr"),
+        )
+        .arg(
+            Arg::new("group")
+                .long("group")
+                .global(true)
+                .value_parser(clap::value_parser!(usize))
+                .help("Split the ciphertext into groups of this many characters"),
+        )
+        .arg(
+            Arg::new("group_sep")
+                .long("group_sep")
+                .global(true)
+                .help("Separator placed between groups and stripped from the input (default: a space when --group is given)"),
+        )
+        .arg(
+            Arg::new("invert")
+                .long("invert")
+                .global(true)
+                .action(clap::ArgAction::SetTrue)
+                .help("Apply an Atbash layer to the input and the output"),
+        )
+        .arg(
+            Arg::new("strict_ascii")
+                .long("strict_ascii")
+                .global(true)
+                .action(clap::ArgAction::SetTrue)
+                .help("Reject non-ASCII input instead of silently dropping it"),
+        )
+        .arg(
+            Arg::new("transpose")
+                .long("transpose")
+                .global(true)
+                .value_name("KEYWORD")
+                .help("Apply a columnar transposition after the substitution (undone first with -d or --untranspose)"),
+        )
+        .arg(
+            Arg::new("untranspose")
+                .long("untranspose")
+                .global(true)
+                .action(clap::ArgAction::SetTrue)
+                .requires("transpose")
+                .help("Undo the --transpose on the input before deciphering; needed by ciphers without -d such as atbash, enigma and beaufort"),
+        )
+        .arg(
+            Arg::new("split_bytes")
+                .long("split_bytes")
+                .global(true)
+                .value_parser(clap::value_parser!(usize))
+                .help("Split the output into files of at most N bytes (OUTPUT.001, OUTPUT.002, ...)"),
+        )
+        .arg(
+            Arg::new("parts")
+                .long("parts")
+                .global(true)
+                .value_parser(clap::value_parser!(usize))
+                .help("Read the input from N split files (INPUT.001 to INPUT.00N)"),
+        )
+        .arg(
+            Arg::new("mark_ciphertext")
+                .long("mark_ciphertext")
+                .global(true)
+                .action(clap::ArgAction::SetTrue)
+                .help("Mark the output as ciphertext; ignored when decrypting"),
+        )
+        .arg(
+            Arg::new("input_encoding")
+                .long("input_encoding")
+                .global(true)
+                .value_parser(["utf-8", "latin-1", "ascii"])
+                .help("Encoding of the input file (default utf-8)"),
+        )
+        .arg(
+            Arg::new("output_encoding")
+                .long("output_encoding")
+                .global(true)
+                .value_parser(["utf-8", "latin-1", "ascii"])
+                .help("Encoding of the output file; ascii also uppercases letters (default utf-8)"),
+        )
+        .arg(
+            Arg::new("case")
+                .long("case")
+                .global(true)
+                .value_parser(["upper", "lower", "preserve"])
+                .help("Case conversion applied to the input before dropping non-alphabet characters"),
+        )
+        .arg(
+            Arg::new("output_case")
+                .long("output_case")
+                .global(true)
+                .value_parser(["upper", "lower", "preserve"])
+                .help("Case of the emitted ciphertext (default preserve)"),
+        )
+        .arg(
+            Arg::new("alphabet")
+                .long("alphabet")
+                .global(true)
+                .help("Characters the ciphers work on, in order; overrides CIPHER_ALPHABET (default A-Z)"),
+        )
+        .arg(
+            Arg::new("dump_alphabet")
+                .long("dump_alphabet")
+                .global(true)
+                .action(clap::ArgAction::SetTrue)
+                .help("Print the working alphabet with indices and exit"),
+        )
+        .arg(
+            Arg::new("from_phonetic")
+                .long("from_phonetic")
+                .global(true)
+                .action(clap::ArgAction::SetTrue)
+                .help("Read the input as NATO phonetic words"),
+        )
+        .arg(
+            Arg::new("features")
+                .long("features")
+                .action(clap::ArgAction::SetTrue)
+                .help("Print the version, build profile, available ciphers and optional features, then exit"),
+        )
         .subcommand(
             Command::new("caesar")
                 .about("Caesar cipher")
@@ -22,6 +171,93 @@ fn main() -> std::io::Result<()> {
                         .long("shift")
                         .default_value("3")
                         .value_parser(clap::value_parser!(i32)),
+                )
+                .arg(
+                    Arg::new("decrypt")
+                        .short('d')
+                        .long("decrypt")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Decrypt by shifting backwards"),
+                )
+                .arg(
+                    Arg::new("emit_recipe")
+                        .long("emit_recipe")
+                        .value_name("FILE")
+                        .help("Write instructions for decrypting the output to FILE"),
+                )
+                .arg(
+                    Arg::new("flashcards")
+                        .long("flashcards")
+                        .value_name("FILE")
+                        .conflicts_with("decrypt")
+                        .help("Also write each input word and its encryption to FILE as TSV flashcards"),
+                )
+                .arg(
+                    Arg::new("stride")
+                        .long("stride")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Encrypt only every Nth cleaned character, leaving the others unchanged"),
+                )
+                .arg(
+                    Arg::new("line_increment")
+                        .long("line_increment")
+                        .default_value("0")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(i32))
+                        .help("Add this much to the shift for every following line"),
+                )
+                .arg(
+                    Arg::new("preserve_non_alpha")
+                        .long("preserve_non_alpha")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Keep spaces and punctuation in place instead of dropping them"),
+                )
+                .arg(
+                    Arg::new("preserve_chars")
+                        .long("preserve_chars")
+                        .value_name("CHARS")
+                        .help("Keep only these characters from outside the alphabet, drop the rest"),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Encrypt only the string values of a JSON document"),
+                )
+                .arg(
+                    Arg::new("stream")
+                        .long("stream")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with_all(["json", "flashcards"])
+                        .help("Read, encrypt and write the input in chunks instead of loading it whole"),
+                ),
+        )
+        .subcommand(
+            Command::new("affine")
+                .about("Affine cipher: x becomes a*x + b modulo the alphabet length")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(Arg::new("output").short('o').long("output").required(true))
+                .arg(
+                    Arg::new("a")
+                        .long("a")
+                        .required(true)
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(i32))
+                        .help("Multiplier; must share no factor with the alphabet length"),
+                )
+                .arg(
+                    Arg::new("b")
+                        .long("b")
+                        .default_value("0")
+                        .allow_negative_numbers(true)
+                        .value_parser(clap::value_parser!(i32))
+                        .help("Shift added after multiplying"),
+                )
+                .arg(
+                    Arg::new("decrypt")
+                        .short('d')
+                        .long("decrypt")
+                        .action(clap::ArgAction::SetTrue),
                 ),
         )
         .subcommand(
@@ -29,17 +265,217 @@ fn main() -> std::io::Result<()> {
                 .about("Polyalphabetic cipher")
                 .arg(Arg::new("input").short('i').long("input").required(true))
                 .arg(Arg::new("output").short('o').long("output").required(true))
+                .arg(
+                    Arg::new("stride")
+                        .long("stride")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Encrypt only every Nth cleaned character, leaving the others unchanged"),
+                )
                 .arg(
                     Arg::new("keyword")
                         .short('k')
                         .long("keyword")
+                        .help("Keyword; overrides CIPHER_KEYWORD"),
+                )
+                .arg(
+                    Arg::new("passphrase")
+                        .long("passphrase")
+                        .help("Derive the keyword from a passphrase via PBKDF2"),
+                )
+                .arg(
+                    Arg::new("running_key_file")
+                        .long("running_key_file")
+                        .conflicts_with_all(["passphrase", "skip_symbol"])
+                        .help("Use the text of a file as a running key; it must be at least as long as the input"),
+                )
+                .arg(
+                    Arg::new("key_length")
+                        .long("key_length")
+                        .default_value("16")
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("decrypt")
+                        .short('d')
+                        .long("decrypt")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("mode")
+                        .long("mode")
+                        .default_value("repeating")
+                        .value_parser(["repeating", "autokey", "beaufort"])
+                        .conflicts_with_all(["running_key_file", "skip_symbol"])
+                        .help("autokey continues the key with the plaintext once the keyword is used up; beaufort subtracts the plaintext from the key and is its own inverse"),
+                )
+                .arg(
+                    Arg::new("skip_symbol")
+                        .long("skip_symbol")
+                        .value_parser(clap::value_parser!(char))
+                        .help("Keyword character meaning no shift at that position"),
+                )
+                .arg(
+                    Arg::new("emit_recipe")
+                        .long("emit_recipe")
+                        .value_name("FILE")
+                        .help("Write instructions for decrypting the output to FILE"),
+                )
+                .arg(
+                    Arg::new("flashcards")
+                        .long("flashcards")
+                        .value_name("FILE")
+                        .conflicts_with("decrypt")
+                        .help("Also write each input word and its encryption to FILE as TSV flashcards"),
+                )
+                .arg(
+                    Arg::new("preserve_non_alpha")
+                        .long("preserve_non_alpha")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Keep spaces and punctuation in place instead of dropping them"),
+                )
+                .arg(
+                    Arg::new("preserve_chars")
+                        .long("preserve_chars")
+                        .value_name("CHARS")
+                        .help("Keep only these characters from outside the alphabet, drop the rest"),
+                )
+                .arg(
+                    Arg::new("product_keyword")
+                        .long("product_keyword")
+                        .action(clap::ArgAction::Append)
+                        .conflicts_with("running_key_file")
+                        .help("Stack another keyword on top of the first (product cipher); repeatable"),
+                )
+                .arg(
+                    Arg::new("show_config")
+                        .long("show_config")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Print the effective key period to stderr"),
+                )
+                .arg(
+                    Arg::new("fingerprint")
+                        .long("fingerprint")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Embed a short key fingerprint so decryption can detect a wrong key"),
+                )
+                .arg(
+                    Arg::new("repeat_key_to_length")
+                        .long("repeat_key_to_length")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Print the full key stream used for the cleaned input"),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Encrypt only the string values of a JSON document"),
+                )
+                .arg(
+                    Arg::new("stream")
+                        .long("stream")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with_all(["json", "flashcards", "repeat_key_to_length"])
+                        .help("Read, encrypt and write the input in chunks instead of loading it whole"),
+                ),
+        )
+        .subcommand(
+            Command::new("poly-crack")
+                .about("Recover a polyalphabetic keyword with a wordlist attack")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(Arg::new("output").short('o').long("output").required(true))
+                .arg(
+                    Arg::new("wordlist")
+                        .short('w')
+                        .long("wordlist")
                         .required(true),
                 )
+                .arg(
+                    Arg::new("score")
+                        .long("score")
+                        .default_value("chi2")
+                        .value_parser(["chi2", "trigram"])
+                        .help("How to rank candidate plaintexts"),
+                ),
+        )
+        .subcommand(
+            Command::new("caesar-crack")
+                .about("Try every Caesar shift and list the candidate plaintexts")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Write the candidates here instead of stdout"),
+                )
+                .arg(
+                    Arg::new("score")
+                        .long("score")
+                        .default_value("chi2")
+                        .value_parser(["chi2", "trigram", "none"])
+                        .help("List the candidates best first by this score; none lists them by shift"),
+                ),
+        )
+        .subcommand(
+            Command::new("keylen")
+                .about("Rank candidate polyalphabetic key lengths by average index of coincidence")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(
+                    Arg::new("max_length")
+                        .long("max_length")
+                        .default_value("20")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Longest key length to try"),
+                ),
+        )
+        .subcommand(
+            Command::new("analyze")
+                .about("Print the letter count, index of coincidence and frequency table of a file")
+                .arg(Arg::new("input").short('i').long("input").required(true)),
+        )
+        .subcommand(
+            Command::new("benchmark")
+                .about("Time each cipher on the same synthetic input")
+                .arg(
+                    Arg::new("size")
+                        .long("size")
+                        .default_value("100000")
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .default_value("0")
+                        .value_parser(clap::value_parser!(u64)),
+                ),
+        )
+        .subcommand(
+            Command::new("rename")
+                .about("Encrypt the file names in a directory, keeping extensions")
+                .arg(Arg::new("dir").long("dir").required(true))
+                .arg(
+                    Arg::new("shift")
+                        .short('s')
+                        .long("shift")
+                        .default_value("3")
+                        .value_parser(clap::value_parser!(i32)),
+                )
+                .arg(
+                    Arg::new("keyword")
+                        .short('k')
+                        .long("keyword")
+                        .help("Use the polyalphabetic cipher instead of Caesar"),
+                )
                 .arg(
                     Arg::new("decrypt")
                         .short('d')
                         .long("decrypt")
-                        .action(clap::ArgAction::SetFalse),
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("dry_run")
+                        .long("dry_run")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Only print the planned renames"),
                 ),
         )
         .subcommand(
@@ -74,92 +510,1756 @@ fn main() -> std::io::Result<()> {
                         .long("plugboard_file")
                         .default_value("plugboard.txt"),
                 )
+                .arg(
+                    Arg::new("state_file")
+                        .long("state_file")
+                        .value_name("FILE")
+                        .help("Load the whole machine from this JSON file if it exists, ignoring the separate config files and machine options; otherwise save the machine built from them to it"),
+                )
+                .arg(
+                    Arg::new("plugboard2_file")
+                        .long("plugboard2_file")
+                        .help("Experimental second plugboard between the rotors and the reflector"),
+                )
+                .arg(
+                    Arg::new("demo_seed")
+                        .long("demo_seed")
+                        .value_parser(clap::value_parser!(u64))
+                        .help("Generate the whole machine from a seed, ignoring config files"),
+                )
                 .arg(
                     Arg::new("reflector_from")
                         .long("reflector_from")
                         .default_value("M"),
                 )
+                .arg(
+                    Arg::new("reflector_pairs")
+                        .long("reflector_pairs")
+                        .num_args(1..)
+                        .value_name("PAIR")
+                        .conflicts_with_all(["reflector_from", "demo_seed"])
+                        .help("Build the reflector from letter pairs such as AB CD EF instead of a file"),
+                )
                 .arg(
                     Arg::new("rotors_from")
                         .long("rotors_from")
                         .default_value("M"),
+                )
+                .arg(
+                    Arg::new("rotor_set")
+                        .long("rotor_set")
+                        .num_args(1..)
+                        .value_name("ROTOR")
+                        .conflicts_with("demo_seed")
+                        .help("Use the historical rotors I-V, named from left to right such as I II III, instead of the passwords file"),
+                )
+                .arg(
+                    Arg::new("ring_settings")
+                        .long("ring_settings")
+                        .num_args(1..)
+                        .value_name("RING")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Ring setting of each rotor, in the same order as the rotor cursors (fastest rotor first)"),
+                )
+                .arg(
+                    Arg::new("rotor_pass_order")
+                        .long("rotor_pass_order")
+                        .default_value("same")
+                        .value_parser(["same", "reversed"])
+                        .help("Rotor order on the return path; reversed matches the real machine. Only affects --rotor_set rotors, shift rotors give the same result either way"),
+                )
+                .arg(
+                    Arg::new("emit_recipe")
+                        .long("emit_recipe")
+                        .value_name("FILE")
+                        .help("Write instructions for decrypting the output to FILE"),
+                )
+                .arg(
+                    Arg::new("preserve_non_alpha")
+                        .long("preserve_non_alpha")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Keep spaces and punctuation in place instead of dropping them"),
+                )
+                .arg(
+                    Arg::new("preserve_chars")
+                        .long("preserve_chars")
+                        .value_name("CHARS")
+                        .help("Keep only these characters from outside the alphabet, drop the rest"),
+                )
+                .arg(
+                    Arg::new("invert_rotors")
+                        .long("invert_rotors")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Replace every rotor with its mirror image (inverse offsets)"),
+                )
+                .arg(
+                    Arg::new("single_step")
+                        .long("single_step")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Simplified stepping for teaching: only the first rotor ever moves"),
+                )
+                .arg(
+                    Arg::new("step_size")
+                        .long("step_size")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("1")
+                        .help("Experimental: advance the rotors by N positions per character"),
+                )
+                .arg(
+                    Arg::new("indicator")
+                        .long("indicator")
+                        .conflicts_with("read_indicator")
+                        .help("Encipher this message key twice at the start and use it as the start position"),
+                )
+                .arg(
+                    Arg::new("show_positions")
+                        .long("show_positions")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Print where the rotors ended up after encrypting, fastest rotor first"),
+                )
+                .arg(
+                    Arg::new("key_schedule")
+                        .long("key_schedule")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Print the substitution alphabet for the first N rotor positions and exit"),
+                )
+                .arg(
+                    Arg::new("read_indicator")
+                        .long("read_indicator")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Read the doubled message key from the start of the ciphertext"),
+                )
+                .arg(
+                    Arg::new("radio_format")
+                        .long("radio_format")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with("read_radio_format")
+                        .help("Write the ciphertext as a radio message: a header with the group count and indicator, then groups of four"),
+                )
+                .arg(
+                    Arg::new("read_radio_format")
+                        .long("read_radio_format")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Read a radio message written with --radio_format"),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with_all([
+                            "indicator",
+                            "read_indicator",
+                            "radio_format",
+                            "read_radio_format",
+                        ])
+                        .help("Encrypt only the string values of a JSON document, in document order"),
+                )
+                .arg(
+                    Arg::new("stream")
+                        .long("stream")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with_all([
+                            "indicator",
+                            "read_indicator",
+                            "radio_format",
+                            "read_radio_format",
+                        ])
+                        .help("Read, encrypt and write the input in chunks instead of loading it whole"),
                 ),
         )
-        .get_matches();
-
-    match matches.subcommand() {
-        Some(("caesar", sub_matches)) => {
-            let input = sub_matches
-                .get_one::<String>("input")
-                .expect("Input file is required");
-            let output = sub_matches
-                .get_one::<String>("output")
-                .expect("Output file is required");
-            let shift = *sub_matches
-                .get_one::<i32>("shift")
-                .expect("Shift value is required");
-            let mut cipher = caesar::CaesarCipher::new(alphabet, input, output, shift);
-            cipher.encrypt()
-        }
-        Some(("poly", sub_matches)) => {
-            let input = sub_matches
-                .get_one::<String>("input")
-                .expect("Input file is required");
-            let output = sub_matches
-                .get_one::<String>("output")
-                .expect("Output file is required");
-            let keyword = sub_matches
-                .get_one::<String>("keyword")
-                .expect("Keyword is required");
-            let decrypt = sub_matches.get_flag("decrypt");
-            let mut cipher = polyalphabetic::PolyalphabeticCipher::new(
-                alphabet, input, output, keyword, decrypt,
-            );
-            cipher.encrypt()
-        }
-        Some(("enigma", sub_matches)) => {
-            let input = sub_matches
-                .get_one::<String>("input")
-                .expect("Input file is required");
-            let output = sub_matches
-                .get_one::<String>("output")
-                .expect("Output file is required");
-            let reflector_from = sub_matches
-                .get_one::<String>("reflector_from")
-                .expect("Reflector from value is required");
-            let reflector_file = sub_matches
-                .get_one::<String>("reflector_file")
-                .expect("Reflector file is required");
-            let rotor_num = *sub_matches
-                .get_one::<usize>("rotor_num")
-                .expect("Rotor number is required");
-            let rotors_from = sub_matches
-                .get_one::<String>("rotors_from")
-                .expect("Rotors from value is required");
-            let passwords_file = sub_matches
-                .get_one::<String>("passwords_file")
-                .expect("Passwords file is required");
-            let rotors_cursor_file = sub_matches
-                .get_one::<String>("rotors_cursor_file")
-                .expect("Rotors cursor file is required");
-            let plugboard_file = sub_matches
-                .get_one::<String>("plugboard_file")
-                .expect("Plugboard file is required");
-
-            let mut enigma = enigma::EnigmaMachine::new(
-                alphabet,
-                input,
-                output,
-                reflector_file,
-                rotor_num,
-                passwords_file,
-                rotors_cursor_file,
-                plugboard_file,
-                reflector_from,
-                rotors_from,
-            );
-            enigma.encrypt()
-        }
-        _ => unreachable!("Exhausted list of subcommands"),
+        .subcommand(
+            Command::new("normalize-config")
+                .about("Validate a plugboard or reflector file and rewrite it in canonical form")
+                .arg(Arg::new("file").short('f').long("file").required(true))
+                .arg(
+                    Arg::new("kind")
+                        .long("kind")
+                        .required(true)
+                        .value_parser(["plugboard", "reflector"]),
+                ),
+        )
+        .subcommand(
+            Command::new("foursquare")
+                .about("Four-Square digraph cipher (J is merged into I)")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(Arg::new("output").short('o').long("output").required(true))
+                .arg(Arg::new("keyword1").long("keyword1").required(true))
+                .arg(Arg::new("keyword2").long("keyword2").required(true))
+                .arg(
+                    Arg::new("decrypt")
+                        .short('d')
+                        .long("decrypt")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("playfair")
+                .about("Playfair digraph cipher (J is merged into I, repeated letters are split with X)")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(Arg::new("output").short('o').long("output").required(true))
+                .arg(
+                    Arg::new("keyword")
+                        .short('k')
+                        .long("keyword")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("decrypt")
+                        .short('d')
+                        .long("decrypt")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("bifid")
+                .about("Bifid cipher: split letters into square coordinates and recombine them (J is merged into I)")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(Arg::new("output").short('o').long("output").required(true))
+                .arg(
+                    Arg::new("keyword")
+                        .short('k')
+                        .long("keyword")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("period")
+                        .short('p')
+                        .long("period")
+                        .default_value("5")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Letters per block; 0 treats the whole text as one block"),
+                )
+                .arg(
+                    Arg::new("decrypt")
+                        .short('d')
+                        .long("decrypt")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("trifid")
+                .about("Trifid cipher: split letters into cube coordinates and recombine them (the cube adds '+' to the letters)")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(Arg::new("output").short('o').long("output").required(true))
+                .arg(
+                    Arg::new("keyword")
+                        .short('k')
+                        .long("keyword")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("period")
+                        .short('p')
+                        .long("period")
+                        .default_value("5")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Letters per block; 0 treats the whole text as one block"),
+                )
+                .arg(
+                    Arg::new("decrypt")
+                        .short('d')
+                        .long("decrypt")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("a1z26")
+                .about("Write letters as their 1-based alphabet positions, or read them back")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(Arg::new("output").short('o').long("output").required(true))
+                .arg(
+                    Arg::new("separator")
+                        .long("separator")
+                        .default_value(" ")
+                        .help("Separator placed between numbers, e.g. \" \" or \",\""),
+                )
+                .arg(
+                    Arg::new("keyword")
+                        .short('k')
+                        .long("keyword")
+                        .help("Shift the letters with a polyalphabetic keyword before numbering"),
+                )
+                .arg(
+                    Arg::new("tolerant")
+                        .long("tolerant")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Replace undecodable numbers with '?' instead of failing"),
+                )
+                .arg(
+                    Arg::new("decrypt")
+                        .short('d')
+                        .long("decrypt")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("checkerboard")
+                .about("Straddling checkerboard from the VIC cipher")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(Arg::new("output").short('o').long("output").required(true))
+                .arg(
+                    Arg::new("keyword")
+                        .short('k')
+                        .long("keyword")
+                        .default_value(""),
+                )
+                .arg(
+                    Arg::new("blanks")
+                        .long("blanks")
+                        .default_value("2,6")
+                        .value_delimiter(',')
+                        .value_parser(clap::value_parser!(usize))
+                        .help("The two blank columns of the top row, e.g. 2,6"),
+                )
+                .arg(
+                    Arg::new("decrypt")
+                        .short('d')
+                        .long("decrypt")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("twosquare")
+                .about("Two-Square digraph cipher (J is merged into I)")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(Arg::new("output").short('o').long("output").required(true))
+                .arg(Arg::new("keyword1").long("keyword1").required(true))
+                .arg(Arg::new("keyword2").long("keyword2").required(true))
+                .arg(
+                    Arg::new("variant")
+                        .long("variant")
+                        .default_value("horizontal")
+                        .value_parser(["horizontal", "vertical"])
+                        .help("Place the two squares side by side or one above the other"),
+                )
+                .arg(
+                    Arg::new("decrypt")
+                        .short('d')
+                        .long("decrypt")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("scytale")
+                .about("Scytale transposition: write rows around a rod, read along it")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(Arg::new("output").short('o').long("output").required(true))
+                .arg(
+                    Arg::new("circumference")
+                        .short('c')
+                        .long("circumference")
+                        .required(true)
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Number of letters around the rod"),
+                )
+                .arg(
+                    Arg::new("decrypt")
+                        .short('d')
+                        .long("decrypt")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("otp")
+                .about("One-time pad: XOR the input bytes with a key file at least as long; run again to decrypt")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(Arg::new("output").short('o').long("output").required(true))
+                .arg(
+                    Arg::new("key_file")
+                        .short('k')
+                        .long("key_file")
+                        .required(true)
+                        .help("Random key bytes, never reused; must be at least as long as the input"),
+                ),
+        )
+        .subcommand(
+            Command::new("atbash")
+                .about("Atbash: reverse the alphabet, encrypting and decrypting are the same")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(Arg::new("output").short('o').long("output").required(true)),
+        )
+        .subcommand(
+            Command::new("railfence")
+                .about("Rail fence transposition: write in a zig-zag over the rails, read rail by rail")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(Arg::new("output").short('o').long("output").required(true))
+                .arg(
+                    Arg::new("rails")
+                        .short('r')
+                        .long("rails")
+                        .required(true)
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("decrypt")
+                        .short('d')
+                        .long("decrypt")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("columnar")
+                .about("Columnar transposition: write rows under the keyword, read columns in keyword order")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(Arg::new("output").short('o').long("output").required(true))
+                .arg(
+                    Arg::new("keyword")
+                        .short('k')
+                        .long("keyword")
+                        .required(true)
+                        .help("Columns are read in the order of its letters; repeated letters left to right"),
+                )
+                .arg(
+                    Arg::new("decrypt")
+                        .short('d')
+                        .long("decrypt")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("sub")
+                .about("Monoalphabetic substitution with a permutation of the alphabet as key")
+                .arg(Arg::new("input").short('i').long("input").required(true))
+                .arg(Arg::new("output").short('o').long("output").required(true))
+                .arg(
+                    Arg::new("key")
+                        .short('k')
+                        .long("key")
+                        .required(true)
+                        .help("The alphabet in substituted order, e.g. QWERTYUIOPASDFGHJKLZXCVBNM"),
+                )
+                .arg(
+                    Arg::new("decrypt")
+                        .short('d')
+                        .long("decrypt")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        );
+
+    // 配置文件中的参数排在命令行参数之前，允许命令行中同名参数覆盖它们
+    let names: Vec<String> = cli
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_string())
+        .collect();
+    for name in names {
+        cli = cli.mut_subcommand(name, |sub| sub.args_override_self(true));
+    }
+    cli
+}
+
+fn main() -> std::io::Result<()> {
+    env_logger::init();
+
+    let cli = build_cli();
+    let args = config::apply_config(&cli, std::env::args().collect())?;
+    let matches = cli.clone().get_matches_from(args);
+    run(&cli, &matches)
+}
+
+/// 按解析好的命令行参数执行子命令。
+fn run(cli: &Command, matches: &ArgMatches) -> io::Result<()> {
+    let alphabet = resolve_alphabet(
+        matches.get_one::<String>("alphabet").cloned(),
+        std::env::var("CIPHER_ALPHABET").ok(),
+    )?;
+    let alphabet: &str = &alphabet;
+
+    if matches.get_flag("features") {
+        print!("{}", build_info(cli));
+        return Ok(());
+    }
+
+    let dump_requested = matches.get_flag("dump_alphabet")
+        || matches
+            .subcommand()
+            .is_some_and(|(_, sub_matches)| sub_matches.get_flag("dump_alphabet"));
+    if dump_requested {
+        print!("{}", cipher::dump_alphabet(alphabet));
+        return Ok(());
+    }
+
+    if let Some(group_sep) = matches
+        .subcommand()
+        .and_then(|(_, sub_matches)| group_separator(sub_matches))
+    {
+        cipher::validate_group_sep(alphabet, &group_sep)?;
+    }
+
+    match matches.subcommand() {
+        Some(("caesar", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            let shift = *sub_matches
+                .get_one::<i32>("shift")
+                .expect("Shift value is required");
+            let mut cipher = caesar::CaesarCipher::new(alphabet, input, output, shift);
+            cipher.set_line_increment(
+                *sub_matches
+                    .get_one::<i32>("line_increment")
+                    .expect("Line increment has a default"),
+            );
+            if cipher.is_reciprocal() {
+                info!("Shift {shift} is its own inverse, encrypting twice restores the input");
+            }
+            apply_io_options(cipher.base_mut(), sub_matches);
+            apply_preserve_options(cipher.base_mut(), sub_matches);
+            cipher.base_mut().json_mode = sub_matches.get_flag("json");
+            cipher.base_mut().stream = sub_matches.get_flag("stream");
+            if sub_matches.get_flag("decrypt") {
+                cipher.decrypt()?;
+            } else {
+                cipher.encrypt()?;
+            }
+            report_stats(cipher.base(), sub_matches);
+            if let Some(recipe_file) = sub_matches.get_one::<String>("emit_recipe") {
+                std::fs::write(recipe_file, cipher.recipe())?;
+            }
+            write_flashcards(cipher.base(), sub_matches, |word| {
+                cipher.encrypt_string(word)
+            })?;
+            Ok(())
+        }
+        Some(("affine", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            let a = *sub_matches.get_one::<i32>("a").expect("a is required");
+            let b = *sub_matches.get_one::<i32>("b").expect("b has a default");
+            let decrypt = sub_matches.get_flag("decrypt");
+
+            let mut cipher = affine::AffineCipher::new(alphabet, input, output, a, b, decrypt)?;
+            apply_io_options(cipher.base_mut(), sub_matches);
+            cipher.encrypt()?;
+            report_stats(cipher.base(), sub_matches);
+            Ok(())
+        }
+        Some(("poly", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            // 环境变量中的关键词只是默认值，命令行同时给出关键词和口令才算冲突
+            if sub_matches.contains_id("passphrase") && sub_matches.contains_id("keyword") {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "--keyword cannot be used with --passphrase",
+                ));
+            }
+            let decrypt = sub_matches.get_flag("decrypt");
+            let mut cipher = if let Some(running_key_file) =
+                sub_matches.get_one::<String>("running_key_file")
+            {
+                let key_text = std::fs::read_to_string(running_key_file)?;
+                polyalphabetic::PolyalphabeticCipher::with_running_key(
+                    alphabet, input, output, &key_text, decrypt,
+                )?
+            } else {
+                let keyword = match sub_matches.get_one::<String>("passphrase") {
+                    Some(passphrase) => {
+                        let key_length = *sub_matches
+                            .get_one::<usize>("key_length")
+                            .expect("Key length is required");
+                        polyalphabetic::derive_keyword(alphabet, passphrase, key_length)
+                    }
+                    None => resolve_keyword(
+                        sub_matches.get_one::<String>("keyword").cloned(),
+                        std::env::var("CIPHER_KEYWORD").ok(),
+                    )?,
+                };
+                match sub_matches.get_one::<char>("skip_symbol") {
+                    Some(&skip_symbol) => polyalphabetic::PolyalphabeticCipher::with_skip_symbol(
+                        alphabet,
+                        input,
+                        output,
+                        &keyword,
+                        decrypt,
+                        skip_symbol,
+                    )?,
+                    None if sub_matches
+                        .get_one::<String>("mode")
+                        .is_some_and(|mode| mode == "autokey") =>
+                    {
+                        polyalphabetic::PolyalphabeticCipher::with_autokey(
+                            alphabet, input, output, &keyword, decrypt,
+                        )?
+                    }
+                    None if sub_matches
+                        .get_one::<String>("mode")
+                        .is_some_and(|mode| mode == "beaufort") =>
+                    {
+                        polyalphabetic::PolyalphabeticCipher::with_beaufort(
+                            alphabet, input, output, &keyword,
+                        )?
+                    }
+                    None => polyalphabetic::PolyalphabeticCipher::new(
+                        alphabet, input, output, &keyword, decrypt,
+                    )?,
+                }
+            };
+            for keyword in sub_matches
+                .get_many::<String>("product_keyword")
+                .unwrap_or_default()
+            {
+                cipher.add_keyword(keyword)?;
+            }
+            if sub_matches.get_flag("show_config") {
+                eprintln!("Period: {}", cipher.period());
+            }
+            if sub_matches.value_source("decrypt") == Some(ValueSource::CommandLine)
+                && cipher.is_reciprocal()
+            {
+                warn!("This keyword makes the cipher its own inverse, --decrypt is unnecessary");
+            }
+            apply_io_options(cipher.base_mut(), sub_matches);
+            apply_preserve_options(cipher.base_mut(), sub_matches);
+            cipher.base_mut().json_mode = sub_matches.get_flag("json");
+            cipher.base_mut().stream = sub_matches.get_flag("stream");
+            if sub_matches.get_flag("fingerprint") {
+                cipher.embed_fingerprint();
+            }
+            cipher.encrypt()?;
+            report_stats(cipher.base(), sub_matches);
+            if let Some(recipe_file) = sub_matches.get_one::<String>("emit_recipe") {
+                std::fs::write(recipe_file, cipher.recipe())?;
+            }
+            write_flashcards(cipher.base(), sub_matches, |word| {
+                cipher.encrypt_string(word)
+            })?;
+
+            if sub_matches.get_flag("repeat_key_to_length") {
+                let key_stream: Vec<String> = cipher
+                    .materialize_key(&cipher.base().plain_text)
+                    .iter()
+                    .map(|shift| shift.to_string())
+                    .collect();
+                println!("{}", key_stream.join(" "));
+            }
+            Ok(())
+        }
+        Some(("poly-crack", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            let wordlist = sub_matches
+                .get_one::<String>("wordlist")
+                .expect("Wordlist file is required");
+
+            let mut base = cipher::CipherBase::new(alphabet, input, output);
+            apply_io_options(&mut base, sub_matches);
+            base.get_text()?;
+            base.clean_text();
+
+            let reader = BufReader::new(File::open(wordlist)?);
+            let scoring = parse_scoring(sub_matches).unwrap_or(analysis::Scoring::ChiSquared);
+            let best =
+                polyalphabetic::crack_with_wordlist(alphabet, &base.plain_text, reader, scoring)?
+                    .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "No usable keyword in wordlist")
+                })?;
+            println!(
+                "Best keyword: {} ({} {:.2})",
+                best.keyword,
+                scoring.name(),
+                best.score
+            );
+            base.encrypted_text = best.plaintext;
+            base.save_file()
+        }
+        Some(("caesar-crack", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+
+            let mut cipher = caesar::CaesarCipher::new(alphabet, input, "", 0);
+            apply_io_options(cipher.base_mut(), sub_matches);
+            cipher.base_mut().get_text()?;
+            cipher.base_mut().clean_text();
+
+            let candidates = match parse_scoring(sub_matches) {
+                Some(scoring) => {
+                    let ranked = cipher.ranked_candidates(scoring);
+                    if let Some((shift, _)) = ranked.first() {
+                        eprintln!("Best guess: shift {shift} ({})", scoring.name());
+                    }
+                    ranked
+                }
+                None => cipher.brute_force(),
+            };
+            let candidates: String = candidates
+                .iter()
+                .map(|(shift, plaintext)| format!("{shift:>2} {plaintext}\n"))
+                .collect();
+            match sub_matches.get_one::<String>("output") {
+                Some(output) => std::fs::write(output, candidates),
+                None => {
+                    print!("{candidates}");
+                    Ok(())
+                }
+            }
+        }
+        Some(("keylen", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let max_length = *sub_matches
+                .get_one::<usize>("max_length")
+                .expect("Max length has a default");
+
+            let mut base = cipher::CipherBase::new(alphabet, input, "");
+            apply_io_options(&mut base, sub_matches);
+            base.get_text()?;
+            base.clean_text();
+
+            for (length, ioc) in
+                analysis::key_length_candidates(&base.plain_text, alphabet, max_length)
+            {
+                println!("{length:>3} {ioc:.4}");
+            }
+            Ok(())
+        }
+        Some(("analyze", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+
+            // 与加密时一样先清理每行，小写字母也计入
+            let base = cipher::CipherBase::new(alphabet, input, "");
+            let reader = BufReader::new(File::open(input)?);
+            let counts =
+                analysis::letter_counts_streaming(reader, alphabet, |line| base.clean(line))?;
+            println!("Letters: {}", counts.values().sum::<usize>());
+            println!(
+                "IoC:     {:.4}",
+                analysis::index_of_coincidence_from_counts(&counts)
+            );
+            println!();
+            print!("{}", analysis::frequency_table(&counts, alphabet));
+            Ok(())
+        }
+        Some(("benchmark", sub_matches)) => {
+            let size = *sub_matches
+                .get_one::<usize>("size")
+                .expect("Size is required");
+            let seed = *sub_matches
+                .get_one::<u64>("seed")
+                .expect("Seed is required");
+
+            println!("{:<14} {:>10} {:>16}", "Cipher", "Chars", "Chars/s");
+            for result in benchmark::run(alphabet, size, seed)? {
+                println!(
+                    "{:<14} {:>10} {:>16.0}",
+                    result.name,
+                    result.chars,
+                    result.rate()
+                );
+            }
+            Ok(())
+        }
+        Some(("rename", sub_matches)) => {
+            let dir = sub_matches
+                .get_one::<String>("dir")
+                .expect("Directory is required");
+            let shift = *sub_matches
+                .get_one::<i32>("shift")
+                .expect("Shift value is required");
+            let decrypt = sub_matches.get_flag("decrypt");
+
+            let transform: Box<dyn Fn(&str) -> String> =
+                match sub_matches.get_one::<String>("keyword") {
+                    Some(keyword) => {
+                        let cipher = polyalphabetic::PolyalphabeticCipher::new(
+                            alphabet, "", "", keyword, decrypt,
+                        )?;
+                        Box::new(move |text| cipher.encrypt_string(text))
+                    }
+                    None => {
+                        let shift = if decrypt { -shift } else { shift };
+                        let cipher = caesar::CaesarCipher::new(alphabet, "", "", shift);
+                        Box::new(move |text| cipher.encrypt_string(text))
+                    }
+                };
+
+            let plan = rename::plan_renames(Path::new(dir), |stem| {
+                rename::transform_alphabet_chars(alphabet, stem, &transform)
+            })?;
+            for (source, target) in &plan {
+                println!("{} -> {}", source.display(), target.display());
+            }
+            if sub_matches.get_flag("dry_run") {
+                Ok(())
+            } else {
+                rename::apply_renames(&plan)
+            }
+        }
+        Some(("enigma", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            let reflector_from = sub_matches
+                .get_one::<String>("reflector_from")
+                .expect("Reflector from value is required");
+            let reflector_file = sub_matches
+                .get_one::<String>("reflector_file")
+                .expect("Reflector file is required");
+            let rotor_num = *sub_matches
+                .get_one::<usize>("rotor_num")
+                .expect("Rotor number is required");
+            let rotors_from = sub_matches
+                .get_one::<String>("rotors_from")
+                .expect("Rotors from value is required");
+            let passwords_file = sub_matches
+                .get_one::<String>("passwords_file")
+                .expect("Passwords file is required");
+            let rotors_cursor_file = sub_matches
+                .get_one::<String>("rotors_cursor_file")
+                .expect("Rotors cursor file is required");
+            let plugboard_file = sub_matches
+                .get_one::<String>("plugboard_file")
+                .expect("Plugboard file is required");
+
+            let reflector_pairs: Option<Vec<String>> = sub_matches
+                .get_many::<String>("reflector_pairs")
+                .map(|pairs| pairs.cloned().collect());
+            let reflector = match &reflector_pairs {
+                Some(pairs) => enigma::ReflectorSource::Pairs(pairs),
+                None => enigma::ReflectorSource::from_flag(reflector_from, reflector_file),
+            };
+
+            let rotor_set: Option<Vec<String>> = sub_matches
+                .get_many::<String>("rotor_set")
+                .map(|names| names.cloned().collect());
+
+            let state_file = sub_matches.get_one::<String>("state_file");
+            let saved_state = state_file.filter(|file| Path::new(file).exists());
+
+            let mut enigma = match (
+                saved_state,
+                sub_matches.get_one::<u64>("demo_seed"),
+                &rotor_set,
+            ) {
+                (Some(file), _, _) => {
+                    enigma::EnigmaMachine::load_state(alphabet, input, output, file)?
+                }
+                (None, Some(&seed), _) => {
+                    enigma::EnigmaMachine::from_seed(alphabet, input, output, rotor_num, seed)?
+                }
+                (None, None, Some(names)) => enigma::EnigmaMachine::historical(
+                    alphabet,
+                    input,
+                    output,
+                    names,
+                    reflector,
+                    plugboard_file,
+                )?,
+                (None, None, None) => enigma::EnigmaMachine::with_reflector(
+                    alphabet,
+                    input,
+                    output,
+                    reflector,
+                    rotor_num,
+                    passwords_file,
+                    rotors_cursor_file,
+                    plugboard_file,
+                    rotors_from,
+                )?,
+            };
+            // 新生成的设置写入了文件，重新载入一次确认文件能复现这台机器
+            if saved_state.is_none()
+                && sub_matches.get_one::<u64>("demo_seed").is_none()
+                && rotor_set.is_none()
+                && (matches!(reflector, enigma::ReflectorSource::Generate(_)) || rotors_from == "m")
+            {
+                let reloaded = enigma::EnigmaMachine::with_reflector(
+                    alphabet,
+                    input,
+                    output,
+                    match reflector {
+                        enigma::ReflectorSource::Generate(file) => {
+                            enigma::ReflectorSource::File(file)
+                        }
+                        other => other,
+                    },
+                    rotor_num,
+                    passwords_file,
+                    rotors_cursor_file,
+                    plugboard_file,
+                    "M",
+                )?;
+                if !enigma.config_eq(&reloaded) {
+                    warn!("The saved configuration files do not reproduce the generated machine");
+                }
+            }
+            // 状态文件已经包含这些设置，载入后再应用一次会重复镜像转子等
+            let machine_options = [
+                "plugboard2_file",
+                "rotor_pass_order",
+                "invert_rotors",
+                "single_step",
+                "step_size",
+                "ring_settings",
+            ];
+            if saved_state.is_some() {
+                let ignored: Vec<String> = machine_options
+                    .iter()
+                    .filter(|id| sub_matches.value_source(id) == Some(ValueSource::CommandLine))
+                    .map(|id| format!("--{id}"))
+                    .collect();
+                if !ignored.is_empty() {
+                    warn!(
+                        "{} ignored, the state file already records the machine settings",
+                        ignored.join(", ")
+                    );
+                }
+            } else {
+                apply_machine_options(&mut enigma, sub_matches)?;
+            }
+            if let Some(file) = state_file
+                && saved_state.is_none()
+            {
+                enigma.save_state(file)?;
+            }
+            if let Some(key) = sub_matches.get_one::<String>("indicator") {
+                enigma.set_indicator(Some(enigma::Indicator::Write(key.clone())));
+            } else if sub_matches.get_flag("read_indicator") {
+                enigma.set_indicator(Some(enigma::Indicator::Read));
+            }
+            if sub_matches.get_flag("radio_format") {
+                enigma.set_radio_format(Some(enigma::RadioFormat::Write));
+            } else if sub_matches.get_flag("read_radio_format") {
+                enigma.set_radio_format(Some(enigma::RadioFormat::Read));
+            }
+            if !enigma.is_reciprocal() {
+                warn!("The reflector or plugboard is not symmetric, decryption will not work");
+            }
+            if let Some(&steps) = sub_matches.get_one::<usize>("key_schedule") {
+                println!("     {alphabet}");
+                for (i, mapping) in enigma.key_schedule(steps)?.iter().enumerate() {
+                    println!("{i:>4} {mapping}");
+                }
+                return Ok(());
+            }
+            apply_io_options(enigma.base_mut(), sub_matches);
+            apply_preserve_options(enigma.base_mut(), sub_matches);
+            enigma.base_mut().stream = sub_matches.get_flag("stream");
+            enigma.base_mut().json_mode = sub_matches.get_flag("json");
+            enigma.encrypt()?;
+            if sub_matches.get_flag("show_positions") {
+                let positions: Vec<String> = enigma
+                    .rotor_positions()
+                    .iter()
+                    .map(usize::to_string)
+                    .collect();
+                eprintln!("Rotor positions: {}", positions.join(" "));
+            }
+            report_stats(enigma.base(), sub_matches);
+            if let Some(recipe_file) = sub_matches.get_one::<String>("emit_recipe") {
+                std::fs::write(recipe_file, enigma_recipe(output, sub_matches))?;
+            }
+            Ok(())
+        }
+        Some(("normalize-config", sub_matches)) => {
+            let file = sub_matches
+                .get_one::<String>("file")
+                .expect("File is required");
+            let kind = match sub_matches.get_one::<String>("kind").map(String::as_str) {
+                Some("reflector") => enigma::ConfigKind::Reflector,
+                _ => enigma::ConfigKind::Plugboard,
+            };
+            enigma::normalize_config(alphabet, file, kind)?;
+            Ok(())
+        }
+        Some(("foursquare", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            let keyword1 = sub_matches
+                .get_one::<String>("keyword1")
+                .expect("First keyword is required");
+            let keyword2 = sub_matches
+                .get_one::<String>("keyword2")
+                .expect("Second keyword is required");
+            let decrypt = sub_matches.get_flag("decrypt");
+
+            let mut cipher = foursquare::FourSquareCipher::new(
+                alphabet, input, output, keyword1, keyword2, decrypt,
+            )?;
+            apply_io_options(cipher.base_mut(), sub_matches);
+            cipher.encrypt()?;
+            report_stats(cipher.base(), sub_matches);
+            Ok(())
+        }
+        Some(("playfair", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            let keyword = sub_matches
+                .get_one::<String>("keyword")
+                .expect("Keyword is required");
+            let decrypt = sub_matches.get_flag("decrypt");
+
+            let mut cipher =
+                playfair::PlayfairCipher::new(alphabet, input, output, keyword, decrypt)?;
+            apply_io_options(cipher.base_mut(), sub_matches);
+            cipher.encrypt()?;
+            report_stats(cipher.base(), sub_matches);
+            Ok(())
+        }
+        Some(("bifid", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            let keyword = sub_matches
+                .get_one::<String>("keyword")
+                .expect("Keyword is required");
+            let period = *sub_matches
+                .get_one::<usize>("period")
+                .expect("Period has a default");
+            let decrypt = sub_matches.get_flag("decrypt");
+
+            let mut cipher =
+                bifid::BifidCipher::new(alphabet, input, output, keyword, period, decrypt)?;
+            apply_io_options(cipher.base_mut(), sub_matches);
+            cipher.encrypt()?;
+            report_stats(cipher.base(), sub_matches);
+            Ok(())
+        }
+        Some(("trifid", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            let keyword = sub_matches
+                .get_one::<String>("keyword")
+                .expect("Keyword is required");
+            let period = *sub_matches
+                .get_one::<usize>("period")
+                .expect("Period has a default");
+            let decrypt = sub_matches.get_flag("decrypt");
+
+            let mut cipher =
+                trifid::TrifidCipher::new(alphabet, input, output, keyword, period, decrypt)?;
+            apply_io_options(cipher.base_mut(), sub_matches);
+            cipher.encrypt()?;
+            report_stats(cipher.base(), sub_matches);
+            Ok(())
+        }
+        Some(("a1z26", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            let separator = sub_matches
+                .get_one::<String>("separator")
+                .expect("Separator is required");
+            let decrypt = sub_matches.get_flag("decrypt");
+
+            let mut cipher = a1z26::A1Z26Cipher::new(alphabet, input, output, separator, decrypt);
+            if let Some(keyword) = sub_matches.get_one::<String>("keyword") {
+                cipher.set_keyword(keyword)?;
+            }
+            cipher.set_tolerant(sub_matches.get_flag("tolerant"));
+            apply_io_options(cipher.base_mut(), sub_matches);
+            cipher.encrypt()?;
+            report_stats(cipher.base(), sub_matches);
+            Ok(())
+        }
+        Some(("checkerboard", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            let keyword = sub_matches
+                .get_one::<String>("keyword")
+                .expect("Keyword is required");
+            let blanks: Vec<usize> = sub_matches
+                .get_many::<usize>("blanks")
+                .expect("Blank positions are required")
+                .copied()
+                .collect();
+            let blanks: [usize; 2] = blanks.try_into().map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "--blanks needs exactly two positions",
+                )
+            })?;
+            let decrypt = sub_matches.get_flag("decrypt");
+
+            let mut cipher = checkerboard::StraddlingCheckerboard::new(
+                alphabet, input, output, keyword, blanks, decrypt,
+            )?;
+            apply_io_options(cipher.base_mut(), sub_matches);
+            cipher.encrypt()?;
+            report_stats(cipher.base(), sub_matches);
+            Ok(())
+        }
+        Some(("twosquare", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            let keyword1 = sub_matches
+                .get_one::<String>("keyword1")
+                .expect("First keyword is required");
+            let keyword2 = sub_matches
+                .get_one::<String>("keyword2")
+                .expect("Second keyword is required");
+            let variant = match sub_matches.get_one::<String>("variant").map(String::as_str) {
+                Some("vertical") => twosquare::TwoSquareVariant::Vertical,
+                _ => twosquare::TwoSquareVariant::Horizontal,
+            };
+            let decrypt = sub_matches.get_flag("decrypt");
+
+            let mut cipher = twosquare::TwoSquareCipher::new(
+                alphabet, input, output, keyword1, keyword2, variant, decrypt,
+            )?;
+            apply_io_options(cipher.base_mut(), sub_matches);
+            cipher.encrypt()?;
+            report_stats(cipher.base(), sub_matches);
+            Ok(())
+        }
+        Some(("scytale", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            let circumference = *sub_matches
+                .get_one::<usize>("circumference")
+                .expect("Circumference is required");
+            let decrypt = sub_matches.get_flag("decrypt");
+
+            let mut cipher =
+                scytale::ScytaleCipher::new(alphabet, input, output, circumference, decrypt)?;
+            apply_io_options(cipher.base_mut(), sub_matches);
+            cipher.encrypt()?;
+            report_stats(cipher.base(), sub_matches);
+            Ok(())
+        }
+        Some(("otp", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            let key_file = sub_matches
+                .get_one::<String>("key_file")
+                .expect("Key file is required");
+            if sub_matches.contains_id("transpose") {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "The one-time pad works on raw bytes and does not support --transpose",
+                ));
+            }
+            otp::apply(input, key_file, output)?;
+            Ok(())
+        }
+        Some(("atbash", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+
+            let mut cipher = atbash::AtbashCipher::new(alphabet, input, output);
+            apply_io_options(cipher.base_mut(), sub_matches);
+            cipher.encrypt()?;
+            report_stats(cipher.base(), sub_matches);
+            Ok(())
+        }
+        Some(("columnar", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            let keyword = sub_matches
+                .get_one::<String>("keyword")
+                .expect("Keyword is required");
+            let decrypt = sub_matches.get_flag("decrypt");
+
+            let mut cipher =
+                columnar::ColumnarCipher::new(alphabet, input, output, keyword, decrypt)?;
+            apply_io_options(cipher.base_mut(), sub_matches);
+            cipher.encrypt()?;
+            report_stats(cipher.base(), sub_matches);
+            Ok(())
+        }
+        Some(("railfence", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            let rails = *sub_matches
+                .get_one::<usize>("rails")
+                .expect("Number of rails is required");
+            let decrypt = sub_matches.get_flag("decrypt");
+
+            let mut cipher =
+                railfence::RailFenceCipher::new(alphabet, input, output, rails, decrypt)?;
+            apply_io_options(cipher.base_mut(), sub_matches);
+            cipher.encrypt()?;
+            report_stats(cipher.base(), sub_matches);
+            Ok(())
+        }
+        Some(("sub", sub_matches)) => {
+            let input = sub_matches
+                .get_one::<String>("input")
+                .expect("Input file is required");
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("Output file is required");
+            let key = sub_matches
+                .get_one::<String>("key")
+                .expect("Key is required");
+            let decrypt = sub_matches.get_flag("decrypt");
+
+            let mut cipher =
+                substitution::SubstitutionCipher::new(alphabet, input, output, key, decrypt)?;
+            apply_io_options(cipher.base_mut(), sub_matches);
+            cipher.encrypt()?;
+            report_stats(cipher.base(), sub_matches);
+            Ok(())
+        }
+        _ => unreachable!("Exhausted list of subcommands"),
+    }
+}
+
+/// 默认字母表，可以用 `--alphabet` 或环境变量 `CIPHER_ALPHABET` 替换。
+const DEFAULT_ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// 确定实际使用的字母表：命令行参数优先，其次是环境变量，都没有时用默认字母表。
+/// 自定义的字母表不能为空，也不能有重复字符。
+fn resolve_alphabet(cli_value: Option<String>, env_value: Option<String>) -> io::Result<String> {
+    let (alphabet, source) = match (cli_value, env_value) {
+        (Some(alphabet), _) => (alphabet, "--alphabet"),
+        (None, Some(alphabet)) => (alphabet, "CIPHER_ALPHABET"),
+        (None, None) => return Ok(DEFAULT_ALPHABET.to_string()),
+    };
+    cipher::validate_alphabet(&alphabet)?;
+    info!("Using alphabet from {source}: {alphabet}");
+    Ok(alphabet)
+}
+
+/// 把恩尼格玛子命令中改变机器本身的选项应用到机器上，这些设置都会写入状态文件。
+fn apply_machine_options(
+    enigma: &mut enigma::EnigmaMachine,
+    sub_matches: &ArgMatches,
+) -> io::Result<()> {
+    if let Some(plugboard2_file) = sub_matches.get_one::<String>("plugboard2_file") {
+        enigma.load_plugboard2(plugboard2_file)?;
+    }
+    // 历史转子默认倒序返回，只有明确给出时才覆盖机器自己的设置
+    if sub_matches.value_source("rotor_pass_order") != Some(ValueSource::DefaultValue) {
+        match sub_matches
+            .get_one::<String>("rotor_pass_order")
+            .map(String::as_str)
+        {
+            Some("reversed") => enigma.set_rotor_pass_order(enigma::RotorPassOrder::Reversed),
+            _ => enigma.set_rotor_pass_order(enigma::RotorPassOrder::Same),
+        }
+    }
+    if sub_matches.get_flag("invert_rotors") {
+        enigma.invert_rotors();
+    }
+    enigma.set_single_step(sub_matches.get_flag("single_step"));
+    if let Some(&step_size) = sub_matches.get_one::<usize>("step_size") {
+        enigma.set_step_size(step_size)?;
+    }
+    if let Some(rings) = sub_matches.get_many::<usize>("ring_settings") {
+        enigma.set_ring_settings(rings.copied().collect())?;
+    }
+    Ok(())
+}
+
+/// 确定多表密码的关键词：命令行参数优先，其次是环境变量 `CIPHER_KEYWORD`。
+/// 两者都没有，又没有给出口令或滚动密钥文件时报错。
+fn resolve_keyword(cli_value: Option<String>, env_value: Option<String>) -> io::Result<String> {
+    cli_value.or(env_value).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--keyword (or CIPHER_KEYWORD) is required unless --passphrase or --running_key_file is given",
+        )
+    })
+}
+
+/// 把各子命令共享的输入输出选项应用到底层的CipherBase上。
+fn apply_io_options(base: &mut cipher::CipherBase, matches: &ArgMatches) {
+    base.phonetic_input = matches.get_flag("from_phonetic");
+    base.phonetic_output = matches.get_flag("phonetic");
+    base.side_by_side = matches.get_flag("side_by_side");
+    base.invert = matches.get_flag("invert");
+    base.strict_ascii = matches.get_flag("strict_ascii");
+    base.transpose = matches.get_one::<String>("transpose").cloned();
+    // 加密和解密相同的子命令没有 --decrypt，由 --untranspose 指明方向
+    base.decrypting = matches.get_flag("untranspose")
+        || matches
+            .try_get_one::<bool>("decrypt")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false);
+    base.transpose_input = base.decrypting;
+    base.split_bytes = matches
+        .get_one::<usize>("split_bytes")
+        .copied()
+        .unwrap_or(0);
+    base.input_parts = matches.get_one::<usize>("parts").copied().unwrap_or(0);
+    if matches.get_flag("mark_ciphertext") && !base.decrypting {
+        base.mark_ciphertext();
+    }
+    // 只有 caesar 和 poly 支持 --stride
+    base.stride = matches
+        .try_get_one::<usize>("stride")
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(1);
+    base.input_encoding = parse_encoding(matches.get_one::<String>("input_encoding"));
+    base.output_encoding = parse_encoding(matches.get_one::<String>("output_encoding"));
+    if let Some(case) = matches.get_one::<String>("case") {
+        base.case_mode = parse_case(case);
+    }
+    base.output_case = matches
+        .get_one::<String>("output_case")
+        .map_or(cipher::CaseMode::Preserve, |case| parse_case(case));
+    base.group_size = matches.get_one::<usize>("group").copied().unwrap_or(0);
+    base.group_sep = group_separator(matches);
+}
+
+/// 用户给出 `--group` 或 `--group_sep` 时实际使用的分组分隔符，只给出 `--group` 时是空格。
+/// 两者都没有给出时为 `None`，输入中的任何字符都不会被当作分隔符去掉。
+fn group_separator(matches: &ArgMatches) -> Option<String> {
+    matches
+        .get_one::<String>("group_sep")
+        .cloned()
+        .or_else(|| matches.contains_id("group").then(|| " ".to_string()))
+}
+
+/// 把大小写选项转换为 `CaseMode`。
+fn parse_case(name: &str) -> cipher::CaseMode {
+    match name {
+        "upper" => cipher::CaseMode::Upper,
+        "lower" => cipher::CaseMode::Lower,
+        _ => cipher::CaseMode::Preserve,
+    }
+}
+
+/// 把编码名称转换为 `Encoding`，未给出时使用UTF-8。
+fn parse_encoding(name: Option<&String>) -> cipher::Encoding {
+    match name.map(String::as_str) {
+        Some("latin-1") => cipher::Encoding::Latin1,
+        Some("ascii") => cipher::Encoding::Ascii,
+        _ => cipher::Encoding::Utf8,
+    }
+}
+
+/// 读取破解子命令的 `--score` 选项。
+fn parse_scoring(matches: &ArgMatches) -> Option<analysis::Scoring> {
+    match matches.get_one::<String>("score").map(String::as_str) {
+        Some("trigram") => Some(analysis::Scoring::Trigram),
+        Some("none") | None => None,
+        Some(_) => Some(analysis::Scoring::ChiSquared),
+    }
+}
+
+/// 给出 `--flashcards` 时，重新读取输入，把每个单词和它的密文写成TSV抽认卡。
+fn write_flashcards<F: Fn(&str) -> String>(
+    base: &cipher::CipherBase,
+    matches: &ArgMatches,
+    f: F,
+) -> io::Result<()> {
+    let Some(flashcards_file) = matches.get_one::<String>("flashcards") else {
+        return Ok(());
+    };
+    if base.input_file == cipher::STDIO_PATH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--flashcards needs an input file, standard input can only be read once",
+        ));
+    }
+    std::fs::write(flashcards_file, base.flashcards(&base.read_input()?, f))
+}
+
+/// 读取保留非字母字符的选项，只有 caesar、poly 和 enigma 提供。
+fn apply_preserve_options(base: &mut cipher::CipherBase, matches: &ArgMatches) {
+    base.preserve_non_alpha = matches.get_flag("preserve_non_alpha");
+    base.preserve_chars = matches.get_one::<String>("preserve_chars").cloned();
+}
+
+/// 恩尼格玛的解密说明：同一台机器加密即是解密，收件人需要相同的配置文件或演示种子。
+fn enigma_recipe(output: &str, matches: &ArgMatches) -> String {
+    let rotor_num = matches.get_one::<usize>("rotor_num").copied().unwrap_or(3);
+    let mut command = format!("cipher enigma -i {output} -o <plaintext> -n {rotor_num}");
+    let mut files = Vec::new();
+    if let Some(state_file) = matches.get_one::<String>("state_file") {
+        command.push_str(&format!(" --state_file {state_file}"));
+        files.push(state_file.clone());
+    } else if let Some(seed) = matches.get_one::<u64>("demo_seed") {
+        command.push_str(&format!(" --demo_seed {seed}"));
+    } else {
+        for name in [
+            "reflector_file",
+            "passwords_file",
+            "rotors_cursor_file",
+            "plugboard_file",
+        ] {
+            if name == "reflector_file" && matches.contains_id("reflector_pairs") {
+                continue;
+            }
+            if matches!(name, "passwords_file" | "rotors_cursor_file")
+                && matches.contains_id("rotor_set")
+            {
+                continue;
+            }
+            let file = matches.get_one::<String>(name).expect("File has a default");
+            command.push_str(&format!(" --{name} {file}"));
+            files.push(file.clone());
+        }
+        if let Some(pairs) = matches.get_many::<String>("reflector_pairs") {
+            let pairs: Vec<&str> = pairs.map(String::as_str).collect();
+            command.push_str(&format!(" --reflector_pairs {}", pairs.join(" ")));
+        }
+        if let Some(names) = matches.get_many::<String>("rotor_set") {
+            let names: Vec<&str> = names.map(String::as_str).collect();
+            command.push_str(&format!(" --rotor_set {}", names.join(" ")));
+        }
+    }
+    // 状态文件已经记录了这些设置
+    if !matches.contains_id("state_file") {
+        if let Some(plugboard2_file) = matches.get_one::<String>("plugboard2_file") {
+            command.push_str(&format!(" --plugboard2_file {plugboard2_file}"));
+            files.push(plugboard2_file.clone());
+        }
+        if let Some(rings) = matches.get_many::<usize>("ring_settings") {
+            let rings: Vec<String> = rings.map(usize::to_string).collect();
+            command.push_str(&format!(" --ring_settings {}", rings.join(" ")));
+        }
+        for name in ["rotor_pass_order", "step_size"] {
+            if matches.value_source(name) == Some(ValueSource::CommandLine) {
+                let value = matches.get_raw(name).unwrap().next().unwrap();
+                command.push_str(&format!(" --{name} {}", value.to_string_lossy()));
+            }
+        }
+        if matches.get_flag("invert_rotors") {
+            command.push_str(" --invert_rotors");
+        }
+        if matches.get_flag("single_step") {
+            command.push_str(" --single_step");
+        }
+    }
+    if matches.contains_id("indicator") {
+        command.push_str(" --read_indicator");
+    }
+    if matches.get_flag("radio_format") {
+        command.push_str(" --read_radio_format");
+    }
+
+    let mut recipe = format!("To decrypt, run:\n  {command}\n");
+    if !files.is_empty() {
+        recipe.push_str(&format!(
+            "You also need these configuration files, which are not included here: {}\n",
+            files.join(", ")
+        ));
+    }
+    recipe
+}
+
+/// 版本和构建信息：构建类型、可用的子命令，以及编译进来的可选特性。
+fn build_info(cli: &Command) -> String {
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    let commands: Vec<&str> = cli.get_subcommands().map(Command::get_name).collect();
+    let features: Vec<&str> = OPTIONAL_FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect();
+    format!(
+        "cipher {}\nBuild: {profile}\nCommands: {}\nOptional features: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        commands.join(", "),
+        if features.is_empty() {
+            "none".to_string()
+        } else {
+            features.join(", ")
+        }
+    )
+}
+
+/// 在 `--stats` 打开时把统计信息输出到标准错误。
+fn report_stats(base: &cipher::CipherBase, matches: &ArgMatches) {
+    if !matches.get_flag("stats") {
+        return;
+    }
+    let stats = base.stats();
+    eprintln!("Input length:          {}", stats.input_len);
+    eprintln!("Cleaned length:        {}", stats.cleaned_len);
+    eprintln!("Output length:         {}", stats.output_len);
+    eprintln!("Distinct output chars: {}", stats.distinct_output_chars);
+    eprintln!("Output IoC:            {:.4}", stats.output_ioc);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::Builder;
+
+    fn parse(args: &[&str]) -> ArgMatches {
+        let cli = build_cli();
+        let args = config::apply_config(&cli, args.iter().map(|s| s.to_string()).collect())
+            .expect("Failed to apply config");
+        cli.try_get_matches_from(args)
+            .expect("Failed to parse args")
+    }
+
+    #[test]
+    fn test_alphabet_and_keyword_from_env() {
+        assert_eq!(resolve_alphabet(None, None).unwrap(), DEFAULT_ALPHABET);
+        assert_eq!(
+            resolve_alphabet(None, Some("ABC".to_string())).unwrap(),
+            "ABC"
+        );
+        assert!(resolve_alphabet(None, Some("ABA".to_string())).is_err());
+
+        // 命令行参数优先于环境变量，同样检查重复字符
+        assert_eq!(
+            resolve_alphabet(Some("XYZ".to_string()), Some("ABC".to_string())).unwrap(),
+            "XYZ"
+        );
+        assert!(resolve_alphabet(Some("XYZX".to_string()), None).is_err());
+        let matches = parse(&[
+            "cipher",
+            "caesar",
+            "-i",
+            "in.txt",
+            "-o",
+            "out.txt",
+            "--alphabet",
+            "0123456789",
+        ]);
+        assert_eq!(
+            matches.get_one::<String>("alphabet").map(String::as_str),
+            Some("0123456789")
+        );
+
+        // 环境变量中的关键词作为默认值，命令行参数优先
+        assert_eq!(
+            resolve_keyword(None, Some("LEMON".to_string())).unwrap(),
+            "LEMON"
+        );
+        assert_eq!(
+            resolve_keyword(Some("CAT".to_string()), Some("LEMON".to_string())).unwrap(),
+            "CAT"
+        );
+        assert!(resolve_keyword(None, None).is_err());
+        // 不给关键词时由 resolve_keyword 检查，而不是在解析命令行时报错
+        let matches = parse(&["cipher", "poly", "-i", "in.txt", "-o", "out.txt"]);
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(sub_matches.get_one::<String>("keyword"), None);
+    }
+
+    #[test]
+    fn test_build_info_reflects_build() {
+        let info = build_info(&build_cli());
+        assert!(info.starts_with(&format!("cipher {}\n", env!("CARGO_PKG_VERSION"))));
+        assert!(info.contains(if cfg!(debug_assertions) {
+            "Build: debug"
+        } else {
+            "Build: release"
+        }));
+        for command in ["caesar", "poly", "enigma", "trifid"] {
+            assert!(info.contains(command), "{command} missing from {info}");
+        }
+
+        // 列出的可选特性恰好是编译进来的那些
+        let listed = info
+            .lines()
+            .find_map(|line| line.strip_prefix("Optional features: "))
+            .unwrap();
+        for (name, enabled) in OPTIONAL_FEATURES {
+            assert_eq!(listed.split(", ").any(|f| f == *name), *enabled, "{name}");
+        }
+        if OPTIONAL_FEATURES.iter().all(|(_, enabled)| !enabled) {
+            assert_eq!(listed, "none");
+        }
+
+        assert!(parse(&["cipher", "--features"]).get_flag("features"));
+    }
+
+    #[test]
+    fn test_caesar_crack_ranks_by_chi_squared_by_default() {
+        let matches = parse(&["cipher", "caesar-crack", "-i", "in.txt"]);
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(
+            parse_scoring(sub_matches),
+            Some(analysis::Scoring::ChiSquared)
+        );
+
+        let matches = parse(&["cipher", "caesar-crack", "-i", "in.txt", "--score", "none"]);
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(parse_scoring(sub_matches), None);
+    }
+
+    #[test]
+    fn test_alphabet_with_space_is_not_a_group_separator() {
+        use tempfile::NamedTempFile;
+
+        let input_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary file");
+        let output_path = output_file.path().to_str().unwrap();
+        std::fs::write(input_path, "HELLO WORLD").unwrap();
+
+        // 没有给出 --group 或 --group_sep 时空格只是字母表中的一个字符
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ ";
+        let args = [
+            "cipher",
+            "--alphabet",
+            alphabet,
+            "caesar",
+            "-i",
+            input_path,
+            "-o",
+            output_path,
+            "-s",
+            "3",
+        ];
+        run(&build_cli(), &parse(&args)).unwrap();
+        assert_eq!(std::fs::read_to_string(output_path).unwrap(), "KHOORCZRUOG");
+
+        // 明确要求用空格分组时才与字母表冲突
+        let matches = parse(&[&args[..], &["--group", "4"]].concat());
+        assert!(run(&build_cli(), &matches).is_err());
+    }
+
+    #[test]
+    fn test_stride_only_for_caesar_and_poly() {
+        let cli = build_cli();
+        let io = ["-i", "in.txt", "-o", "out.txt"];
+        let parses = |sub: &[&str], stride: bool| {
+            let stride: &[&str] = if stride { &["--stride", "2"] } else { &[] };
+            cli.clone()
+                .try_get_matches_from([&["cipher"][..], sub, &io[..], stride].concat())
+                .is_ok()
+        };
+        assert!(parses(&["caesar"], true));
+        assert!(parses(&["poly"], true));
+        // 其他子命令不会悄悄忽略 --stride
+        for sub in [
+            &["atbash"][..],
+            &["railfence", "--rails", "3"],
+            &["sub", "--key", "QWERTY"],
+        ] {
+            assert!(parses(sub, false));
+            assert!(!parses(sub, true));
+        }
+    }
+
+    #[test]
+    fn test_poly_decrypt_flag() {
+        // 不加 -d 时加密，加 -d 时解密
+        let matches = parse(&[
+            "cipher", "poly", "-i", "in.txt", "-o", "out.txt", "-k", "CAT",
+        ]);
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert!(!sub_matches.get_flag("decrypt"));
+
+        let matches = parse(&[
+            "cipher", "poly", "-i", "in.txt", "-o", "out.txt", "-k", "CAT", "-d",
+        ]);
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert!(sub_matches.get_flag("decrypt"));
+    }
+
+    #[test]
+    fn test_untranspose_sets_direction_without_decrypt_flag() {
+        let transposed_input = |args: &[&str]| {
+            let matches = parse(args);
+            let (_, sub_matches) = matches.subcommand().unwrap();
+            let mut base = cipher::CipherBase::new(DEFAULT_ALPHABET, "in.txt", "out.txt");
+            apply_io_options(&mut base, sub_matches);
+            base.transpose_input
+        };
+        let args = ["cipher", "atbash", "-i", "in.txt", "-o", "out.txt"];
+        assert!(!transposed_input(
+            &[&args[..], &["--transpose", "ZEBRAS"]].concat()
+        ));
+        assert!(transposed_input(
+            &[&args[..], &["--transpose", "ZEBRAS", "--untranspose"]].concat()
+        ));
+
+        // --untranspose 必须与 --transpose 一起使用
+        let cli = build_cli();
+        assert!(
+            cli.try_get_matches_from([&args[..], &["--untranspose"]].concat())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_config_file_sets_and_cli_overrides_shift() {
+        let config_file = Builder::new()
+            .suffix(".toml")
+            .tempfile()
+            .expect("Failed to create temporary config file");
+        std::fs::write(
+            config_file.path(),
+            "[caesar]\ninput = \"input.txt\"\noutput = \"output.txt\"\nshift = 7\nunknown = 1\n",
+        )
+        .expect("Failed to write config file");
+        let config_path = config_file.path().to_str().unwrap();
+
+        let matches = parse(&["cipher", "--config", config_path, "caesar"]);
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(sub_matches.get_one::<i32>("shift"), Some(&7));
+        assert_eq!(
+            sub_matches.get_one::<String>("input").map(String::as_str),
+            Some("input.txt")
+        );
+
+        let matches = parse(&["cipher", "--config", config_path, "caesar", "-s", "2"]);
+        let (_, sub_matches) = matches.subcommand().unwrap();
+        assert_eq!(sub_matches.get_one::<i32>("shift"), Some(&2));
     }
 }
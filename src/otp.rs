@@ -0,0 +1,81 @@
+use crate::error::{CipherError, Result};
+use log::info;
+use std::fs;
+
+/// 一次性密码本（弗纳姆密码）：把输入的每个字节与密钥文件中对应位置的字节异或。
+/// 直接处理原始字节，不经过字母表，所以任何文件都能加密。
+/// 异或是自身的逆运算，用同一个密钥再做一次就还原明文。
+///
+/// 密钥不能比数据短；比数据长时只用开头的部分。
+///
+/// ```
+/// let ciphertext = cipher::otp::xor(b"HI", &[0x01, 0xff]).unwrap();
+/// assert_eq!(ciphertext, vec![b'H' ^ 0x01, b'I' ^ 0xff]);
+/// assert_eq!(cipher::otp::xor(&ciphertext, &[0x01, 0xff]).unwrap(), b"HI");
+/// ```
+pub fn xor(data: &[u8], key: &[u8]) -> Result<Vec<u8>> {
+    if key.len() < data.len() {
+        return Err(CipherError::InvalidInput(format!(
+            "The key is {} bytes but the input is {} bytes; a one-time pad must be at least as long as the input",
+            key.len(),
+            data.len()
+        )));
+    }
+    Ok(data.iter().zip(key).map(|(d, k)| d ^ k).collect())
+}
+
+/// 读取输入文件和密钥文件，把异或结果写到输出文件。
+pub fn apply(input_file: &str, key_file: &str, output_file: &str) -> Result<()> {
+    let data = fs::read(input_file)?;
+    let key = fs::read(key_file)?;
+    if key.len() > data.len() {
+        info!("Using the first {} of {} key bytes", data.len(), key.len());
+    }
+    fs::write(output_file, xor(&data, &key)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_otp_round_trip_random_bytes() {
+        use tempfile::NamedTempFile;
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut data = vec![0u8; 4096];
+        let mut key = vec![0u8; 4096];
+        rng.fill(&mut data[..]);
+        rng.fill(&mut key[..]);
+
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let key_file = NamedTempFile::new().expect("Failed to create temporary key file");
+        let key_path = key_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        fs::write(input_path, &data).unwrap();
+        fs::write(key_path, &key).unwrap();
+
+        apply(input_path, key_path, output_path).unwrap();
+        let ciphertext = fs::read(output_path).unwrap();
+        assert_eq!(ciphertext.len(), data.len());
+        assert_ne!(ciphertext, data);
+
+        apply(output_path, key_path, round_trip_path).unwrap();
+        assert_eq!(fs::read(round_trip_path).unwrap(), data);
+    }
+
+    #[test]
+    fn test_otp_rejects_short_key() {
+        let err = xor(b"ATTACK", b"KEY").unwrap_err();
+        assert!(matches!(err, CipherError::InvalidInput(_)), "{err}");
+        // 密钥较长时只用开头的部分
+        assert_eq!(xor(b"AB", b"\x00\x00\x01").unwrap(), b"AB");
+    }
+}
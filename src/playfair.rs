@@ -0,0 +1,171 @@
+use crate::cipher::{Cipher, CipherBase};
+use crate::square::{ALT_FILLER, FILLER, KeySquare, locate};
+use std::io;
+
+/// 普莱费尔密码：用关键词生成的5x5方阵（J并入I）每次加密一对字母。
+/// 同行的字母取右边一格，同列的取下面一格，否则取矩形的另外两个角。
+pub struct PlayfairCipher<'a> {
+    base: CipherBase<'a>,
+    square: KeySquare,
+    decrypt: bool,
+}
+
+impl<'a> PlayfairCipher<'a> {
+    pub fn new(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        keyword: &str,
+        decrypt: bool,
+    ) -> io::Result<Self> {
+        Ok(PlayfairCipher {
+            base: CipherBase::new(alphabet, input_file, output_file),
+            square: KeySquare::new(alphabet, keyword, 5)?,
+            decrypt,
+        })
+    }
+
+    /// 把明文整理成字母对：J换成I，去掉方阵以外的字符。
+    /// 一对中两个字母相同时在中间插入X（字母本身是X时插入Q），长度为奇数时同样在末尾补X或Q。
+    fn plaintext_digraphs(&self, text: &str) -> Vec<char> {
+        let mut letters = Vec::new();
+        for ch in text.chars().map(|c| self.square.normalize(c)) {
+            if self.square.position(ch).is_none() {
+                continue;
+            }
+            if letters.len() % 2 == 1 && letters.last() == Some(&ch) {
+                letters.push(Self::filler(ch));
+            }
+            letters.push(ch);
+        }
+        if letters.len() % 2 == 1 {
+            let last = letters[letters.len() - 1];
+            letters.push(Self::filler(last));
+        }
+        letters
+    }
+
+    /// 插在 `ch` 之后的填充字母。
+    fn filler(ch: char) -> char {
+        if ch == FILLER { ALT_FILLER } else { FILLER }
+    }
+
+    /// 加密一对字母；`step` 为1时加密，为4（即方阵边长减1）时解密。
+    fn transform_pair(&self, first: char, second: char, step: usize) -> (char, char) {
+        let (r1, c1) = locate(&self.square, first);
+        let (r2, c2) = locate(&self.square, second);
+        if r1 == r2 {
+            (
+                self.square.at(r1, (c1 + step) % 5),
+                self.square.at(r2, (c2 + step) % 5),
+            )
+        } else if c1 == c2 {
+            (
+                self.square.at((r1 + step) % 5, c1),
+                self.square.at((r2 + step) % 5, c2),
+            )
+        } else {
+            (self.square.at(r1, c2), self.square.at(r2, c1))
+        }
+    }
+
+    /// 在内存中加密（或按构造时的设置解密）一段文本。
+    /// 解密时不会去掉加密时插入的填充字母，因为无法区分填充和原文中的X。
+    pub fn encrypt_string(&self, text: &str) -> String {
+        let (letters, step) = if self.decrypt {
+            (self.square.digraphs(text), 4)
+        } else {
+            (self.plaintext_digraphs(text), 1)
+        };
+        letters
+            .chunks(2)
+            .flat_map(|pair| {
+                let (a, b) = self.transform_pair(pair[0], pair[1], step);
+                [a, b]
+            })
+            .collect()
+    }
+
+    pub fn base(&self) -> &CipherBase<'a> {
+        &self.base
+    }
+
+    pub fn base_mut(&mut self) -> &mut CipherBase<'a> {
+        &mut self.base
+    }
+
+    pub fn encrypt(&mut self) -> io::Result<()> {
+        self.base.get_text()?;
+        self.base.clean_text();
+
+        self.base.encrypted_text = self.encrypt_string(&self.base.plain_text);
+
+        self.base.save_file()
+    }
+}
+
+impl Cipher for PlayfairCipher<'_> {
+    fn encrypt(&mut self) -> io::Result<()> {
+        let decrypt = std::mem::replace(&mut self.decrypt, false);
+        let result = PlayfairCipher::encrypt(self);
+        self.decrypt = decrypt;
+        result
+    }
+
+    fn decrypt(&mut self) -> io::Result<()> {
+        let decrypt = std::mem::replace(&mut self.decrypt, true);
+        let result = PlayfairCipher::encrypt(self);
+        self.decrypt = decrypt;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    #[test]
+    fn test_playfair_published_example() {
+        // 维基百科的例子：TREE 中的 EE 被拆开，成为 TR EX ES
+        let encrypt = PlayfairCipher::new(ALPHABET, "", "", "playfair example", false).unwrap();
+        let decrypt = PlayfairCipher::new(ALPHABET, "", "", "playfair example", true).unwrap();
+        let ciphertext = encrypt.encrypt_string("HIDETHEGOLDINTHETREESTUMP");
+        assert_eq!(ciphertext, "BMODZBXDNABEKUDMUIXMMOUVIF");
+        assert_eq!(
+            decrypt.encrypt_string(&ciphertext),
+            "HIDETHEGOLDINTHETREXESTUMP"
+        );
+    }
+
+    #[test]
+    fn test_playfair_digraph_preparation() {
+        let cipher = PlayfairCipher::new(ALPHABET, "", "", "MONARCHY", false).unwrap();
+        let prepared = |text: &str| -> String { cipher.plaintext_digraphs(text).iter().collect() };
+        // 只拆开同一对中的重复字母，跨对的重复不受影响
+        assert_eq!(prepared("BALLOON"), "BALXLOON");
+        assert_eq!(prepared("ABBA"), "ABBA");
+        // 奇数长度补X，末尾是X时补Q；XX 之间插入Q
+        assert_eq!(prepared("CAT"), "CATX");
+        assert_eq!(prepared("BOX"), "BOXQ");
+        assert_eq!(prepared("XX"), "XQXQ");
+        // J并入I，II 与 JI 一样被拆开；方阵以外的字符被忽略
+        assert_eq!(prepared("JIG"), "IXIG");
+        assert_eq!(prepared("1-2 3"), "");
+    }
+
+    #[test]
+    fn test_playfair_round_trip() {
+        let encrypt = PlayfairCipher::new(ALPHABET, "", "", "MONARCHY", false).unwrap();
+        let decrypt = PlayfairCipher::new(ALPHABET, "", "", "MONARCHY", true).unwrap();
+        // 同行、同列、矩形三种情况都会出现
+        for plaintext in ["INSTRUMENTS", "ARMOURY", "JUSTATEST", "WXYZ"] {
+            let ciphertext = encrypt.encrypt_string(plaintext);
+            let expected: String = encrypt.plaintext_digraphs(plaintext).iter().collect();
+            assert_eq!(decrypt.encrypt_string(&ciphertext), expected);
+        }
+        // 常见教材中 MONARCHY 方阵的例子，奇数长度在末尾补X
+        assert_eq!(encrypt.encrypt_string("INSTRUMENTS"), "GATLMZCLRQXA");
+    }
+}
@@ -1,64 +1,585 @@
-use crate::cipher::Cipher;
+use crate::analysis::Scoring;
+use crate::cipher::{Cipher, CipherBase, char_to_index};
+use crate::error::{CipherError, Result};
+use pbkdf2::pbkdf2_hmac;
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+
+/// 口令派生密钥时使用的固定盐值与迭代次数。
+const KDF_SALT: &[u8] = b"cipher-polyalphabetic";
+const KDF_ROUNDS: u32 = 10_000;
+
+/// 通过 PBKDF2-HMAC-SHA256 把口令派生成一个指定长度、由字母表字符组成的关键词。
+/// 相同的口令和长度总会得到相同的关键词。
+///
+/// 每个字符直接取派生结果中的8个字节模字母表长度，不经过随机数生成器，
+/// 所以结果不会随 `rand` 的版本变化。
+pub fn derive_keyword(alphabet: &str, passphrase: &str, length: usize) -> String {
+    let mut derived = vec![0u8; length * 8];
+    pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), KDF_SALT, KDF_ROUNDS, &mut derived);
+
+    let letters: Vec<char> = alphabet.chars().collect();
+    derived
+        .chunks_exact(8)
+        .map(|chunk| {
+            let value = u64::from_be_bytes(chunk.try_into().expect("Chunk is 8 bytes"));
+            letters[(value % letters.len() as u64) as usize]
+        })
+        .collect()
+}
+
+/// 检查关键词是否非空，且每个字符都在字母表中（不区分大小写）。
+pub fn validate_keyword(alphabet: &str, keyword: &str) -> Result<()> {
+    if keyword.is_empty() {
+        return Err(CipherError::InvalidInput(
+            "The keyword must not be empty".to_string(),
+        ));
+    }
+    match keyword
+        .chars()
+        .find(|&ch| char_to_index(alphabet, ch).is_none())
+    {
+        Some(ch) => Err(CipherError::InvalidKeyword(ch)),
+        None => Ok(()),
+    }
+}
+
+/// 用给定的关键词解密一段文本，不需要输入输出文件。字母表之外的字符会被去掉。
+/// 关键词无效时返回 `validate_keyword` 的错误。
+///
+/// ```
+/// let plaintext =
+///     cipher::polyalphabetic::decrypt("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "LMIYFSRV", "CAT").unwrap();
+/// assert_eq!(plaintext, "ILOVEYOU");
+/// ```
+pub fn decrypt(alphabet: &str, text: &str, keyword: &str) -> Result<String> {
+    let cipher = PolyalphabeticCipher::new(alphabet, "", "", keyword, true)?;
+    Ok(cipher.encrypt_string(&cipher.base().clean(text)))
+}
+
+/// 把关键词换算成各位置的偏移量，关键词无效时报告是哪个字符。
+fn keyword_to_key(alphabet: &str, keyword: &str) -> Result<Vec<i32>> {
+    validate_keyword(alphabet, keyword)?;
+    Ok(keyword
+        .chars()
+        .filter_map(|ch| char_to_index(alphabet, ch))
+        .map(|idx| idx as i32 + 1)
+        .collect())
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn lcm(a: usize, b: usize) -> usize {
+    a / gcd(a, b) * b
+}
+
+/// 字典攻击得到的候选结果。
+pub struct KeywordCandidate {
+    pub keyword: String,
+    pub plaintext: String,
+    pub score: f64,
+}
+
+/// 逐行读取字典，把每个单词当作关键词解密密文，返回按 `scoring` 得分最低（最像英文）的候选。
+/// 字典按行流式读取，不会一次性载入内存；含有字母表以外字符的单词会被跳过。
+/// 得分相同时保留字典中先出现的单词。
+pub fn crack_with_wordlist<R: BufRead>(
+    alphabet: &str,
+    ciphertext: &str,
+    wordlist: R,
+    scoring: Scoring,
+) -> Result<Option<KeywordCandidate>> {
+    let mut best: Option<KeywordCandidate> = None;
+
+    for line in wordlist.lines() {
+        let word = line?.trim().to_uppercase();
+        if word.is_empty() || !word.chars().all(|c| alphabet.contains(c)) {
+            continue;
+        }
+
+        let cipher = PolyalphabeticCipher::new(alphabet, "", "", &word, true)?;
+        let plaintext = cipher.encrypt_string(ciphertext);
+        let score = scoring.score(&plaintext);
+        if best.as_ref().is_none_or(|b| score < b.score) {
+            best = Some(KeywordCandidate {
+                keyword: word,
+                plaintext,
+                score,
+            });
+        }
+    }
+
+    Ok(best)
+}
+
+/// 密钥的使用方式。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyMode {
+    /// 关键词循环使用。
+    Repeating,
+    /// 滚动密钥：密钥取自一段与明文至少等长的文本，只用到明文长度为止，不循环。
+    Running,
+    /// 自动密钥：关键词用完之后，密钥由明文本身接续。解密时用已恢复的明文重建密钥流。
+    Autokey,
+    /// 博福特密码：关键词循环使用，密文是密钥字母减去明文字母，加密和解密是同一个变换。
+    Beaufort,
+}
+
+/// 分块加密时在块之间保留的密钥位置。
+struct KeyCursor {
+    /// 已经加密的字母数。
+    position: usize,
+    /// 自动密钥模式下接下来依次使用的偏移量：先是关键词，随后是已处理的明文字母。
+    autokey: VecDeque<i32>,
+}
 
 pub struct PolyalphabeticCipher<'a> {
-    base: Cipher<'a>,
+    base: CipherBase<'a>,
     key: Vec<i32>,
+    key_mode: KeyMode,
     decrypt: bool,
+    /// 关键词中表示不偏移的符号，写进解密说明。
+    skip_symbol: Option<char>,
+    /// 用 `add_keyword` 叠加的关键词个数，写进解密说明。
+    product_keywords: usize,
 }
 
 impl<'a> PolyalphabeticCipher<'a> {
+    /// 关键词为空或含有字母表之外的字符时返回错误，并指出是哪个字符。
     pub fn new(
         alphabet: &'a str,
         input_file: &'a str,
         output_file: &'a str,
         keyword: &str,
         decrypt: bool,
-    ) -> Self {
+    ) -> Result<Self> {
+        let key = keyword_to_key(alphabet, keyword)?;
+
+        Ok(PolyalphabeticCipher {
+            base: CipherBase::new(alphabet, input_file, output_file),
+            key,
+            key_mode: KeyMode::Repeating,
+            decrypt,
+            skip_symbol: None,
+            product_keywords: 0,
+        })
+    }
+
+    /// 与 `new` 相同，但关键词中的 `skip_symbol` 表示该位置不偏移，用于构造稀疏密钥。
+    /// `skip_symbol` 不能是字母表中的字符，否则无法与普通关键词字符区分。
+    pub fn with_skip_symbol(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        keyword: &str,
+        decrypt: bool,
+        skip_symbol: char,
+    ) -> Result<Self> {
+        if alphabet.contains(skip_symbol) {
+            return Err(CipherError::InvalidInput(format!(
+                "Skip symbol '{skip_symbol}' must not be in the alphabet"
+            )));
+        }
+        if keyword.is_empty() {
+            return Err(CipherError::InvalidInput(
+                "The keyword must not be empty".to_string(),
+            ));
+        }
+
         let key = keyword
             .chars()
-            .map(|ch| alphabet.find(ch).unwrap() as i32 + 1)
+            .map(|ch| {
+                if ch == skip_symbol {
+                    Ok(0)
+                } else {
+                    char_to_index(alphabet, ch)
+                        .map(|idx| idx as i32 + 1)
+                        .ok_or(CipherError::InvalidKeyword(ch))
+                }
+            })
+            .collect::<Result<Vec<i32>>>()?;
+
+        Ok(PolyalphabeticCipher {
+            base: CipherBase::new(alphabet, input_file, output_file),
+            key,
+            key_mode: KeyMode::Repeating,
+            decrypt,
+            skip_symbol: Some(skip_symbol),
+            product_keywords: 0,
+        })
+    }
+
+    /// 用一段文本作为滚动密钥，例如一本书的片段。文本中不属于字母表的字符被忽略，
+    /// 字母不区分大小写。
+    pub fn with_running_key(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        key_text: &str,
+        decrypt: bool,
+    ) -> Result<Self> {
+        let key: Vec<i32> = key_text
+            .chars()
+            .filter_map(|ch| char_to_index(alphabet, ch))
+            .map(|idx| idx as i32 + 1)
             .collect();
+        if key.is_empty() {
+            return Err(CipherError::InvalidInput(
+                "The running key contains no alphabet characters".to_string(),
+            ));
+        }
 
-        PolyalphabeticCipher {
-            base: Cipher::new(alphabet, input_file, output_file),
+        Ok(PolyalphabeticCipher {
+            base: CipherBase::new(alphabet, input_file, output_file),
             key,
+            key_mode: KeyMode::Running,
             decrypt,
+            skip_symbol: None,
+            product_keywords: 0,
+        })
+    }
+
+    /// 自动密钥模式：先用关键词，之后依次用明文的每个字母作为密钥。
+    pub fn with_autokey(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        keyword: &str,
+        decrypt: bool,
+    ) -> Result<Self> {
+        let mut cipher = Self::new(alphabet, input_file, output_file, keyword, decrypt)?;
+        cipher.key_mode = KeyMode::Autokey;
+        Ok(cipher)
+    }
+
+    /// 博福特模式：密文字母的序号是密钥字母的序号减去明文字母的序号，再对字母表长度取模。
+    /// 这个变换是对合的，加密两次还原明文，所以不区分加密和解密。
+    pub fn with_beaufort(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        keyword: &str,
+    ) -> Result<Self> {
+        let mut cipher = Self::new(alphabet, input_file, output_file, keyword, false)?;
+        cipher.key_mode = KeyMode::Beaufort;
+        Ok(cipher)
+    }
+
+    /// 滚动密钥模式下检查密钥是否足够加密 `len` 个字符，不够时说明还差多少。
+    pub fn check_key_length(&self, len: usize) -> Result<()> {
+        if self.key_mode == KeyMode::Running && self.key.len() < len {
+            return Err(CipherError::InvalidInput(format!(
+                "The running key is too short: {} more characters needed",
+                len - self.key.len()
+            )));
         }
+        Ok(())
     }
 
-    fn encrypt_char(
-        &self,
-        alphabet: &str,
-        ch: char,
-        key: &Vec<i32>,
-        idx: usize,
-        sign: i32,
-    ) -> char {
-        let shift = key[idx % key.len()] * sign;
-        let alphabet_len = alphabet.len() as i32;
-        let idx = alphabet.find(ch).unwrap() as i32;
-        let new_idx = self.base.change_index(alphabet_len, idx, shift);
-        alphabet.chars().nth(new_idx).expect("Index out of range")
+    /// 与 `encrypt_string` 相同，但滚动密钥不够长时返回错误而不是循环使用。
+    pub fn try_encrypt_string(&self, plaintext: &str) -> Result<String> {
+        let len = plaintext
+            .chars()
+            .filter(|&ch| self.base.alphabet.contains(ch))
+            .count();
+        self.check_key_length(len)?;
+        Ok(self.encrypt_string(plaintext))
+    }
+
+    /// 按偏移量移动单个字符。字母表之外的字符原样返回。
+    fn shift_char(&self, ch: char, shift: i32) -> char {
+        let Some(ch_idx) = self.base.index_of(ch) else {
+            return ch;
+        };
+        let alphabet_len = self.base.letters.len() as i32;
+        let new_idx = self.base.change_index(alphabet_len, ch_idx as i32, shift);
+        self.base.letter_at(new_idx)
+    }
+
+    /// 加密是否与解密相同。只有每个位置的偏移量都是0或字母表长度的一半时才成立。
+    /// 自动密钥的偏移量来自明文，不考虑这种情况。
+    pub fn is_reciprocal(&self) -> bool {
+        let alphabet_len = self.base.letters.len() as i32;
+        if self.key_mode == KeyMode::Beaufort {
+            return true;
+        }
+        self.key_mode != KeyMode::Autokey
+            && self
+                .key
+                .iter()
+                .all(|shift| (2 * shift).rem_euclid(alphabet_len) == 0)
+    }
+
+    /// 密钥的指纹：对每个位置的偏移量做SHA-256，取前3个字节的十六进制。
+    /// 指纹可以随密文公开，用来确认解密方使用的是同一个密钥。
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        for shift in &self.key {
+            hasher.update(shift.to_le_bytes());
+        }
+        hasher.finalize()[..3]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// 在输出的元数据中写入密钥指纹。
+    pub fn embed_fingerprint(&mut self) {
+        let fingerprint = self.fingerprint();
+        self.base
+            .metadata
+            .push(("fingerprint".to_string(), fingerprint));
+    }
+
+    /// 解密时，如果输入带有密钥指纹，检查它与当前密钥的指纹是否一致。
+    fn verify_fingerprint(&self) -> Result<()> {
+        match self.base.input_metadata_value("fingerprint") {
+            Some(expected)
+                if self.decrypt && !expected.eq_ignore_ascii_case(&self.fingerprint()) =>
+            {
+                Err(CipherError::InvalidInput(format!(
+                    "Key fingerprint mismatch: the input was encrypted with key {expected}, this key is {}",
+                    self.fingerprint()
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// 叠加一个关键词，构成乘积密码：两次偏移相加，等价于一个长度为两者最小公倍数的密钥。
+    pub fn add_keyword(&mut self, keyword: &str) -> Result<()> {
+        let other = keyword_to_key(self.base.alphabet, keyword)?;
+        if self.key_mode != KeyMode::Repeating {
+            return Err(CipherError::InvalidInput(format!(
+                "{:?} keys cannot be combined with another keyword",
+                self.key_mode
+            )));
+        }
+        let period = lcm(self.key.len(), other.len());
+        self.key = (0..period)
+            .map(|i| self.key[i % self.key.len()] + other[i % other.len()])
+            .collect();
+        self.product_keywords += 1;
+        Ok(())
+    }
+
+    /// 密钥重复的周期，即密钥长度；叠加多个关键词时是各关键词长度的最小公倍数。
+    /// 滚动密钥和自动密钥不循环，返回的是密钥文本或关键词的长度。
+    pub fn period(&self) -> usize {
+        self.key.len()
+    }
+
+    /// 给收件人的解密说明：使用的子命令、公开的参数，以及需要另外获得的秘密。
+    pub fn recipe(&self) -> String {
+        let (key_arg, secret) = match self.key_mode {
+            KeyMode::Repeating => ("-k <keyword>", "the keyword"),
+            KeyMode::Running => ("--running_key_file <file>", "the running key text"),
+            KeyMode::Autokey => ("-k <keyword> --mode autokey", "the keyword"),
+            KeyMode::Beaufort => ("-k <keyword> --mode beaufort", "the keyword"),
+        };
+        let mut command = format!(
+            "cipher poly -i {} -o <plaintext> {key_arg}",
+            self.base.output_file
+        );
+        if let Some(skip_symbol) = self.skip_symbol {
+            command.push_str(&format!(" --skip_symbol '{skip_symbol}'"));
+        }
+        for i in 0..self.product_keywords {
+            command.push_str(&format!(" --product_keyword <keyword{}>", i + 2));
+        }
+        let secret = if self.product_keywords > 0 {
+            "all of the keywords, which are"
+        } else {
+            &format!("{secret}, which is")
+        };
+        if !self.decrypt && self.key_mode != KeyMode::Beaufort {
+            command.push_str(" -d");
+        }
+        if self.base.stride > 1 {
+            command.push_str(&format!(" --stride {}", self.base.stride));
+        }
+        if self.base.invert {
+            command.push_str(" --invert");
+        }
+        format!("To decrypt, run:\n  {command}\nYou also need {secret} not included here.\n")
+    }
+
+    /// 展开加密已清理的文本 `text` 时的完整密钥流，即每个字母实际使用的偏移量。
+    /// 自动密钥的密钥流在关键词之后接着明文字母，解密时 `text` 是密文，先解出明文。
+    pub fn materialize_key(&self, text: &str) -> Vec<i32> {
+        let len = self.base.count_letters(text);
+        if self.key_mode != KeyMode::Autokey {
+            return (0..len).map(|i| self.key[i % self.key.len()]).collect();
+        }
+        let plaintext = if self.decrypt {
+            self.encrypt_string(text)
+        } else {
+            text.to_string()
+        };
+        let plain_shifts = plaintext
+            .chars()
+            .filter_map(|ch| self.base.index_of(ch))
+            .map(|index| index as i32 + 1);
+        self.key
+            .iter()
+            .copied()
+            .chain(plain_shifts)
+            .take(len)
+            .collect()
+    }
+
+    pub fn base(&self) -> &CipherBase<'a> {
+        &self.base
+    }
+
+    pub fn base_mut(&mut self) -> &mut CipherBase<'a> {
+        &mut self.base
+    }
+
+    /// 在内存中加密（或按构造时的设置解密）一段已清理的文本，密钥从文本开头重新计数。
+    pub fn encrypt_string(&self, plaintext: &str) -> String {
+        self.encrypt_from(plaintext, &mut self.key_cursor())
+    }
+
+    /// 从文本开头开始的密钥位置。
+    fn key_cursor(&self) -> KeyCursor {
+        KeyCursor {
+            position: 0,
+            autokey: self.key.iter().copied().collect(),
+        }
+    }
+
+    /// 从 `cursor` 记录的密钥位置继续加密一段文本，并把位置推进到文本之后，
+    /// 所以连续调用相当于加密一段连续的文本。
+    fn encrypt_from(&self, text: &str, cursor: &mut KeyCursor) -> String {
+        if self.key_mode == KeyMode::Autokey {
+            return self.autokey_string(text, cursor);
+        }
+        // 密钥位置只按字母表中的字符计数，保留下来的空格和标点不消耗密钥
+        let encrypted = self
+            .base
+            .map_letters(text, cursor.position, |ch, i| self.encrypt_letter(ch, i));
+        cursor.position += self.base.count_letters(text);
+        encrypted
+    }
+
+    /// 加密第 `i` 个字母，只取决于它的位置，因此可以并行处理。
+    fn encrypt_letter(&self, ch: char, i: usize) -> char {
+        let shift = self.key[i % self.key.len()];
+        if !self.base.in_stride(i) {
+            ch
+        } else if self.key_mode == KeyMode::Beaufort {
+            self.beaufort_char(ch, shift)
+        } else if self.decrypt {
+            self.shift_char(ch, -shift)
+        } else {
+            self.shift_char(ch, shift)
+        }
+    }
+
+    /// 博福特变换单个字符。密钥中存的偏移量是字母序号加1，这里换回字母序号。
+    fn beaufort_char(&self, ch: char, shift: i32) -> char {
+        let Some(ch_idx) = self.base.index_of(ch) else {
+            return ch;
+        };
+        let alphabet_len = self.base.letters.len() as i32;
+        let new_idx = self
+            .base
+            .change_index(alphabet_len, shift - 1, -(ch_idx as i32));
+        self.base.letter_at(new_idx)
+    }
+
+    /// 自动密钥模式的加解密。关键词之后的第 `i` 个偏移量来自明文中第 `i` 个字母，
+    /// 加密时明文已知，解密时逐个使用刚恢复出来的明文。
+    fn autokey_string(&self, text: &str, cursor: &mut KeyCursor) -> String {
+        let sign = if self.decrypt { -1 } else { 1 };
+        text.chars()
+            .map(|ch| {
+                if self.base.index_of(ch).is_none() {
+                    return ch;
+                }
+                let i = cursor.position;
+                cursor.position += 1;
+                let shift = cursor
+                    .autokey
+                    .pop_front()
+                    .expect("One pending shift per letter");
+                let out = if self.base.in_stride(i) {
+                    self.shift_char(ch, shift * sign)
+                } else {
+                    ch
+                };
+                let plain = if self.decrypt { out } else { ch };
+                let plain_idx = self.base.index_of(plain).expect("Checked above");
+                cursor.autokey.push_back(plain_idx as i32 + 1);
+                out
+            })
+            .collect()
+    }
+
+    /// 分块读取、加密和写出输入，密钥位置在块之间延续。
+    fn stream(&mut self) -> std::io::Result<()> {
+        let mut stream = self.base.open_stream()?;
+        self.verify_fingerprint()?;
+        let mut cursor = self.key_cursor();
+        while let Some(chunk) = self.base.next_chunk(&mut stream)? {
+            self.check_key_length(cursor.position + self.base.count_letters(&chunk))?;
+            let encrypted = self.encrypt_from(&chunk, &mut cursor);
+            self.base.write_chunk(&mut stream, &encrypted)?;
+        }
+        stream.finish()
     }
 
     pub fn encrypt(&mut self) -> std::io::Result<()> {
+        if self.base.stream {
+            return self.stream();
+        }
+        if self.base.json_mode {
+            if self.key_mode == KeyMode::Running {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Running keys cannot be used with JSON input",
+                ));
+            }
+            let value = self.base.read_json()?;
+            self.verify_fingerprint()?;
+            self.base.encrypted_text = self
+                .base
+                .transform_json_input(value, |text| self.encrypt_string(text));
+            return self.base.save_file();
+        }
+
         self.base.get_text()?;
+        self.verify_fingerprint()?;
         self.base.clean_text();
 
-        let sign = if self.decrypt { -1 } else { 1 };
-
-        self.base.encrypted_text = self
-            .base
-            .plain_text
-            .chars()
-            .enumerate()
-            .map(|(i, ch)| self.encrypt_char(&self.base.alphabet, ch, &self.key, i, sign))
-            .collect();
+        self.base.encrypted_text = self.try_encrypt_string(&self.base.plain_text)?;
+        self.base.assert_length_preserved();
 
         self.base.save_file()
     }
 }
 
+/// `encrypt` 总是加密、`decrypt` 总是解密，与构造时给出的方向无关，调用后恢复原来的方向。
+impl Cipher for PolyalphabeticCipher<'_> {
+    fn encrypt(&mut self) -> std::io::Result<()> {
+        let decrypt = std::mem::replace(&mut self.decrypt, false);
+        let result = PolyalphabeticCipher::encrypt(self);
+        self.decrypt = decrypt;
+        result
+    }
+
+    fn decrypt(&mut self) -> std::io::Result<()> {
+        let decrypt = std::mem::replace(&mut self.decrypt, true);
+        let result = PolyalphabeticCipher::encrypt(self);
+        self.decrypt = decrypt;
+        result
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -85,11 +606,505 @@ mod tests {
             output_path.to_str().expect("Invalid output path"),
             keyword,
             false,
-        );
+        )
+        .unwrap();
         cipher.encrypt().expect("Encryption failed");
 
         let encrypted_content = read_to_string(output_path).expect("Failed to read output file");
         let expected_encrypted_content = "LMIYFSRV";
         assert_eq!(encrypted_content.trim(), expected_encrypted_content);
     }
+
+    #[test]
+    fn test_derive_keyword() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+        let first = derive_keyword(alphabet, "correct horse battery staple", 12);
+        let second = derive_keyword(alphabet, "correct horse battery staple", 12);
+        assert_eq!(first, second);
+        assert_eq!(first.chars().count(), 12);
+        assert!(first.chars().all(|c| alphabet.contains(c)));
+
+        let other = derive_keyword(alphabet, "correct horse battery stapler", 12);
+        assert_ne!(first, other);
+        // 固定的期望值：派生算法变化会改变已有口令对应的关键词
+        assert_eq!(first, "NQNECBYIJUTX");
+    }
+
+    #[test]
+    fn test_materialize_key() {
+        let cipher = PolyalphabeticCipher::new(
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+            "input.txt",
+            "output.txt",
+            "CAT",
+            false,
+        )
+        .unwrap();
+        // C=3, A=1, T=20，按关键词长度循环
+        assert_eq!(
+            cipher.materialize_key("AAAAAAA"),
+            vec![3, 1, 20, 3, 1, 20, 3]
+        );
+        assert!(cipher.materialize_key("").is_empty());
+    }
+
+    #[test]
+    fn test_crack_with_wordlist() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let plaintext = "ITWASTHEBESTOFTIMESITWASTHEWORSTOFTIMESITWASTHEAGEOFWISDOM";
+        let ciphertext = PolyalphabeticCipher::new(alphabet, "", "", "LEMON", false)
+            .unwrap()
+            .encrypt_string(plaintext);
+
+        let wordlist = io::Cursor::new("apple\nlemon\n\nkiwi-fruit\norange\n");
+        let best = crack_with_wordlist(alphabet, &ciphertext, wordlist, Scoring::ChiSquared)
+            .unwrap()
+            .expect("A candidate should be found");
+        assert_eq!(best.keyword, "LEMON");
+        assert_eq!(best.plaintext, plaintext);
+    }
+
+    #[test]
+    fn test_crack_with_wordlist_tie_keeps_first_word() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let ciphertext = PolyalphabeticCipher::new(alphabet, "", "", "LEMON", false)
+            .unwrap()
+            .encrypt_string("ATTACKATDAWN");
+
+        // 两个关键词解密结果相同，得分也相同，取先出现的
+        for (wordlist, expected) in [
+            ("lemonlemon\nlemon\n", "LEMONLEMON"),
+            ("lemon\nlemonlemon\n", "LEMON"),
+        ] {
+            let best = crack_with_wordlist(
+                alphabet,
+                &ciphertext,
+                io::Cursor::new(wordlist),
+                Scoring::Trigram,
+            )
+            .unwrap()
+            .unwrap();
+            assert_eq!(best.keyword, expected);
+        }
+    }
+
+    #[test]
+    fn test_polyalphabetic_cipher_preserves_length() {
+        let cipher =
+            PolyalphabeticCipher::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ", "", "", "LEMON", false)
+                .unwrap();
+        for plaintext in ["", "A", "HELLOWORLD", "ZZZZZZZZZZZZZZZZZZZZ"] {
+            assert_eq!(
+                cipher.encrypt_string(plaintext).chars().count(),
+                plaintext.chars().count()
+            );
+        }
+    }
+
+    #[test]
+    fn test_skip_symbol() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let cipher =
+            PolyalphabeticCipher::with_skip_symbol(alphabet, "", "", "B*C*", false, '*').unwrap();
+        assert_eq!(cipher.materialize_key("AAAA"), vec![2, 0, 3, 0]);
+        // 第2、4位对应跳过符号，字母保持不变
+        assert_eq!(cipher.encrypt_string("AAAAAA"), "CADACA");
+
+        assert!(
+            PolyalphabeticCipher::with_skip_symbol(alphabet, "", "", "BAC", false, 'A').is_err()
+        );
+        // 与 `new` 一样拒绝空关键词，否则密钥长度为0
+        assert!(matches!(
+            PolyalphabeticCipher::with_skip_symbol(alphabet, "", "", "", false, '*'),
+            Err(CipherError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_keyword_is_an_error() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        // 空格不在字母表中，应返回错误而不是panic，并指出是哪个字符
+        let err = PolyalphabeticCipher::new(alphabet, "", "", "my cat", false)
+            .err()
+            .expect("Keyword with a space should be rejected");
+        assert!(matches!(err, CipherError::InvalidKeyword(' ')), "{err}");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("' '"), "{err}");
+
+        assert!(matches!(
+            PolyalphabeticCipher::new(alphabet, "", "", "", false),
+            Err(CipherError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            decrypt(alphabet, "LMIYFSRV", "C4T"),
+            Err(CipherError::InvalidKeyword('4'))
+        ));
+        assert!(matches!(
+            PolyalphabeticCipher::with_skip_symbol(alphabet, "", "", "C?T!", false, '?'),
+            Err(CipherError::InvalidKeyword('!'))
+        ));
+    }
+
+    #[test]
+    fn test_autokey_round_trip() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let plaintext = "ATTACKATDAWNANDHOLDTHEBRIDGE";
+        let encrypt = PolyalphabeticCipher::with_autokey(alphabet, "", "", "QUEEN", false).unwrap();
+        let decrypt = PolyalphabeticCipher::with_autokey(alphabet, "", "", "QUEEN", true).unwrap();
+        let ciphertext = encrypt.encrypt_string(plaintext);
+        assert_eq!(decrypt.encrypt_string(&ciphertext), plaintext);
+
+        // 关键词部分与普通模式相同，之后由明文接续密钥
+        let repeating = PolyalphabeticCipher::new(alphabet, "", "", "QUEEN", false)
+            .unwrap()
+            .encrypt_string(plaintext);
+        assert_eq!(ciphertext[..5], repeating[..5]);
+        assert_ne!(ciphertext[5..], repeating[5..]);
+        // 第6个字母K的密钥是明文第1个字母A，偏移量为1
+        assert_eq!(&ciphertext[5..6], "L");
+    }
+
+    #[test]
+    fn test_materialize_autokey() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let encrypt = PolyalphabeticCipher::with_autokey(alphabet, "", "", "CAT", false).unwrap();
+        let decrypt = PolyalphabeticCipher::with_autokey(alphabet, "", "", "CAT", true).unwrap();
+        // 关键词C、A、T之后接着明文H、E、L、L
+        let expected = vec![3, 1, 20, 8, 5, 12, 12];
+        assert_eq!(encrypt.materialize_key("HELLOXY"), expected);
+        // 解密时从密文恢复出同样的密钥流
+        let ciphertext = encrypt.encrypt_string("HELLOXY");
+        assert_eq!(decrypt.materialize_key(&ciphertext), expected);
+    }
+
+    #[test]
+    fn test_autokey_preserve_non_alpha_round_trip() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let plaintext = "MEET ME, AFTER THE TOGA PARTY.";
+        let encrypt = PolyalphabeticCipher::with_autokey(alphabet, "", "", "KEY", false).unwrap();
+        let decrypt = PolyalphabeticCipher::with_autokey(alphabet, "", "", "KEY", true).unwrap();
+        let ciphertext = encrypt.encrypt_string(plaintext);
+        assert_eq!(&ciphertext[4..5], " ");
+        assert_eq!(decrypt.encrypt_string(&ciphertext), plaintext);
+        assert!(!encrypt.is_reciprocal());
+    }
+
+    #[test]
+    fn test_running_key_lengths() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let plaintext = "ATTACK";
+
+        // 等长的密钥
+        let exact =
+            PolyalphabeticCipher::with_running_key(alphabet, "", "", "abcdef", false).unwrap();
+        let ciphertext = exact.try_encrypt_string(plaintext).unwrap();
+        assert_eq!(ciphertext, "BVWEHQ");
+
+        // 更长的密钥只用到明文长度，多余部分不影响结果
+        let longer =
+            PolyalphabeticCipher::with_running_key(alphabet, "", "", "ABC, DEF! GHIJKL", false)
+                .unwrap();
+        assert_eq!(longer.try_encrypt_string(plaintext).unwrap(), ciphertext);
+
+        let decrypt =
+            PolyalphabeticCipher::with_running_key(alphabet, "", "", "ABCDEFGH", true).unwrap();
+        assert_eq!(decrypt.try_encrypt_string(&ciphertext).unwrap(), plaintext);
+
+        // 更短的密钥报错，并说明还差几个字符
+        let shorter =
+            PolyalphabeticCipher::with_running_key(alphabet, "", "", "ABCD", false).unwrap();
+        let error = shorter.try_encrypt_string(plaintext).unwrap_err();
+        assert!(error.to_string().contains("2 more characters"));
+    }
+
+    #[test]
+    fn test_preserve_non_alpha_round_trip() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        std::fs::write(input_path, "ATTACK AT DAWN!").unwrap();
+
+        let mut cipher =
+            PolyalphabeticCipher::new(alphabet, input_path, output_path, "LEMON", false).unwrap();
+        cipher.base_mut().preserve_non_alpha = true;
+        cipher.encrypt().unwrap();
+        // 去掉空格和标点后与不保留时的密文一致
+        let plain = PolyalphabeticCipher::new(alphabet, "", "", "LEMON", false).unwrap();
+        let expected: String = plain.encrypt_string("ATTACKATDAWN");
+        let ciphertext = read_to_string(output_path).unwrap();
+        assert_eq!(ciphertext.replace([' ', '!'], ""), expected);
+        assert_eq!(&ciphertext[6..7], " ");
+        assert!(ciphertext.ends_with('!'));
+
+        let mut cipher =
+            PolyalphabeticCipher::new(alphabet, output_path, round_trip_path, "LEMON", true)
+                .unwrap();
+        cipher.base_mut().preserve_non_alpha = true;
+        cipher.encrypt().unwrap();
+        assert_eq!(read_to_string(round_trip_path).unwrap(), "ATTACK AT DAWN!");
+    }
+
+    #[test]
+    fn test_poly_recipe() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let cipher = PolyalphabeticCipher::new(alphabet, "", "secret.txt", "CAT", false).unwrap();
+        let recipe = cipher.recipe();
+        assert!(recipe.contains("cipher poly -i secret.txt -o <plaintext> -k <keyword> -d"));
+
+        // 跳过符号和叠加的关键词都要写进说明，否则收件人无法解密
+        let mut cipher =
+            PolyalphabeticCipher::with_skip_symbol(alphabet, "", "secret.txt", "C*T", false, '*')
+                .unwrap();
+        cipher.add_keyword("LION").unwrap();
+        let recipe = cipher.recipe();
+        assert!(recipe.contains("--skip_symbol '*'"));
+        assert!(recipe.contains("--product_keyword <keyword2>"));
+        assert!(recipe.contains("all of the keywords, which are not included"));
+    }
+
+    #[test]
+    fn test_period_of_product_keywords() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let mut cipher = PolyalphabeticCipher::new(alphabet, "", "", "CAT", false).unwrap();
+        assert_eq!(cipher.period(), 3);
+        cipher.add_keyword("LION").unwrap();
+        assert_eq!(cipher.period(), 12);
+
+        // 叠加的效果与依次用两个关键词加密相同
+        let first = PolyalphabeticCipher::new(alphabet, "", "", "CAT", false).unwrap();
+        let second = PolyalphabeticCipher::new(alphabet, "", "", "LION", false).unwrap();
+        let plaintext = "ATTACKATDAWNTOMORROW";
+        assert_eq!(
+            cipher.encrypt_string(plaintext),
+            second.encrypt_string(&first.encrypt_string(plaintext))
+        );
+        assert!(cipher.add_keyword("L1ON").is_err());
+    }
+
+    #[test]
+    fn test_fingerprint_detects_wrong_keyword() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        std::fs::write(input_path, "ATTACKATDAWN").unwrap();
+
+        let mut cipher =
+            PolyalphabeticCipher::new(alphabet, input_path, output_path, "LEMON", false).unwrap();
+        assert_eq!(cipher.fingerprint().len(), 6);
+        cipher.embed_fingerprint();
+        cipher.encrypt().unwrap();
+
+        // 错误的关键词在解密前就被发现
+        let mut wrong =
+            PolyalphabeticCipher::new(alphabet, output_path, round_trip_path, "MELON", true)
+                .unwrap();
+        assert!(wrong.encrypt().is_err());
+
+        let mut right =
+            PolyalphabeticCipher::new(alphabet, output_path, round_trip_path, "lemon", true)
+                .unwrap();
+        right.encrypt().unwrap();
+        assert_eq!(read_to_string(round_trip_path).unwrap(), "ATTACKATDAWN");
+    }
+
+    #[test]
+    fn test_fingerprint_detects_wrong_keyword_in_json() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        std::fs::write(input_path, r#"{"text": "ATTACKATDAWN"}"#).unwrap();
+
+        let mut cipher =
+            PolyalphabeticCipher::new(alphabet, input_path, output_path, "LEMON", false).unwrap();
+        cipher.base.json_mode = true;
+        cipher.embed_fingerprint();
+        cipher.encrypt().unwrap();
+        assert!(
+            read_to_string(output_path)
+                .unwrap()
+                .contains("fingerprint=")
+        );
+
+        // JSON输入中的指纹同样在解密前检查，错误的关键词不会写出输出
+        let mut wrong =
+            PolyalphabeticCipher::new(alphabet, output_path, round_trip_path, "MELON", true)
+                .unwrap();
+        wrong.base.json_mode = true;
+        assert!(wrong.encrypt().is_err());
+        assert_eq!(read_to_string(round_trip_path).unwrap(), "");
+
+        let mut right =
+            PolyalphabeticCipher::new(alphabet, output_path, round_trip_path, "LEMON", true)
+                .unwrap();
+        right.base.json_mode = true;
+        right.encrypt().unwrap();
+        let value: serde_json::Value =
+            serde_json::from_str(&read_to_string(round_trip_path).unwrap()).unwrap();
+        assert_eq!(value["text"], "ATTACKATDAWN");
+    }
+
+    #[test]
+    fn test_beaufort_is_an_involution() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let cipher =
+            PolyalphabeticCipher::with_beaufort(alphabet, "", "", "FORTIFICATION").unwrap();
+        let plaintext = "DEFENDTHEEASTWALLOFTHECASTLE";
+        let ciphertext = cipher.encrypt_string(plaintext);
+        assert_eq!(ciphertext, "CKMPVCPVWPIWUJOGIUAPVWRIWUUK");
+        assert_eq!(cipher.encrypt_string(&ciphertext), plaintext);
+        assert!(cipher.is_reciprocal());
+
+        // 解密方向的设置不影响结果
+        let mut cipher = cipher;
+        cipher.decrypt = true;
+        assert_eq!(cipher.encrypt_string(plaintext), ciphertext);
+    }
+
+    #[test]
+    fn test_alphanumeric_alphabet() {
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        std::fs::write(input_path, "Attack at 0600!").unwrap();
+
+        // 36个字符的字母表：数字参与加密，不会被清理掉
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+        let mut cipher =
+            PolyalphabeticCipher::new(alphabet, input_path, output_path, "KEY9", false).unwrap();
+        cipher.encrypt().unwrap();
+        let ciphertext = read_to_string(output_path).unwrap();
+        assert_eq!(ciphertext.trim(), "LYIANPZTBBP0");
+
+        let mut cipher =
+            PolyalphabeticCipher::new(alphabet, output_path, round_trip_path, "KEY9", true)
+                .unwrap();
+        cipher.encrypt().unwrap();
+        assert_eq!(
+            read_to_string(round_trip_path).unwrap().trim(),
+            "ATTACKAT0600"
+        );
+    }
+
+    #[test]
+    fn test_polyalphabetic_stride() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let mut cipher = PolyalphabeticCipher::new(alphabet, "", "", "AB", false).unwrap();
+        cipher.base_mut().stride = 2;
+        // 跳过的位置仍然计入密钥位置，因此只用到关键词的第一个字母
+        assert_eq!(cipher.encrypt_string("AAAA"), "BABA");
+
+        let mut cipher = PolyalphabeticCipher::new(alphabet, "", "", "ABC", false).unwrap();
+        cipher.base_mut().stride = 2;
+        assert_eq!(cipher.encrypt_string("AAAA"), "BADA");
+    }
+
+    #[test]
+    fn test_polyalphabetic_cipher_reciprocity() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        assert!(
+            !PolyalphabeticCipher::new(alphabet, "", "", "CAT", false)
+                .unwrap()
+                .is_reciprocal()
+        );
+        // M 对应偏移量13，Z 对应偏移量26（等价于0）
+        assert!(
+            PolyalphabeticCipher::new(alphabet, "", "", "MZM", false)
+                .unwrap()
+                .is_reciprocal()
+        );
+        // Б 对应偏移量2，是四个字母的字母表长度的一半
+        assert!(
+            PolyalphabeticCipher::new("АБВГ", "", "", "Б", false)
+                .unwrap()
+                .is_reciprocal()
+        );
+    }
+
+    #[test]
+    fn test_large_input_round_trip() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let text = crate::benchmark::synthetic_input(alphabet, 100_000, 11);
+        let encrypt = PolyalphabeticCipher::new(alphabet, "", "", "LEMON", false).unwrap();
+        let decrypt = PolyalphabeticCipher::new(alphabet, "", "", "LEMON", true).unwrap();
+        let ciphertext = encrypt.encrypt_string(&text);
+        // 第一个字母按L移动12位（关键词字母的序号加1）
+        let first = text.chars().next().unwrap();
+        let expected = (b'A' + ((first as u8 - b'A' + 12) % 26)) as char;
+        assert_eq!(ciphertext.chars().next(), Some(expected));
+        assert_eq!(decrypt.encrypt_string(&ciphertext), text);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_parallel_matches_serial() {
+        // 保留的空格不消耗密钥，隔位加密时位置也要与串行一致
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let text = crate::benchmark::synthetic_input(alphabet, 50_000, 5).replace("Q", "Q ");
+        let mut cipher = PolyalphabeticCipher::new(alphabet, "", "", "LEMON", false).unwrap();
+        cipher.base_mut().stride = 3;
+        let serial = cipher
+            .base()
+            .map_letters_serial(&text, 0, |ch, i| cipher.encrypt_letter(ch, i));
+        assert_eq!(cipher.encrypt_string(&text), serial);
+        assert_eq!(
+            cipher
+                .base()
+                .map_letters_parallel(&text, 0, |ch, i| cipher.encrypt_letter(ch, i)),
+            serial
+        );
+    }
+
+    #[test]
+    fn test_stream_matches_whole_file() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let text = crate::benchmark::synthetic_input(alphabet, 300_000, 6).replace("Z", "Z, ");
+        let input = NamedTempFile::new().unwrap();
+        let whole = NamedTempFile::new().unwrap();
+        let streamed = NamedTempFile::new().unwrap();
+        std::fs::write(input.path(), &text).unwrap();
+        let input_path = input.path().to_str().unwrap();
+
+        // 密钥位置和自动密钥的明文都要在块之间延续
+        for autokey in [false, true] {
+            for (output, stream) in [(&whole, false), (&streamed, true)] {
+                let output_path = output.path().to_str().unwrap();
+                let mut cipher = if autokey {
+                    PolyalphabeticCipher::with_autokey(
+                        alphabet,
+                        input_path,
+                        output_path,
+                        "LEMON",
+                        false,
+                    )
+                } else {
+                    PolyalphabeticCipher::new(alphabet, input_path, output_path, "LEMON", false)
+                }
+                .unwrap();
+                cipher.base_mut().preserve_non_alpha = true;
+                cipher.base_mut().stream = stream;
+                cipher.encrypt().expect("Encryption failed");
+            }
+            assert_eq!(
+                read_to_string(streamed.path()).unwrap(),
+                read_to_string(whole.path()).unwrap(),
+                "autokey {autokey}"
+            );
+        }
+    }
 }
@@ -0,0 +1,149 @@
+use crate::cipher::{Cipher, CipherBase};
+use std::io;
+
+/// 栅栏密码：把文本沿 `rails` 条栏按之字形上下书写，再逐栏读出。
+/// 一条栏时密文与明文相同；栏数不少于文本长度时每个字符各占一栏，同样原样输出。
+pub struct RailFenceCipher<'a> {
+    base: CipherBase<'a>,
+    rails: usize,
+    decrypt: bool,
+}
+
+impl<'a> RailFenceCipher<'a> {
+    pub fn new(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        rails: usize,
+        decrypt: bool,
+    ) -> io::Result<Self> {
+        if rails == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The number of rails must be at least 1",
+            ));
+        }
+        Ok(RailFenceCipher {
+            base: CipherBase::new(alphabet, input_file, output_file),
+            rails,
+            decrypt,
+        })
+    }
+
+    /// 第 `len` 个字符之前，每个位置所在的栏，按之字形 0, 1, …, rails-1, rails-2, …, 1, 0, 1, … 排列。
+    fn rail_pattern(&self, len: usize) -> Vec<usize> {
+        if self.rails == 1 {
+            return vec![0; len];
+        }
+        let cycle = 2 * (self.rails - 1);
+        (0..len)
+            .map(|i| {
+                let pos = i % cycle;
+                if pos < self.rails { pos } else { cycle - pos }
+            })
+            .collect()
+    }
+
+    /// 每个字符在密文中的位置：先按栏，再按原来的顺序排列。
+    fn read_order(&self, len: usize) -> Vec<usize> {
+        let pattern = self.rail_pattern(len);
+        let mut order: Vec<usize> = (0..len).collect();
+        order.sort_by_key(|&i| pattern[i]);
+        order
+    }
+
+    /// 在内存中加密（或按构造时的设置解密）一段文本。
+    pub fn encrypt_string(&self, text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let order = self.read_order(chars.len());
+        if self.decrypt {
+            let mut plain = vec![' '; chars.len()];
+            for (&pos, &ch) in order.iter().zip(&chars) {
+                plain[pos] = ch;
+            }
+            plain.into_iter().collect()
+        } else {
+            order.into_iter().map(|i| chars[i]).collect()
+        }
+    }
+
+    pub fn base(&self) -> &CipherBase<'a> {
+        &self.base
+    }
+
+    pub fn base_mut(&mut self) -> &mut CipherBase<'a> {
+        &mut self.base
+    }
+
+    pub fn encrypt(&mut self) -> io::Result<()> {
+        self.base.get_text()?;
+        self.base.clean_text();
+
+        self.base.encrypted_text = self.encrypt_string(&self.base.plain_text);
+        self.base.assert_length_preserved();
+
+        self.base.save_file()
+    }
+}
+
+impl Cipher for RailFenceCipher<'_> {
+    fn encrypt(&mut self) -> io::Result<()> {
+        let decrypt = std::mem::replace(&mut self.decrypt, false);
+        let result = RailFenceCipher::encrypt(self);
+        self.decrypt = decrypt;
+        result
+    }
+
+    fn decrypt(&mut self) -> io::Result<()> {
+        let decrypt = std::mem::replace(&mut self.decrypt, true);
+        let result = RailFenceCipher::encrypt(self);
+        self.decrypt = decrypt;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    #[test]
+    fn test_railfence_three_rails() {
+        // W . . . E . . . C . . . R . . . L . . . T . . . E
+        // . E . R . D . S . O . E . E . F . E . A . O . C .
+        // . . A . . . I . . . V . . . D . . . E . . . N . .
+        let plaintext = "WEAREDISCOVEREDFLEEATONCE";
+        let encrypt = RailFenceCipher::new(ALPHABET, "", "", 3, false).unwrap();
+        let decrypt = RailFenceCipher::new(ALPHABET, "", "", 3, true).unwrap();
+        let ciphertext = encrypt.encrypt_string(plaintext);
+        assert_eq!(ciphertext, "WECRLTEERDSOEEFEAOCAIVDEN");
+        assert_eq!(decrypt.encrypt_string(&ciphertext), plaintext);
+    }
+
+    #[test]
+    fn test_railfence_edge_cases() {
+        let plaintext = "ATTACKATDAWN";
+        // 一条栏，或栏数不少于文本长度，都是原样输出
+        for rails in [1, 12, 13, 100] {
+            let cipher = RailFenceCipher::new(ALPHABET, "", "", rails, false).unwrap();
+            assert_eq!(cipher.encrypt_string(plaintext), plaintext, "rails {rails}");
+        }
+        for rails in 1..15 {
+            let encrypt = RailFenceCipher::new(ALPHABET, "", "", rails, false).unwrap();
+            let decrypt = RailFenceCipher::new(ALPHABET, "", "", rails, true).unwrap();
+            assert_eq!(
+                decrypt.encrypt_string(&encrypt.encrypt_string(plaintext)),
+                plaintext,
+                "rails {rails}"
+            );
+        }
+        assert_eq!(
+            RailFenceCipher::new(ALPHABET, "", "", 2, false)
+                .unwrap()
+                .encrypt_string(""),
+            ""
+        );
+        assert!(RailFenceCipher::new(ALPHABET, "", "", 0, false).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use crate::cipher::char_to_index;
+use log::info;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 只变换文本中属于字母表的字符，其余字符留在原位。
+/// 属于字母表的字符会按原顺序拼成一段交给 `f`，因此多表密码的密钥位置是连续的。
+/// 字符按 `char_to_index` 查找，大写字母表也接受小写字符，结果保留原字符的大小写。
+pub fn transform_alphabet_chars<F: Fn(&str) -> String>(alphabet: &str, text: &str, f: F) -> String {
+    let letters: Vec<char> = alphabet.chars().collect();
+    let lookup = |ch: char| char_to_index(alphabet, ch).map(|index| letters[index]);
+    let selected: String = text.chars().filter_map(lookup).collect();
+    let mut transformed = f(&selected).chars().collect::<Vec<char>>().into_iter();
+
+    text.chars()
+        .map(|ch| match lookup(ch) {
+            Some(letter) => {
+                let out = transformed.next().unwrap_or(letter);
+                match ch {
+                    _ if ch == letter => out,
+                    _ if ch.is_lowercase() => out.to_lowercase().next().unwrap_or(out),
+                    _ => out.to_uppercase().next().unwrap_or(out),
+                }
+            }
+            None => ch,
+        })
+        .collect()
+}
+
+/// 计算目录中每个文件的新文件名（只变换文件名主干，保留扩展名），不做任何改动。
+/// 若新文件名之间重复，或与不参与重命名的已有文件冲突，则返回错误。
+pub fn plan_renames<F: Fn(&str) -> String>(
+    dir: &Path,
+    f: F,
+) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut plan: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut unchanged: HashSet<PathBuf> = HashSet::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+
+        let mut new_name = f(stem);
+        if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+            new_name = format!("{new_name}.{extension}");
+        }
+        let target = path.with_file_name(new_name);
+
+        if target == path {
+            unchanged.insert(path);
+        } else {
+            plan.push((path, target));
+        }
+    }
+    plan.sort();
+
+    let mut targets: HashSet<&PathBuf> = HashSet::new();
+    for (source, target) in &plan {
+        if !targets.insert(target) || unchanged.contains(target) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "Renaming {} would collide with {}",
+                    source.display(),
+                    target.display()
+                ),
+            ));
+        }
+    }
+
+    Ok(plan)
+}
+
+/// 执行重命名计划。先全部改为临时文件名再改为目标名，这样文件名互相轮换时也不会覆盖。
+pub fn apply_renames(plan: &[(PathBuf, PathBuf)]) -> io::Result<()> {
+    let mut staged: Vec<(PathBuf, &PathBuf)> = Vec::with_capacity(plan.len());
+    for (i, (source, target)) in plan.iter().enumerate() {
+        let temporary = source.with_file_name(format!(".cipher-rename-{i}"));
+        fs::rename(source, &temporary)?;
+        staged.push((temporary, target));
+    }
+
+    for (temporary, target) in staged {
+        info!("Renaming to {}", target.display());
+        fs::rename(temporary, target)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::caesar::CaesarCipher;
+    use tempfile::TempDir;
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_rename_round_trip() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let dir = TempDir::new().expect("Failed to create temporary directory");
+        for name in ["HELLO.txt", "WORLD.md", "A_1"] {
+            fs::write(dir.path().join(name), name).unwrap();
+        }
+
+        let encrypt = CaesarCipher::new(alphabet, "", "", 3);
+        let plan = plan_renames(dir.path(), |stem| {
+            transform_alphabet_chars(alphabet, stem, |text| encrypt.encrypt_string(text))
+        })
+        .unwrap();
+        apply_renames(&plan).unwrap();
+        assert_eq!(file_names(dir.path()), vec!["D_1", "KHOOR.txt", "ZRUOG.md"]);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("KHOOR.txt")).unwrap(),
+            "HELLO.txt"
+        );
+
+        let decrypt = CaesarCipher::new(alphabet, "", "", -3);
+        let plan = plan_renames(dir.path(), |stem| {
+            transform_alphabet_chars(alphabet, stem, |text| decrypt.encrypt_string(text))
+        })
+        .unwrap();
+        apply_renames(&plan).unwrap();
+        assert_eq!(file_names(dir.path()), vec!["A_1", "HELLO.txt", "WORLD.md"]);
+    }
+
+    #[test]
+    fn test_transform_alphabet_chars_keeps_case() {
+        let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let cipher = CaesarCipher::new(alphabet, "", "", 3);
+        let encrypt =
+            |stem| transform_alphabet_chars(alphabet, stem, |text| cipher.encrypt_string(text));
+
+        // 小写和大小写混合的文件名也会被变换，并保留原来的大小写
+        assert_eq!(encrypt("hello"), "khoor");
+        assert_eq!(encrypt("Hello_World-2"), "Khoor_Zruog-2");
+        assert_eq!(encrypt("HELLO"), "KHOOR");
+    }
+
+    #[test]
+    fn test_rename_collision_is_rejected() {
+        let dir = TempDir::new().expect("Failed to create temporary directory");
+        fs::write(dir.path().join("B.txt"), "").unwrap();
+        fs::write(dir.path().join("C.txt"), "").unwrap();
+
+        // 两个文件映射到同一个名字时，应在改名之前报错
+        let result = plan_renames(dir.path(), |_| "SAME".to_string());
+        assert!(result.is_err());
+        assert_eq!(file_names(dir.path()), vec!["B.txt", "C.txt"]);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::cipher::CipherBase;
+use std::io;
+
+/// 斯巴达密码棒：把文本按行写在周长为 `circumference` 的棒上，再沿棒的方向按列读出。
+/// 最后一行可以不满，读列时跳过空位。
+pub struct ScytaleCipher<'a> {
+    base: CipherBase<'a>,
+    circumference: usize,
+    decrypt: bool,
+}
+
+impl<'a> ScytaleCipher<'a> {
+    pub fn new(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        circumference: usize,
+        decrypt: bool,
+    ) -> io::Result<Self> {
+        if circumference == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The circumference must be at least 1",
+            ));
+        }
+        Ok(ScytaleCipher {
+            base: CipherBase::new(alphabet, input_file, output_file),
+            circumference,
+            decrypt,
+        })
+    }
+
+    /// 每一列的长度。文本长度不能被周长整除时，前面几列比后面的多一个字符。
+    fn column_lengths(&self, len: usize) -> Vec<usize> {
+        let rows = len / self.circumference;
+        let long_columns = len % self.circumference;
+        (0..self.circumference)
+            .map(|col| rows + usize::from(col < long_columns))
+            .collect()
+    }
+
+    /// 在内存中加密（或按构造时的设置解密）一段文本。
+    pub fn encrypt_string(&self, text: &str) -> String {
+        if self.decrypt {
+            self.decrypt_string(text)
+        } else {
+            let chars: Vec<char> = text.chars().collect();
+            (0..self.circumference)
+                .flat_map(|col| chars.iter().skip(col).step_by(self.circumference))
+                .collect()
+        }
+    }
+
+    /// 把按列读出的密文重新按行排列。
+    fn decrypt_string(&self, text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut columns = Vec::with_capacity(self.circumference);
+        let mut start = 0;
+        for len in self.column_lengths(chars.len()) {
+            columns.push(&chars[start..start + len]);
+            start += len;
+        }
+        (0..chars.len())
+            .map(|i| columns[i % self.circumference][i / self.circumference])
+            .collect()
+    }
+
+    pub fn base(&self) -> &CipherBase<'a> {
+        &self.base
+    }
+
+    pub fn base_mut(&mut self) -> &mut CipherBase<'a> {
+        &mut self.base
+    }
+
+    pub fn encrypt(&mut self) -> io::Result<()> {
+        self.base.get_text()?;
+        self.base.clean_text();
+
+        self.base.encrypted_text = self.encrypt_string(&self.base.plain_text);
+        self.base.assert_length_preserved();
+
+        self.base.save_file()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    #[test]
+    fn test_scytale_encrypt() {
+        // 周长4：按行写成 ABCD / EFGH，再按列读出
+        let cipher = ScytaleCipher::new(ALPHABET, "", "", 4, false).unwrap();
+        assert_eq!(cipher.encrypt_string("ABCDEFGH"), "AEBFCGDH");
+        // 最后一行不满：ABCD / EFG
+        assert_eq!(cipher.encrypt_string("ABCDEFG"), "AEBFCGD");
+    }
+
+    #[test]
+    fn test_scytale_round_trip() {
+        let plaintext = "IAMHURTVERYBADLYHELP";
+        for circumference in [1, 2, 3, 4, 5, 6, 7, 19, 20, 25] {
+            let encrypt = ScytaleCipher::new(ALPHABET, "", "", circumference, false).unwrap();
+            let decrypt = ScytaleCipher::new(ALPHABET, "", "", circumference, true).unwrap();
+            let ciphertext = encrypt.encrypt_string(plaintext);
+            assert_eq!(ciphertext.len(), plaintext.len());
+            assert_eq!(
+                decrypt.encrypt_string(&ciphertext),
+                plaintext,
+                "circumference {circumference}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_scytale_zero_circumference_rejected() {
+        assert!(ScytaleCipher::new(ALPHABET, "", "", 0, false).is_err());
+    }
+}
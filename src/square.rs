@@ -0,0 +1,206 @@
+use std::io;
+
+/// 6x6方阵在字母之后补充的数字。
+const SQUARE_DIGITS: &str = "0123456789";
+
+/// 字母对长度为奇数或需要拆开重复字母时插入的填充字母。
+pub const FILLER: char = 'X';
+/// 被填充的字母本身是 `FILLER` 时改用的填充字母。
+pub const ALT_FILLER: char = 'Q';
+
+/// 由关键词打乱的字母方阵，供四方、两方等方阵类密码共用。
+/// 5x5方阵把J并入I；6x6方阵在字母之后加上数字0到9。
+pub struct KeySquare {
+    size: usize,
+    cells: Vec<char>,
+}
+
+impl KeySquare {
+    /// 用关键词生成方阵：先按顺序放入关键词中不重复的字符，再补上其余字符。
+    /// 关键词中不属于方阵的字符被忽略；方阵的字符数必须恰好是 `size * size`，
+    /// 并且要包含填充字母 `FILLER` 和 `ALT_FILLER`。
+    pub fn new(alphabet: &str, keyword: &str, size: usize) -> io::Result<Self> {
+        let letters: Vec<char> = match size {
+            5 => alphabet.chars().filter(|&c| c != 'J').collect(),
+            6 => alphabet.chars().chain(SQUARE_DIGITS.chars()).collect(),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Unsupported square size {size}, expected 5 or 6"),
+                ));
+            }
+        };
+        if letters.len() != size * size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "A {size}x{size} square needs {} characters, the alphabet gives {}",
+                    size * size,
+                    letters.len()
+                ),
+            ));
+        }
+
+        if let Some(filler) = [FILLER, ALT_FILLER]
+            .into_iter()
+            .find(|ch| !letters.contains(ch))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "The square needs the filler letter {filler}, which is not in the alphabet"
+                ),
+            ));
+        }
+
+        let normalize = |ch: char| if size == 5 && ch == 'J' { 'I' } else { ch };
+        Ok(KeySquare {
+            size,
+            cells: keyed_cells(keyword, &letters, normalize),
+        })
+    }
+
+    /// 直接用给定的字符排成方阵，不做任何检查，供测试构造别的字母取舍方式。
+    #[cfg(test)]
+    pub(crate) fn from_cells(size: usize, cells: Vec<char>) -> Self {
+        KeySquare { size, cells }
+    }
+
+    /// 把字符转换成方阵中使用的形式：5x5方阵中J视为I。
+    pub fn normalize(&self, ch: char) -> char {
+        if self.size == 5 && ch == 'J' { 'I' } else { ch }
+    }
+
+    /// 查找字符所在的行和列，不在方阵中时返回 `None`。
+    pub fn position(&self, ch: char) -> Option<(usize, usize)> {
+        let ch = self.normalize(ch);
+        self.cells
+            .iter()
+            .position(|&c| c == ch)
+            .map(|idx| (idx / self.size, idx % self.size))
+    }
+
+    /// 取出指定行列上的字符。
+    pub fn at(&self, row: usize, col: usize) -> char {
+        self.cells[row * self.size + col]
+    }
+
+    /// 把文本整理成可以两两分组的形式：统一字符形式、去掉方阵以外的字符，
+    /// 长度为奇数时在末尾补 `FILLER`。
+    pub fn digraphs(&self, text: &str) -> Vec<char> {
+        let mut letters: Vec<char> = text
+            .chars()
+            .map(|c| self.normalize(c))
+            .filter(|&c| self.cells.contains(&c))
+            .collect();
+        if letters.len() % 2 == 1 {
+            letters.push(FILLER);
+        }
+        letters
+    }
+}
+
+/// 用关键词打乱 `letters`：先按顺序放入关键词中不重复的字符，再补上其余字符。
+/// 关键词先转成大写并经 `normalize` 处理，不属于 `letters` 的字符被忽略。
+pub fn keyed_cells(keyword: &str, letters: &[char], normalize: impl Fn(char) -> char) -> Vec<char> {
+    let mut cells = Vec::with_capacity(letters.len());
+    for ch in keyword
+        .to_ascii_uppercase()
+        .chars()
+        .chain(letters.iter().copied())
+    {
+        let ch = normalize(ch);
+        if letters.contains(&ch) && !cells.contains(&ch) {
+            cells.push(ch);
+        }
+    }
+    cells
+}
+
+/// 分组密码的换位：把一块字符的坐标按维度分行写出（先写全部第一个坐标，
+/// 再写全部第二个坐标……），然后按原来的顺序每 `N` 个数重新组成坐标。
+pub fn fractionate<const N: usize>(coords: &[[usize; N]]) -> Vec<[usize; N]> {
+    let digits: Vec<usize> = (0..N)
+        .flat_map(|dim| coords.iter().map(move |coord| coord[dim]))
+        .collect();
+    digits
+        .chunks(N)
+        .map(|chunk| std::array::from_fn(|i| chunk[i]))
+        .collect()
+}
+
+/// `fractionate` 的逆过程：展开坐标后，第 `d` 段就是原来各字符的第 `d` 个坐标。
+pub fn unfractionate<const N: usize>(coords: &[[usize; N]]) -> Vec<[usize; N]> {
+    let digits: Vec<usize> = coords.iter().flatten().copied().collect();
+    let len = coords.len();
+    (0..len)
+        .map(|i| std::array::from_fn(|dim| digits[dim * len + i]))
+        .collect()
+}
+
+/// 查找字符在方阵中的行和列。调用前文本已经过 `KeySquare::digraphs` 整理。
+pub fn locate(square: &KeySquare, ch: char) -> (usize, usize) {
+    square
+        .position(ch)
+        .unwrap_or_else(|| panic!("Character '{ch}' not found in square"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    #[test]
+    fn test_key_square_5x5() {
+        let square = KeySquare::new(ALPHABET, "example", 5).unwrap();
+        let cells: String = square.cells.iter().collect();
+        assert_eq!(cells, "EXAMPLBCDFGHIKNOQRSTUVWYZ");
+
+        assert_eq!(square.position('E'), Some((0, 0)));
+        assert_eq!(square.position('N'), Some((2, 4)));
+        // J与I共用同一个位置
+        assert_eq!(square.position('J'), square.position('I'));
+        assert_eq!(square.position('1'), None);
+        assert_eq!(square.at(4, 4), 'Z');
+        assert_eq!(square.digraphs("JAM"), vec!['I', 'A', 'M', 'X']);
+    }
+
+    #[test]
+    fn test_key_square_6x6() {
+        let square = KeySquare::new(ALPHABET, "R2D2", 6).unwrap();
+        assert_eq!(square.at(0, 0), 'R');
+        assert_eq!(square.at(0, 1), '2');
+        assert_eq!(square.at(0, 2), 'D');
+        assert_eq!(square.at(5, 5), '9');
+        // 6x6方阵中J有自己的位置
+        assert_ne!(square.position('J'), square.position('I'));
+
+        for row in 0..6 {
+            for col in 0..6 {
+                assert_eq!(square.position(square.at(row, col)), Some((row, col)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_fractionate_round_trip() {
+        // 行号 1 2 0，列号 0 2 1，依次读出后重新两两组合
+        let coords = [[1, 0], [2, 2], [0, 1]];
+        let mixed = fractionate(&coords);
+        assert_eq!(mixed, vec![[1, 2], [0, 0], [2, 1]]);
+        assert_eq!(unfractionate(&mixed), coords);
+
+        let cube = [[0, 1, 2], [2, 2, 0], [1, 0, 1], [0, 0, 2]];
+        assert_eq!(unfractionate(&fractionate(&cube)), cube);
+    }
+
+    #[test]
+    fn test_key_square_rejects_bad_size() {
+        assert!(KeySquare::new(ALPHABET, "", 4).is_err());
+        assert!(KeySquare::new("ABC", "", 5).is_err());
+        // 字母表中没有填充字母时无法补齐字母对
+        assert!(KeySquare::new("ABCDEFGHIJKLMNOPQRSTUVW1YZ", "", 5).is_err());
+        assert!(KeySquare::new("ABCDEFGHIJKLMNOP1RSTUVWXYZ", "", 5).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::cipher::{Cipher, CipherBase};
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+/// 单表代换密码：密钥是字母表的一个排列，字母表中第 `i` 个字符替换为密钥中第 `i` 个字符。
+pub struct SubstitutionCipher<'a> {
+    base: CipherBase<'a>,
+    forward: HashMap<char, char>,
+    inverse: HashMap<char, char>,
+    decrypt: bool,
+}
+
+/// 检查密钥是否是字母表的一个排列：长度相同，没有重复，字母表中的字符都出现。
+pub fn validate_key(alphabet: &str, key: &str) -> io::Result<()> {
+    let expected = alphabet.chars().count();
+    let actual = key.chars().count();
+    if actual != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("The key has {actual} characters, the alphabet has {expected}"),
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    for ch in key.chars() {
+        if !alphabet.contains(ch) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Key character '{ch}' not found in alphabet"),
+            ));
+        }
+        if !seen.insert(ch) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Key character '{ch}' appears more than once"),
+            ));
+        }
+    }
+    Ok(())
+}
+
+impl<'a> SubstitutionCipher<'a> {
+    pub fn new(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        key: &str,
+        decrypt: bool,
+    ) -> io::Result<Self> {
+        validate_key(alphabet, key)?;
+
+        let forward: HashMap<char, char> = alphabet.chars().zip(key.chars()).collect();
+        let inverse = forward.iter().map(|(&plain, &sub)| (sub, plain)).collect();
+
+        Ok(SubstitutionCipher {
+            base: CipherBase::new(alphabet, input_file, output_file),
+            forward,
+            inverse,
+            decrypt,
+        })
+    }
+
+    /// 在内存中加密（或按构造时的设置解密）一段文本。字母表之外的字符原样返回。
+    pub fn encrypt_string(&self, text: &str) -> String {
+        let table = if self.decrypt {
+            &self.inverse
+        } else {
+            &self.forward
+        };
+        text.chars()
+            .map(|ch| table.get(&ch).copied().unwrap_or(ch))
+            .collect()
+    }
+
+    pub fn base(&self) -> &CipherBase<'a> {
+        &self.base
+    }
+
+    pub fn base_mut(&mut self) -> &mut CipherBase<'a> {
+        &mut self.base
+    }
+
+    pub fn encrypt(&mut self) -> io::Result<()> {
+        self.base.get_text()?;
+        self.base.clean_text();
+
+        self.base.encrypted_text = self.encrypt_string(&self.base.plain_text);
+        self.base.assert_length_preserved();
+
+        self.base.save_file()
+    }
+}
+
+impl Cipher for SubstitutionCipher<'_> {
+    fn encrypt(&mut self) -> io::Result<()> {
+        let decrypt = std::mem::replace(&mut self.decrypt, false);
+        let result = SubstitutionCipher::encrypt(self);
+        self.decrypt = decrypt;
+        result
+    }
+
+    fn decrypt(&mut self) -> io::Result<()> {
+        let decrypt = std::mem::replace(&mut self.decrypt, true);
+        let result = SubstitutionCipher::encrypt(self);
+        self.decrypt = decrypt;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    const KEY: &str = "QWERTYUIOPASDFGHJKLZXCVBNM";
+
+    #[test]
+    fn test_substitution_encrypt() {
+        let cipher = SubstitutionCipher::new(ALPHABET, "", "", KEY, false).unwrap();
+        assert_eq!(cipher.encrypt_string("HELLOWORLD"), "ITSSGVGKSR");
+    }
+
+    #[test]
+    fn test_substitution_round_trip() {
+        let plaintext = "THEQUICKBROWNFOXJUMPSOVERTHELAZYDOG";
+        let encrypt = SubstitutionCipher::new(ALPHABET, "", "", KEY, false).unwrap();
+        let decrypt = SubstitutionCipher::new(ALPHABET, "", "", KEY, true).unwrap();
+        let ciphertext = encrypt.encrypt_string(plaintext);
+        assert_ne!(ciphertext, plaintext);
+        assert_eq!(decrypt.encrypt_string(&ciphertext), plaintext);
+    }
+
+    #[test]
+    fn test_substitution_file_round_trip() {
+        use std::fs::read_to_string;
+        use tempfile::NamedTempFile;
+
+        let input_file = NamedTempFile::new().expect("Failed to create temporary input file");
+        let input_path = input_file.path().to_str().unwrap();
+        let output_file = NamedTempFile::new().expect("Failed to create temporary output file");
+        let output_path = output_file.path().to_str().unwrap();
+        let round_trip = NamedTempFile::new().expect("Failed to create temporary output file");
+        let round_trip_path = round_trip.path().to_str().unwrap();
+        std::fs::write(input_path, "ATTACKATDAWN").unwrap();
+
+        let mut cipher =
+            SubstitutionCipher::new(ALPHABET, input_path, output_path, KEY, false).unwrap();
+        cipher.encrypt().unwrap();
+        // 通过 Cipher trait 解密
+        let mut cipher =
+            SubstitutionCipher::new(ALPHABET, output_path, round_trip_path, KEY, false).unwrap();
+        Cipher::decrypt(&mut cipher).unwrap();
+        assert_eq!(read_to_string(round_trip_path).unwrap(), "ATTACKATDAWN");
+    }
+
+    #[test]
+    fn test_invalid_keys_rejected() {
+        // 长度不对、有重复、含字母表之外的字符
+        assert!(validate_key(ALPHABET, "QWERTY").is_err());
+        assert!(validate_key(ALPHABET, "QQERTYUIOPASDFGHJKLZXCVBNM").is_err());
+        assert!(validate_key(ALPHABET, "QWERTYUIOPASDFGHJKLZXCVBN1").is_err());
+        assert!(SubstitutionCipher::new(ALPHABET, "", "", "ABC", false).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::cipher::{Cipher, CipherBase};
+use crate::square::{fractionate, keyed_cells, unfractionate};
+use std::io;
+
+/// 立方体在字母之后补充的第27个符号。
+pub const TRIFID_EXTRA: char = '+';
+
+/// 三分密码：把每个字符换成它在3x3x3立方体中的层号、行号和列号，按周期分块，
+/// 每块依次写出全部层号、行号和列号，然后每三个数组合成新的字符。
+/// 立方体由26个字母加上 `TRIFID_EXTRA` 组成，这个符号也可能出现在密文中。
+pub struct TrifidCipher<'a> {
+    base: CipherBase<'a>,
+    /// 立方体的27个格子，按层、行、列的顺序排列。
+    cells: Vec<char>,
+    /// 每块的字符数，为0时整段文本是一块。
+    period: usize,
+    decrypt: bool,
+}
+
+impl<'a> TrifidCipher<'a> {
+    pub fn new(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        keyword: &str,
+        period: usize,
+        decrypt: bool,
+    ) -> io::Result<Self> {
+        let letters: Vec<char> = alphabet.chars().chain([TRIFID_EXTRA]).collect();
+        if letters.len() != 27 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "A trifid cube needs 26 letters plus '{TRIFID_EXTRA}', the alphabet gives {}",
+                    alphabet.chars().count()
+                ),
+            ));
+        }
+
+        let mut base = CipherBase::new(alphabet, input_file, output_file);
+        // 清理文本时保留立方体中多出的符号，否则含有它的密文无法解密
+        base.preserve_chars = Some(TRIFID_EXTRA.to_string());
+        Ok(TrifidCipher {
+            base,
+            cells: keyed_cells(keyword, &letters, |c| c),
+            period,
+            decrypt,
+        })
+    }
+
+    /// 查找字符在立方体中的层、行、列。
+    fn locate(&self, ch: char) -> [usize; 3] {
+        let idx = self
+            .cells
+            .iter()
+            .position(|&c| c == ch)
+            .unwrap_or_else(|| panic!("Character '{ch}' not found in cube"));
+        [idx / 9, idx / 3 % 3, idx % 3]
+    }
+
+    /// 取出指定坐标上的字符。
+    fn at(&self, [layer, row, col]: [usize; 3]) -> char {
+        self.cells[layer * 9 + row * 3 + col]
+    }
+
+    /// 在内存中加密（或按构造时的设置解密）一段文本。立方体以外的字符被去掉。
+    pub fn encrypt_string(&self, text: &str) -> String {
+        let letters: Vec<char> = text.chars().filter(|c| self.cells.contains(c)).collect();
+        let period = if self.period == 0 {
+            letters.len().max(1)
+        } else {
+            self.period
+        };
+        letters
+            .chunks(period)
+            .flat_map(|block| {
+                let coords: Vec<[usize; 3]> = block.iter().map(|&c| self.locate(c)).collect();
+                let mixed = if self.decrypt {
+                    unfractionate(&coords)
+                } else {
+                    fractionate(&coords)
+                };
+                mixed.into_iter().map(|coord| self.at(coord))
+            })
+            .collect()
+    }
+
+    pub fn base(&self) -> &CipherBase<'a> {
+        &self.base
+    }
+
+    pub fn base_mut(&mut self) -> &mut CipherBase<'a> {
+        &mut self.base
+    }
+
+    pub fn encrypt(&mut self) -> io::Result<()> {
+        self.base.get_text()?;
+        self.base.clean_text();
+
+        self.base.encrypted_text = self.encrypt_string(&self.base.plain_text);
+
+        self.base.save_file()
+    }
+}
+
+impl Cipher for TrifidCipher<'_> {
+    fn encrypt(&mut self) -> io::Result<()> {
+        let decrypt = std::mem::replace(&mut self.decrypt, false);
+        let result = TrifidCipher::encrypt(self);
+        self.decrypt = decrypt;
+        result
+    }
+
+    fn decrypt(&mut self) -> io::Result<()> {
+        let decrypt = std::mem::replace(&mut self.decrypt, true);
+        let result = TrifidCipher::encrypt(self);
+        self.decrypt = decrypt;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    #[test]
+    fn test_trifid_published_example() {
+        // 立方体 FELIXMARD / STBCGHJKN / OPQUVWYZ+，周期为5
+        let encrypt =
+            TrifidCipher::new(ALPHABET, "", "", "FELIX MARIE DELASTELLE", 5, false).unwrap();
+        let decrypt =
+            TrifidCipher::new(ALPHABET, "", "", "FELIX MARIE DELASTELLE", 5, true).unwrap();
+        let ciphertext = encrypt.encrypt_string("AIDETOILECIELTAIDERA");
+        assert_eq!(ciphertext, "FMJFVOISSUFTFPUFEQQC");
+        assert_eq!(decrypt.encrypt_string(&ciphertext), "AIDETOILECIELTAIDERA");
+    }
+
+    #[test]
+    fn test_trifid_round_trip() {
+        // 28个字符不是多数周期的整数倍，最后一块较短
+        let plaintext = "DEFENDTHEEASTWALLOFTHECASTLE";
+        for period in [0, 1, 4, 5, 7, 27, 100] {
+            let encrypt = TrifidCipher::new(ALPHABET, "", "", "KEYWORD", period, false).unwrap();
+            let decrypt = TrifidCipher::new(ALPHABET, "", "", "KEYWORD", period, true).unwrap();
+            let ciphertext = encrypt.encrypt_string(plaintext);
+            assert_eq!(ciphertext.len(), plaintext.len());
+            assert_eq!(
+                decrypt.encrypt_string(&ciphertext),
+                plaintext,
+                "period {period}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_trifid_extra_symbol_survives_file_round_trip() {
+        use tempfile::NamedTempFile;
+
+        let input = NamedTempFile::new().unwrap();
+        let encrypted = NamedTempFile::new().unwrap();
+        let decrypted = NamedTempFile::new().unwrap();
+        std::fs::write(input.path(), "ATTACK + AT DAWN").unwrap();
+        let input_path = input.path().to_str().unwrap();
+        let encrypted_path = encrypted.path().to_str().unwrap();
+        let decrypted_path = decrypted.path().to_str().unwrap();
+
+        TrifidCipher::new(ALPHABET, input_path, encrypted_path, "ZEBRA", 5, false)
+            .unwrap()
+            .encrypt()
+            .unwrap();
+        TrifidCipher::new(ALPHABET, encrypted_path, decrypted_path, "ZEBRA", 5, false)
+            .unwrap()
+            .decrypt()
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(decrypted.path()).unwrap(),
+            "ATTACK+ATDAWN"
+        );
+    }
+
+    #[test]
+    fn test_trifid_needs_26_letters() {
+        assert!(TrifidCipher::new("ABC", "", "", "KEY", 5, false).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::cipher::CipherBase;
+use crate::square::{KeySquare, locate};
+use std::io;
+
+/// 两方密码中两个方阵的摆放方式。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TwoSquareVariant {
+    /// 第一个方阵在左，第二个方阵在右。
+    Horizontal,
+    /// 第一个方阵在上，第二个方阵在下。
+    Vertical,
+}
+
+/// 两方密码：用两个关键词方阵加密字母对。
+/// 第一个字母在第一个方阵中查找，第二个字母在第二个方阵中查找。
+pub struct TwoSquareCipher<'a> {
+    base: CipherBase<'a>,
+    first_square: KeySquare,
+    second_square: KeySquare,
+    variant: TwoSquareVariant,
+    decrypt: bool,
+}
+
+impl<'a> TwoSquareCipher<'a> {
+    pub fn new(
+        alphabet: &'a str,
+        input_file: &'a str,
+        output_file: &'a str,
+        keyword1: &str,
+        keyword2: &str,
+        variant: TwoSquareVariant,
+        decrypt: bool,
+    ) -> io::Result<Self> {
+        Ok(TwoSquareCipher {
+            base: CipherBase::new(alphabet, input_file, output_file),
+            first_square: KeySquare::new(alphabet, keyword1, 5)?,
+            second_square: KeySquare::new(alphabet, keyword2, 5)?,
+            variant,
+            decrypt,
+        })
+    }
+
+    /// 加密一对字母，取两个字母所在矩形的另外两个角。
+    /// 横排时两字母同行，结果是原字母对的倒序；竖排时两字母同列，结果与原字母对相同。
+    fn encrypt_pair(&self, first: char, second: char) -> (char, char) {
+        let (r1, c1) = locate(&self.first_square, first);
+        let (r2, c2) = locate(&self.second_square, second);
+        match self.variant {
+            TwoSquareVariant::Horizontal => {
+                (self.second_square.at(r1, c2), self.first_square.at(r2, c1))
+            }
+            TwoSquareVariant::Vertical => {
+                (self.first_square.at(r1, c2), self.second_square.at(r2, c1))
+            }
+        }
+    }
+
+    /// 解密一对字母，是 `encrypt_pair` 的逆过程。
+    fn decrypt_pair(&self, first: char, second: char) -> (char, char) {
+        let ((r1, c2), (r2, c1)) = match self.variant {
+            TwoSquareVariant::Horizontal => (
+                locate(&self.second_square, first),
+                locate(&self.first_square, second),
+            ),
+            TwoSquareVariant::Vertical => (
+                locate(&self.first_square, first),
+                locate(&self.second_square, second),
+            ),
+        };
+        (self.first_square.at(r1, c1), self.second_square.at(r2, c2))
+    }
+
+    /// 在内存中加密（或按构造时的设置解密）一段文本。
+    pub fn encrypt_string(&self, text: &str) -> String {
+        self.first_square
+            .digraphs(text)
+            .chunks(2)
+            .flat_map(|pair| {
+                let (a, b) = if self.decrypt {
+                    self.decrypt_pair(pair[0], pair[1])
+                } else {
+                    self.encrypt_pair(pair[0], pair[1])
+                };
+                [a, b]
+            })
+            .collect()
+    }
+
+    pub fn base(&self) -> &CipherBase<'a> {
+        &self.base
+    }
+
+    pub fn base_mut(&mut self) -> &mut CipherBase<'a> {
+        &mut self.base
+    }
+
+    pub fn encrypt(&mut self) -> std::io::Result<()> {
+        self.base.get_text()?;
+        self.base.clean_text();
+
+        self.base.encrypted_text = self.encrypt_string(&self.base.plain_text);
+
+        self.base.save_file()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    fn round_trip(variant: TwoSquareVariant, plaintext: &str) -> (String, String) {
+        let encrypt =
+            TwoSquareCipher::new(ALPHABET, "", "", "EXAMPLE", "KEYWORD", variant, false).unwrap();
+        let decrypt =
+            TwoSquareCipher::new(ALPHABET, "", "", "EXAMPLE", "KEYWORD", variant, true).unwrap();
+        let ciphertext = encrypt.encrypt_string(plaintext);
+        let decrypted = decrypt.encrypt_string(&ciphertext);
+        (ciphertext, decrypted)
+    }
+
+    #[test]
+    fn test_two_square_horizontal_round_trip() {
+        let (ciphertext, decrypted) =
+            round_trip(TwoSquareVariant::Horizontal, "HELPMEOBIWANKENOBI");
+        assert_ne!(ciphertext, "HELPMEOBIWANKENOBI");
+        assert_eq!(decrypted, "HELPMEOBIWANKENOBI");
+
+        // E和K分别是两个方阵第一行的字母，同行时结果为倒序
+        assert_eq!(round_trip(TwoSquareVariant::Horizontal, "EK").0, "KE");
+    }
+
+    #[test]
+    fn test_two_square_vertical_round_trip() {
+        let (ciphertext, decrypted) = round_trip(TwoSquareVariant::Vertical, "JUSTATEST");
+        // J并入I，奇数长度补X
+        assert_eq!(decrypted, "IUSTATESTX");
+        assert_ne!(ciphertext, decrypted);
+
+        // E和K都在各自方阵的第一列，同列时结果不变
+        assert_eq!(round_trip(TwoSquareVariant::Vertical, "EK").0, "EK");
+    }
+}